@@ -4,7 +4,7 @@ const COILS_PER_BYTE: usize = 8;
 
 /// Calculate the number of bytes needed to store the given number of coils
 pub const fn bytes_needed(coils: usize) -> usize {
-    (coils + COILS_PER_BYTE - 1) / COILS_PER_BYTE
+    coils.div_ceil(COILS_PER_BYTE)
 }
 
 /// Write coil values to the given byte slice
@@ -72,6 +72,68 @@ pub fn unpack_coils(bytes: &[u8], coils: &mut [Coil]) {
     }
 }
 
+/// Copy `count` packed coils out of `bytes`, starting at coil `start`, into `out`
+///
+/// The coils are written to the start of `out` in the same packed format, so they can be copied
+/// straight into a read response. Unused bits in the last byte written are cleared, and any
+/// unneeded bytes are left unchanged.
+///
+/// # Panics
+///
+/// Panics if `bytes` doesn't hold `start + count` coils, or if `out` is shorter than
+/// `bytes_needed(count)`.
+pub fn get_coils(bytes: &[u8], start: usize, count: usize, out: &mut [u8]) {
+    let out = &mut out[..bytes_needed(count)];
+    let bytes = &bytes[..bytes_needed(start + count)];
+
+    let first_byte = start / COILS_PER_BYTE;
+    let shift = start % COILS_PER_BYTE;
+
+    for (index, byte) in out.iter_mut().enumerate() {
+        let low = bytes[first_byte + index] >> shift;
+
+        // The rest of the bits come from the next byte, if the coils aren't byte aligned
+        let high = match bytes.get(first_byte + index + 1) {
+            Some(next) if shift != 0 => next << (COILS_PER_BYTE - shift),
+            _ => 0,
+        };
+
+        *byte = low | high;
+    }
+
+    let used_bits = count % COILS_PER_BYTE;
+
+    if let (Some(last), true) = (out.last_mut(), used_bits != 0) {
+        *last &= (1 << used_bits) - 1;
+    }
+}
+
+/// Copy `count` packed coils from `coils` into `bytes`, starting at coil `start`
+///
+/// This is the reverse of `get_coils`. Coils in `bytes` outside of the range are left unchanged.
+///
+/// # Panics
+///
+/// Panics if `bytes` doesn't hold `start + count` coils, or if `coils` is shorter than
+/// `bytes_needed(count)`.
+pub fn set_coils(bytes: &mut [u8], start: usize, count: usize, coils: &[u8]) {
+    let bytes = &mut bytes[..bytes_needed(start + count)];
+    let coils = &coils[..bytes_needed(count)];
+
+    for coil_index in 0..count {
+        let value = coils[coil_index / COILS_PER_BYTE] & (1 << (coil_index % COILS_PER_BYTE));
+
+        let target = start + coil_index;
+        let bit_flag: u8 = 1 << (target % COILS_PER_BYTE);
+
+        if value == 0 {
+            bytes[target / COILS_PER_BYTE] &= !bit_flag;
+        } else {
+            bytes[target / COILS_PER_BYTE] |= bit_flag;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -99,6 +161,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::unusual_byte_groupings)]
     fn pack_coils_works() {
         use crate::Coil::*;
 
@@ -128,6 +191,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::unusual_byte_groupings)]
     fn unpack_coils_works() {
         use crate::Coil::*;
 
@@ -152,4 +216,65 @@ mod test {
             &[Off, Off, On, On, On, Off, Off, On, On, Off, Off, On]
         );
     }
+
+    #[test]
+    fn get_coils_works() {
+        let bytes = [0b1010_0101, 0b1111_0000, 0b0000_1111];
+        let out = &mut [0xAA; 3];
+
+        get_coils(&bytes, 0, 8, out);
+        assert_eq!(out, &[0b1010_0101, 0xAA, 0xAA]);
+
+        get_coils(&bytes, 0, 3, out);
+        assert_eq!(out, &[0b0000_0101, 0xAA, 0xAA]);
+
+        get_coils(&bytes, 4, 8, out);
+        assert_eq!(out, &[0b0000_1010, 0xAA, 0xAA]);
+
+        get_coils(&bytes, 6, 12, out);
+        assert_eq!(out, &[0b1100_0010, 0b0000_1111, 0xAA]);
+
+        get_coils(&bytes, 20, 4, out);
+        assert_eq!(out, &[0b0000_0000, 0b0000_1111, 0xAA]);
+
+        get_coils(&bytes, 0, 0, out);
+        assert_eq!(out, &[0b0000_0000, 0b0000_1111, 0xAA]);
+    }
+
+    #[test]
+    fn set_coils_works() {
+        let bytes = &mut [0u8; 3];
+
+        set_coils(bytes, 0, 8, &[0b1010_0101]);
+        assert_eq!(bytes, &[0b1010_0101, 0, 0]);
+
+        set_coils(bytes, 6, 4, &[0b0000_1101]);
+        assert_eq!(bytes, &[0b0110_0101, 0b0000_0011, 0]);
+
+        set_coils(bytes, 12, 12, &[0xFF, 0xFF]);
+        assert_eq!(bytes, &[0b0110_0101, 0b1111_0011, 0xFF]);
+
+        set_coils(bytes, 1, 2, &[0]);
+        assert_eq!(bytes, &[0b0110_0001, 0b1111_0011, 0xFF]);
+    }
+
+    #[test]
+    fn get_and_set_round_trip() {
+        let source = [0x5A, 0xC3, 0x0F, 0xE1];
+
+        for start in 0..8 {
+            for count in 0..=24 {
+                let mut bytes = [0; 4];
+                let mut out = [0; 4];
+
+                set_coils(&mut bytes, start, count, &source);
+                get_coils(&bytes, start, count, &mut out);
+
+                let mut expected = [0; 4];
+                get_coils(&source, 0, count, &mut expected);
+
+                assert_eq!(out, expected, "start {} count {}", start, count);
+            }
+        }
+    }
 }
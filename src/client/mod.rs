@@ -0,0 +1,11 @@
+//! Tools for implementing a MODBUS client (master)
+//!
+//! The clients here don't do any I/O themselves. They encode requests into buffers you send,
+//! and match the packets you receive back to the requests they answer. Time is measured in
+//! ticks that you supply, so they work without an operating system.
+
+mod rtu;
+mod tcp;
+
+pub use rtu::{RetryPolicy, RtuEvent, RtuMaster};
+pub use tcp::{TcpClient, TcpReply, TcpTransaction};
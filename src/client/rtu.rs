@@ -0,0 +1,488 @@
+use crate::pdu::{Request, Response};
+use crate::protocols::{ModbusProtocol, ModbusRtu, ModbusRtuHeader};
+use crate::recv_buffer::RecvBuffer;
+use crate::{Direction, ModbusError};
+
+/// Requests sent to this address go to every device, and aren't answered
+const BROADCAST_ADDRESS: u8 = 0;
+
+// The address before the PDU, and the CRC after it
+const ADDRESS_LENGTH: usize = 1;
+const CRC_LENGTH: usize = 2;
+
+/// When an `RtuMaster` sends a request again
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a request can be sent again after the first attempt fails
+    pub retries: u8,
+
+    /// Also retry after a reply that can't be used, like one with a bad CRC
+    ///
+    /// Otherwise, only requests that time out are retried. Exception responses are never
+    /// retried, since the device did answer.
+    pub retry_invalid: bool,
+}
+
+impl RetryPolicy {
+    /// Never send a request again
+    pub const NEVER: Self = RetryPolicy {
+        retries: 0,
+        retry_invalid: false,
+    };
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::NEVER
+    }
+}
+
+/// Something that happened to the request sent by an `RtuMaster`
+#[derive(Debug, PartialEq)]
+pub enum RtuEvent<'a> {
+    /// A valid response to the request
+    ///
+    /// Exception responses are included, as `Response::Exception`. `pdu` is the response PDU
+    /// that `response` was parsed from, for forwarding it as it is.
+    Response {
+        response: Response<'a>,
+        pdu: &'a [u8],
+    },
+
+    /// The request is being retried, so send this ADU again
+    Resend(&'a [u8]),
+
+    /// No response arrived in time, and there are no retries left
+    TimedOut,
+
+    /// The reply couldn't be used, and there are no retries left
+    ///
+    /// This is the error from receiving the reply, like `CrcMismatch`, or `ResponseMismatch` if
+    /// it came from the wrong address or doesn't answer the request.
+    Invalid(ModbusError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Ready to send a request
+    Idle,
+
+    /// Waiting for a response to the request sent at `sent_at`
+    Waiting { sent_at: u64, attempts: u8 },
+
+    /// Waiting for the line to go quiet after an invalid reply, before sending the request again
+    Backoff { since: u64, attempts: u8 },
+
+    /// Waiting for the devices to handle a broadcast sent at `sent_at`
+    Turnaround { sent_at: u64 },
+}
+
+/// A MODBUS RTU master (client) for a serial line
+///
+/// Only one request can be outstanding on a serial line, so the master sends a request with
+/// `send` and then waits for its response before the next one can be sent. Pass the bytes you
+/// receive to `receive`, and call `poll` regularly so that timeouts and retries can happen. The
+/// master doesn't do any I/O itself: it gives you the ADU to send, and tells you what happened
+/// with an `RtuEvent`.
+///
+/// A response has to come from the address the request was sent to, and answer the request
+/// (see `Response::parse_for`). Bytes received while no request is outstanding are ignored.
+///
+/// Requests to the broadcast address (0) aren't answered. Instead, the master waits for the
+/// turnaround delay, to give the devices time to handle the request, before it's ready to send
+/// another one. The turnaround delay is also waited before retrying after an invalid reply, to
+/// let the line go quiet.
+///
+/// Times are measured in ticks, which can be any unit of time as long as all of the timestamps
+/// agree. The timestamp passed to `send` should be when the request finished sending.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::client::*;
+/// use modbus_core::pdu::Response;
+///
+/// // Time out after 1000 ticks, with a 100 tick turnaround delay
+/// let mut master = RtuMaster::new(1000, 100);
+///
+/// let adu = master.send(0x11, &[0x03, 0x00, 0x6b, 0x00, 0x01], 0).unwrap();
+/// assert_eq!(adu, &[0x11, 0x03, 0x00, 0x6b, 0x00, 0x01, 0xf7, 0x46]);
+///
+/// // The response arrives in two pieces
+/// assert_eq!(master.receive(&[0x11, 0x03, 0x02], 10), None);
+///
+/// match master.receive(&[0x12, 0x34, 0x74, 0xf0], 20) {
+///     Some(RtuEvent::Response { response: Response::ReadHoldingRegisters { registers }, .. }) => {
+///         assert_eq!(registers.get(0), Some(0x1234));
+///     }
+///     event => panic!("Unexpected event: {:?}", event),
+/// }
+///
+/// assert!(master.is_ready(20));
+/// ```
+pub struct RtuMaster {
+    response_timeout: u64,
+    turnaround_delay: u64,
+    retry_policy: RetryPolicy,
+    state: State,
+    buffer: RecvBuffer<ModbusRtu>,
+    adu: <ModbusRtu as ModbusProtocol>::Buffer,
+    adu_length: usize,
+}
+
+impl RtuMaster {
+    /// Create a master that waits `response_timeout` ticks for a response, and
+    /// `turnaround_delay` ticks after a broadcast
+    ///
+    /// Requests aren't retried by default. See `set_retry_policy`.
+    pub fn new(response_timeout: u64, turnaround_delay: u64) -> Self {
+        RtuMaster {
+            response_timeout,
+            turnaround_delay,
+            retry_policy: RetryPolicy::NEVER,
+            state: State::Idle,
+            buffer: RecvBuffer::with_direction(Direction::Response),
+            adu: [0; ModbusRtu::ADU_MAX_LENGTH],
+            adu_length: 0,
+        }
+    }
+
+    /// The time to wait for a response, in ticks
+    pub fn response_timeout(&self) -> u64 {
+        self.response_timeout
+    }
+
+    /// The time to wait after a broadcast, in ticks
+    pub fn turnaround_delay(&self) -> u64 {
+        self.turnaround_delay
+    }
+
+    /// Set when requests are sent again
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// When requests are sent again
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Whether a new request can be sent at time `now`
+    pub fn is_ready(&self, now: u64) -> bool {
+        match self.state {
+            State::Idle => true,
+            State::Turnaround { sent_at } => now.wrapping_sub(sent_at) >= self.turnaround_delay,
+            _ => false,
+        }
+    }
+
+    /// Start a request to `address`, sent at time `now`. Returns the ADU to send.
+    ///
+    /// - If the master isn't ready for a new request, returns `Err(TooManyTransactions)`
+    /// - If `address` is reserved (above 247), returns `Err(BadValue)`
+    /// - If `pdu` isn't a valid request, returns the error from `Request::parse`
+    pub fn send(&mut self, address: u8, pdu: &[u8], now: u64) -> Result<&[u8], ModbusError> {
+        if !self.is_ready(now) {
+            return Err(ModbusError::TooManyTransactions);
+        }
+
+        if address > ModbusRtu::MAX_ADDRESS {
+            return Err(ModbusError::BadValue);
+        }
+
+        Request::parse(pdu)?;
+
+        let header = ModbusRtuHeader { address, crc: 0 };
+        self.adu_length = ModbusRtu::write_adu(&header, pdu, &mut self.adu)?;
+
+        self.state = if address == BROADCAST_ADDRESS {
+            State::Turnaround { sent_at: now }
+        } else {
+            self.buffer = RecvBuffer::with_direction(Direction::Response);
+            State::Waiting {
+                sent_at: now,
+                attempts: 0,
+            }
+        };
+
+        Ok(&self.adu[..self.adu_length])
+    }
+
+    /// Handle bytes received at time `now`
+    ///
+    /// Returns an event once the response has been received, or a reply turned out to be
+    /// invalid with no retries left.
+    pub fn receive(&mut self, data: &[u8], now: u64) -> Option<RtuEvent<'_>> {
+        let attempts = match self.state {
+            State::Waiting { attempts, .. } => attempts,
+            _ => return None,
+        };
+
+        let request_pdu = &self.adu[ADDRESS_LENGTH..self.adu_length - CRC_LENGTH];
+
+        let error = match self.buffer.process(data) {
+            Err(ModbusError::NotEnoughData) => return None,
+            Err(e) => e,
+            Ok((packet, _)) if packet.header.address != self.adu[0] => {
+                ModbusError::ResponseMismatch
+            }
+            Ok((packet, _)) => match Request::parse(request_pdu)
+                .and_then(|request| Response::parse_for(packet.pdu, &request))
+            {
+                Ok(response) => {
+                    self.state = State::Idle;
+                    return Some(RtuEvent::Response {
+                        response,
+                        pdu: packet.pdu,
+                    });
+                }
+                Err(e) => e,
+            },
+        };
+
+        if self.retry_policy.retry_invalid && attempts < self.retry_policy.retries {
+            self.state = State::Backoff {
+                since: now,
+                attempts,
+            };
+
+            None
+        } else {
+            self.state = State::Idle;
+
+            Some(RtuEvent::Invalid(error))
+        }
+    }
+
+    /// Check for timeouts and retries at time `now`
+    pub fn poll(&mut self, now: u64) -> Option<RtuEvent<'_>> {
+        match self.state {
+            State::Waiting { sent_at, attempts }
+                if now.wrapping_sub(sent_at) >= self.response_timeout =>
+            {
+                if attempts < self.retry_policy.retries {
+                    Some(self.resend(now, attempts + 1))
+                } else {
+                    self.state = State::Idle;
+
+                    Some(RtuEvent::TimedOut)
+                }
+            }
+
+            State::Backoff { since, attempts }
+                if now.wrapping_sub(since) >= self.turnaround_delay =>
+            {
+                Some(self.resend(now, attempts + 1))
+            }
+
+            State::Turnaround { sent_at } if now.wrapping_sub(sent_at) >= self.turnaround_delay => {
+                self.state = State::Idle;
+
+                None
+            }
+
+            _ => None,
+        }
+    }
+
+    /// Give up on the outstanding request, if there is one
+    ///
+    /// This doesn't wait for the turnaround delay, so the line might not be quiet yet.
+    pub fn cancel(&mut self) {
+        self.state = State::Idle;
+    }
+
+    /// Send the request again at time `now`
+    fn resend(&mut self, now: u64, attempts: u8) -> RtuEvent<'_> {
+        self.buffer = RecvBuffer::with_direction(Direction::Response);
+        self.state = State::Waiting {
+            sent_at: now,
+            attempts,
+        };
+
+        RtuEvent::Resend(&self.adu[..self.adu_length])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdu::{Exception, ExceptionCode};
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    fn master(retry_policy: RetryPolicy) -> RtuMaster {
+        let mut master = RtuMaster::new(100, 10);
+        master.set_retry_policy(retry_policy);
+
+        master
+    }
+
+    fn is_response(event: Option<RtuEvent>) -> bool {
+        matches!(event, Some(RtuEvent::Response { .. }))
+    }
+
+    #[test]
+    fn single_outstanding_request() {
+        let mut master = master(RetryPolicy::NEVER);
+
+        assert_eq!(master.send(0x11, ADU3_PDU(), 0), Ok(ADU3_RTU));
+        assert!(!master.is_ready(0));
+        assert_eq!(master.send(0x11, ADU3_PDU(), 0), Err(TooManyTransactions));
+
+        // Byte by byte
+        for &byte in &ADU4_RTU[..ADU4_RTU.len() - 1] {
+            assert_eq!(master.receive(&[byte], 1), None);
+        }
+
+        match master.receive(&ADU4_RTU[ADU4_RTU.len() - 1..], 1) {
+            Some(RtuEvent::Response { response, pdu }) => {
+                assert_eq!(response.function_code(), 0x03);
+                assert_eq!(pdu, ADU4_PDU());
+            }
+            event => panic!("Unexpected event: {:?}", event),
+        }
+
+        assert!(master.is_ready(1));
+
+        // Nothing is outstanding, so this is ignored
+        assert_eq!(master.receive(ADU4_RTU, 2), None);
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let mut master = master(RetryPolicy::NEVER);
+
+        assert_eq!(master.send(248, ADU3_PDU(), 0), Err(BadValue));
+        assert_eq!(master.send(1, &[0x03, 0x00], 0), Err(BadLength));
+        assert!(master.is_ready(0));
+    }
+
+    #[test]
+    fn exception_response() {
+        let mut master = master(RetryPolicy {
+            retries: 3,
+            retry_invalid: true,
+        });
+
+        master.send(0x11, ADU3_PDU(), 0).unwrap();
+
+        let mut adu = [0; 5];
+        let header = ModbusRtuHeader {
+            address: 0x11,
+            crc: 0,
+        };
+        ModbusRtu::write_adu(&header, &[0x83, 0x02], &mut adu).unwrap();
+
+        assert_eq!(
+            master.receive(&adu, 1),
+            Some(RtuEvent::Response {
+                response: Response::Exception(Exception {
+                    function_code: 0x03,
+                    code: ExceptionCode::IllegalDataAddress
+                }),
+                pdu: &[0x83, 0x02]
+            })
+        );
+    }
+
+    #[test]
+    fn timeout_and_retries() {
+        let mut master = master(RetryPolicy {
+            retries: 2,
+            retry_invalid: false,
+        });
+
+        master.send(0x11, ADU3_PDU(), 0).unwrap();
+
+        assert_eq!(master.poll(99), None);
+        assert_eq!(master.poll(100), Some(RtuEvent::Resend(ADU3_RTU)));
+        assert_eq!(master.poll(199), None);
+        assert_eq!(master.poll(200), Some(RtuEvent::Resend(ADU3_RTU)));
+
+        // A partial response from the first attempt is thrown away when retrying
+        assert_eq!(master.receive(&ADU4_RTU[..4], 250), None);
+        assert_eq!(master.poll(300), Some(RtuEvent::TimedOut));
+        assert!(master.is_ready(300));
+
+        // Timestamps can wrap around
+        master.send(0x11, ADU3_PDU(), u64::MAX - 50).unwrap();
+        assert_eq!(master.poll(48), None);
+        assert_eq!(master.poll(49), Some(RtuEvent::Resend(ADU3_RTU)));
+        assert!(is_response(master.receive(ADU4_RTU, 60)));
+    }
+
+    #[test]
+    fn invalid_replies() {
+        let mut corrupted = [0; ADU4_ADU_LENGTH];
+        corrupted.copy_from_slice(ADU4_RTU);
+        corrupted[3] ^= 1;
+
+        let mut other_address = [0; ADU4_ADU_LENGTH];
+        let header = ModbusRtuHeader {
+            address: 0x12,
+            crc: 0,
+        };
+        ModbusRtu::write_adu(&header, ADU4_PDU(), &mut other_address).unwrap();
+
+        // Not retried
+        let mut master = master(RetryPolicy {
+            retries: 2,
+            retry_invalid: false,
+        });
+
+        master.send(0x11, ADU3_PDU(), 0).unwrap();
+        assert!(matches!(
+            master.receive(&corrupted, 1),
+            Some(RtuEvent::Invalid(CrcMismatch { .. }))
+        ));
+
+        master.send(0x11, ADU3_PDU(), 2).unwrap();
+        assert_eq!(
+            master.receive(&other_address, 3),
+            Some(RtuEvent::Invalid(ResponseMismatch))
+        );
+
+        // Retried after the turnaround delay
+        master.set_retry_policy(RetryPolicy {
+            retries: 1,
+            retry_invalid: true,
+        });
+
+        master.send(0x11, ADU3_PDU(), 10).unwrap();
+        assert_eq!(master.receive(&corrupted, 11), None);
+        assert!(!master.is_ready(11));
+        assert_eq!(master.receive(ADU4_RTU, 12), None);
+        assert_eq!(master.poll(20), None);
+        assert_eq!(master.poll(21), Some(RtuEvent::Resend(ADU3_RTU)));
+
+        // Out of retries
+        assert_eq!(
+            master.receive(&other_address, 22),
+            Some(RtuEvent::Invalid(ResponseMismatch))
+        );
+        assert!(master.is_ready(22));
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut master = master(RetryPolicy::NEVER);
+
+        let adu = master.send(0, &[0x06, 0x00, 0x01, 0x00, 0x03], 0).unwrap();
+        assert_eq!(adu[0], 0);
+
+        // Nothing is expected back
+        assert_eq!(master.receive(ADU4_RTU, 1), None);
+
+        assert!(!master.is_ready(9));
+        assert_eq!(master.send(0x11, ADU3_PDU(), 9), Err(TooManyTransactions));
+
+        assert!(master.is_ready(10));
+        assert_eq!(master.poll(10), None);
+        assert!(master.is_ready(10));
+        assert!(master.send(0x11, ADU3_PDU(), 10).is_ok());
+
+        master.cancel();
+        assert!(master.is_ready(10));
+    }
+}
@@ -0,0 +1,522 @@
+use crate::pdu::{Request, Response, PDU_MAX_LENGTH};
+use crate::protocols::{ModbusProtocol, TcpModbus, TcpModbusHeader};
+use crate::recv_buffer::Packet;
+use crate::ModbusError;
+
+/// Identifies a transaction sent by a `TcpClient`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TcpTransaction {
+    pub transaction_id: u16,
+    pub unit_id: u8,
+}
+
+/// What a packet received by a `TcpClient` turned out to be
+#[derive(Debug, PartialEq)]
+pub enum TcpReply<'p> {
+    /// A valid response to an outstanding request
+    ///
+    /// Exception responses are included, as `Response::Exception`. Use `Response::into_result`
+    /// if you only care whether the request succeeded.
+    Response(TcpTransaction, Response<'p>),
+
+    /// A reply to an outstanding request, which doesn't parse or doesn't answer the request
+    ///
+    /// The transaction is finished, so the request should be retried if needed.
+    Invalid(TcpTransaction, ModbusError),
+
+    /// Another reply to a transaction that has already been answered
+    Duplicate(TcpTransaction),
+
+    /// A reply to a transaction that has already timed out
+    Late(TcpTransaction),
+
+    /// A reply that doesn't match any recent transaction
+    Unmatched(TcpTransaction),
+}
+
+/// A request that hasn't been answered yet
+#[derive(Clone, Copy)]
+struct Pending {
+    transaction: TcpTransaction,
+    sent_at: u64,
+    pdu: [u8; PDU_MAX_LENGTH],
+    pdu_length: usize,
+}
+
+/// A transaction that was recently answered or timed out
+#[derive(Clone, Copy)]
+struct Finished {
+    transaction: TcpTransaction,
+    timed_out: bool,
+}
+
+/// A MODBUS TCP client that keeps track of up to `N` outstanding transactions
+///
+/// MODBUS TCP lets a client send more requests before the earlier ones are answered
+/// (pipelining), so that a slow server doesn't hold everything up. Each request gets its own
+/// transaction ID, which the server copies into its response.
+///
+/// `send` picks a transaction ID and writes the request ADU for you to send. Pass each packet
+/// you receive (usually from a `RecvBuffer<TcpModbus>`) to `receive`, which matches it to its
+/// request by transaction ID and unit ID, and checks that it answers the request. Call `poll`
+/// regularly to find requests that have timed out.
+///
+/// Timeouts are measured in ticks, which can be any unit of time as long as the timestamps
+/// given to `send` and `poll` agree. Timestamps are allowed to wrap around.
+///
+/// The last `N` finished transactions are remembered, so that a second reply, or one that
+/// arrives after its request has timed out, can be told apart from a reply that doesn't match
+/// anything.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::client::*;
+/// use modbus_core::pdu::Response;
+/// use modbus_core::protocols::*;
+/// use modbus_core::recv_buffer::*;
+///
+/// // Up to 4 outstanding transactions, timing out after 1000 ticks
+/// let mut client: TcpClient<4> = TcpClient::new(1000);
+/// let mut out = [0; 260];
+///
+/// // Read 1 holding register at address 0x10 from unit 1, then another from unit 2
+/// let (first, _) = client.send(1, &[0x03, 0x00, 0x10, 0x00, 0x01], 0, &mut out).unwrap();
+/// let (second, _) = client.send(2, &[0x03, 0x00, 0x10, 0x00, 0x01], 5, &mut out).unwrap();
+/// assert_eq!(client.outstanding(), 2);
+///
+/// // The second request is answered first
+/// let reply = [0x00, 0x02, 0x00, 0x00, 0x00, 0x05, 0x02, 0x03, 0x02, 0x12, 0x34];
+/// assert_eq!(&reply[..2], &out[..2]);
+///
+/// let mut buf: RecvBuffer<TcpModbus> = RecvBuffer::new();
+/// let (packet, _) = buf.process(&reply).unwrap();
+///
+/// match client.receive(&packet) {
+///     TcpReply::Response(transaction, Response::ReadHoldingRegisters { registers }) => {
+///         assert_eq!(transaction, second);
+///         assert_eq!(registers.get(0), Some(0x1234));
+///     }
+///     reply => panic!("Unexpected reply: {:?}", reply),
+/// }
+///
+/// // The first request is never answered
+/// assert_eq!(client.poll(500), None);
+/// assert_eq!(client.poll(1000), Some(first));
+/// assert_eq!(client.outstanding(), 0);
+/// ```
+pub struct TcpClient<const N: usize> {
+    timeout: u64,
+    next_transaction_id: u16,
+    pending: [Option<Pending>; N],
+    finished: [Option<Finished>; N],
+    next_finished: usize,
+}
+
+impl<const N: usize> TcpClient<N> {
+    /// Create a client where requests time out after `timeout` ticks
+    pub fn new(timeout: u64) -> Self {
+        TcpClient {
+            timeout,
+            next_transaction_id: 1,
+            pending: [None; N],
+            finished: [None; N],
+            next_finished: 0,
+        }
+    }
+
+    /// The timeout for requests, in ticks
+    pub fn timeout(&self) -> u64 {
+        self.timeout
+    }
+
+    /// The number of requests that haven't been answered or timed out yet
+    pub fn outstanding(&self) -> usize {
+        self.pending
+            .iter()
+            .filter(|pending| pending.is_some())
+            .count()
+    }
+
+    /// Write a request ADU for `pdu` into `out`, to be sent to `unit_id` at time `now`
+    ///
+    /// Returns the transaction, and the number of bytes written.
+    ///
+    /// - If `pdu` isn't a valid request, returns the error from `Request::parse`
+    /// - If there are already `N` outstanding transactions, returns `Err(TooManyTransactions)`
+    /// - If `out` is too small, returns `Err(BufferFull)`
+    pub fn send(
+        &mut self,
+        unit_id: u8,
+        pdu: &[u8],
+        now: u64,
+        out: &mut [u8],
+    ) -> Result<(TcpTransaction, usize), ModbusError> {
+        Request::parse(pdu)?;
+
+        let slot = self
+            .pending
+            .iter()
+            .position(Option::is_none)
+            .ok_or(ModbusError::TooManyTransactions)?;
+
+        let transaction = TcpTransaction {
+            transaction_id: self.free_transaction_id(),
+            unit_id,
+        };
+
+        let header = TcpModbusHeader {
+            transaction_id: transaction.transaction_id,
+            protocol_id: 0,
+            length: (pdu.len() + 1) as u16,
+            unit_id,
+        };
+
+        let length = TcpModbus::write_adu(&header, pdu, out)?;
+
+        let mut pending = Pending {
+            transaction,
+            sent_at: now,
+            pdu: [0; PDU_MAX_LENGTH],
+            pdu_length: pdu.len(),
+        };
+        pending.pdu[..pdu.len()].copy_from_slice(pdu);
+
+        self.pending[slot] = Some(pending);
+        self.next_transaction_id = transaction.transaction_id.wrapping_add(1);
+
+        // Replies to an old transaction with the same ID can't be told apart anymore
+        for finished in self.finished.iter_mut() {
+            if finished.map(|finished| finished.transaction.transaction_id)
+                == Some(transaction.transaction_id)
+            {
+                *finished = None;
+            }
+        }
+
+        Ok((transaction, length))
+    }
+
+    /// Match a received packet to the request it answers
+    ///
+    /// If the packet answers an outstanding request, that transaction is finished, even if the
+    /// response turns out to be invalid.
+    pub fn receive<'p>(&mut self, packet: &Packet<'p, TcpModbus>) -> TcpReply<'p> {
+        let transaction = TcpTransaction {
+            transaction_id: packet.header.transaction_id,
+            unit_id: packet.header.unit_id,
+        };
+
+        let slot = self.pending.iter_mut().find(|pending| {
+            pending.as_ref().map(|pending| pending.transaction) == Some(transaction)
+        });
+
+        if let Some(pending) = slot.and_then(Option::take) {
+            self.finish(transaction, false);
+
+            let request_pdu = &pending.pdu[..pending.pdu_length];
+
+            return match Request::parse(request_pdu)
+                .and_then(|request| Response::parse_for(packet.pdu, &request))
+            {
+                Ok(response) => TcpReply::Response(transaction, response),
+                Err(e) => TcpReply::Invalid(transaction, e),
+            };
+        }
+
+        let finished = self
+            .finished
+            .iter()
+            .flatten()
+            .find(|finished| finished.transaction == transaction);
+
+        match finished {
+            Some(finished) if finished.timed_out => TcpReply::Late(transaction),
+            Some(_) => TcpReply::Duplicate(transaction),
+            None => TcpReply::Unmatched(transaction),
+        }
+    }
+
+    /// Check for a request that has timed out by time `now`
+    ///
+    /// Returns one timed out transaction at a time, so call this until it returns `None`.
+    pub fn poll(&mut self, now: u64) -> Option<TcpTransaction> {
+        let timeout = self.timeout;
+
+        let expired = self
+            .pending
+            .iter_mut()
+            .find(|pending| match pending {
+                Some(pending) => now.wrapping_sub(pending.sent_at) >= timeout,
+                None => false,
+            })?
+            .take()?;
+
+        self.finish(expired.transaction, true);
+
+        Some(expired.transaction)
+    }
+
+    /// Forget all outstanding and finished transactions
+    ///
+    /// Use this when the connection is lost, since none of the outstanding requests will be
+    /// answered on a new connection.
+    pub fn reset(&mut self) {
+        self.pending = [None; N];
+        self.finished = [None; N];
+        self.next_finished = 0;
+    }
+
+    /// Pick the next transaction ID that isn't in use
+    ///
+    /// There are fewer than `N` outstanding transactions when this is called, so this always
+    /// finds one within `N` tries. Nothing changes until `send` has written the request.
+    fn free_transaction_id(&self) -> u16 {
+        let mut transaction_id = self.next_transaction_id;
+
+        while self
+            .pending
+            .iter()
+            .flatten()
+            .any(|pending| pending.transaction.transaction_id == transaction_id)
+        {
+            transaction_id = transaction_id.wrapping_add(1);
+        }
+
+        transaction_id
+    }
+
+    /// Remember a finished transaction, forgetting the oldest one if needed
+    fn finish(&mut self, transaction: TcpTransaction, timed_out: bool) {
+        if N == 0 {
+            return;
+        }
+
+        self.finished[self.next_finished] = Some(Finished {
+            transaction,
+            timed_out,
+        });
+        self.next_finished = (self.next_finished + 1) % N;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdu::{Exception, ExceptionCode};
+    use crate::ModbusError::*;
+
+    const READ_REQUEST: &[u8] = &[0x04, 0x00, 0x00, 0x00, 0x01];
+    const READ_RESPONSE: &[u8] = &[0x04, 0x02, 0xAB, 0xCD];
+
+    fn packet(transaction: TcpTransaction, pdu: &[u8]) -> Packet<'_, TcpModbus> {
+        Packet {
+            pdu,
+            header: TcpModbusHeader {
+                transaction_id: transaction.transaction_id,
+                protocol_id: 0,
+                length: (pdu.len() + 1) as u16,
+                unit_id: transaction.unit_id,
+            },
+        }
+    }
+
+    fn send<const N: usize>(client: &mut TcpClient<N>, unit_id: u8, now: u64) -> TcpTransaction {
+        let mut out = [0; 260];
+
+        client.send(unit_id, READ_REQUEST, now, &mut out).unwrap().0
+    }
+
+    #[test]
+    fn encodes_request() {
+        let mut client: TcpClient<2> = TcpClient::new(10);
+        let mut out = [0; 260];
+
+        let (transaction, length) = client.send(0x11, READ_REQUEST, 0, &mut out).unwrap();
+
+        assert_eq!(
+            &out[..length],
+            &[0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x04, 0x00, 0x00, 0x00, 0x01]
+        );
+        assert_eq!(
+            transaction,
+            TcpTransaction {
+                transaction_id: 1,
+                unit_id: 0x11
+            }
+        );
+
+        assert_eq!(client.send(1, &[0x03, 0x00], 0, &mut out), Err(BadLength));
+
+        // Time the request out, and make the next request reuse its ID
+        assert_eq!(client.poll(10), Some(transaction));
+        client.next_transaction_id = transaction.transaction_id;
+
+        assert_eq!(
+            client.send(1, READ_REQUEST, 0, &mut out[..11]),
+            Err(BufferFull)
+        );
+        assert_eq!(client.outstanding(), 0);
+        assert_eq!(client.next_transaction_id, transaction.transaction_id);
+
+        // Nothing was sent, so the old transaction is still remembered
+        assert_eq!(
+            client.receive(&packet(transaction, READ_RESPONSE)),
+            TcpReply::Late(transaction)
+        );
+    }
+
+    #[test]
+    fn pipelined_replies_out_of_order() {
+        let mut client: TcpClient<3> = TcpClient::new(10);
+
+        let transactions = [
+            send(&mut client, 1, 0),
+            send(&mut client, 2, 0),
+            send(&mut client, 1, 0),
+        ];
+        assert_eq!(client.outstanding(), 3);
+
+        let mut out = [0; 260];
+        assert_eq!(
+            client.send(1, READ_REQUEST, 0, &mut out),
+            Err(TooManyTransactions)
+        );
+
+        for &transaction in transactions.iter().rev() {
+            match client.receive(&packet(transaction, READ_RESPONSE)) {
+                TcpReply::Response(t, response) => {
+                    assert_eq!(t, transaction);
+                    assert_eq!(response.registers().unwrap().get(0), Some(0xABCD));
+                }
+                reply => panic!("Unexpected reply: {:?}", reply),
+            }
+        }
+
+        assert_eq!(client.outstanding(), 0);
+    }
+
+    #[test]
+    fn unmatched_and_duplicate() {
+        let mut client: TcpClient<2> = TcpClient::new(10);
+        let transaction = send(&mut client, 1, 0);
+
+        // Right transaction ID, wrong unit ID
+        let other_unit = TcpTransaction {
+            unit_id: 2,
+            ..transaction
+        };
+        assert_eq!(
+            client.receive(&packet(other_unit, READ_RESPONSE)),
+            TcpReply::Unmatched(other_unit)
+        );
+
+        assert!(matches!(
+            client.receive(&packet(transaction, READ_RESPONSE)),
+            TcpReply::Response(..)
+        ));
+        assert_eq!(
+            client.receive(&packet(transaction, READ_RESPONSE)),
+            TcpReply::Duplicate(transaction)
+        );
+    }
+
+    #[test]
+    fn exceptions_and_invalid_responses() {
+        let mut client: TcpClient<2> = TcpClient::new(10);
+
+        let transaction = send(&mut client, 1, 0);
+        assert_eq!(
+            client.receive(&packet(transaction, &[0x84, 0x02])),
+            TcpReply::Response(
+                transaction,
+                Response::Exception(Exception {
+                    function_code: 0x04,
+                    code: ExceptionCode::IllegalDataAddress
+                })
+            )
+        );
+
+        // Answers a different function code
+        let transaction = send(&mut client, 1, 0);
+        assert_eq!(
+            client.receive(&packet(transaction, &[0x03, 0x02, 0xAB, 0xCD])),
+            TcpReply::Invalid(transaction, ResponseMismatch)
+        );
+
+        // Too many registers
+        let transaction = send(&mut client, 1, 0);
+        assert_eq!(
+            client.receive(&packet(transaction, &[0x04, 0x04, 0, 0, 0, 0])),
+            TcpReply::Invalid(
+                transaction,
+                ByteCountMismatch {
+                    expected: 2,
+                    actual: 4
+                }
+            )
+        );
+
+        assert_eq!(client.outstanding(), 0);
+    }
+
+    #[test]
+    fn timeouts() {
+        let mut client: TcpClient<2> = TcpClient::new(10);
+
+        let first = send(&mut client, 1, u64::MAX - 4);
+        let second = send(&mut client, 1, 0);
+
+        assert_eq!(client.poll(4), None);
+        assert_eq!(client.poll(5), Some(first));
+        assert_eq!(client.poll(5), None);
+        assert_eq!(client.poll(10), Some(second));
+        assert_eq!(client.outstanding(), 0);
+
+        assert_eq!(
+            client.receive(&packet(first, READ_RESPONSE)),
+            TcpReply::Late(first)
+        );
+
+        client.reset();
+        assert_eq!(
+            client.receive(&packet(first, READ_RESPONSE)),
+            TcpReply::Unmatched(first)
+        );
+    }
+
+    #[test]
+    fn transaction_ids_skip_outstanding() {
+        let mut client: TcpClient<2> = TcpClient::new(10);
+        client.next_transaction_id = 0xFFFF;
+
+        let first = send(&mut client, 1, 0);
+        let second = send(&mut client, 1, 0);
+
+        assert_eq!(first.transaction_id, 0xFFFF);
+        assert_eq!(second.transaction_id, 0x0000);
+
+        client.receive(&packet(second, READ_RESPONSE));
+        client.next_transaction_id = 0xFFFF;
+
+        // 0xFFFF is still outstanding, so 0x0000 is reused
+        let third = send(&mut client, 1, 0);
+        assert_eq!(third.transaction_id, 0x0000);
+        assert!(matches!(
+            client.receive(&packet(third, READ_RESPONSE)),
+            TcpReply::Response(..)
+        ));
+    }
+
+    #[test]
+    fn no_transactions() {
+        let mut client: TcpClient<0> = TcpClient::new(10);
+        let mut out = [0; 260];
+
+        assert_eq!(
+            client.send(1, READ_REQUEST, 0, &mut out),
+            Err(TooManyTransactions)
+        );
+        assert_eq!(client.poll(100), None);
+    }
+}
@@ -0,0 +1,262 @@
+//! `tokio-util` codecs for MODBUS protocols, and async helpers built on them
+//!
+//! `ModbusCodec` wraps a `RecvBuffer`, so any `ModbusProtocol` can be used with
+//! `tokio_util::codec::Framed`. It yields owned `Frame`s, which can be kept across `.await`s.
+//!
+//! This module needs the `tokio` feature.
+
+use crate::protocols::{AduBuffer, ModbusProtocol};
+use crate::recv_buffer::{Packet, RecvBuffer};
+use crate::{Direction, ModbusError};
+use bytes::{Buf, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+mod tcp;
+
+pub use tcp::{serve, AsyncTcpClient};
+
+/// An owned ADU: a header and a PDU
+///
+/// This is the owned version of `Packet`.
+pub struct Frame<P: ModbusProtocol> {
+    pub header: P::Header,
+    pub pdu: Vec<u8>,
+}
+
+impl<P: ModbusProtocol> Frame<P> {
+    /// Create a frame from a header and a PDU
+    pub fn new(header: P::Header, pdu: &[u8]) -> Self {
+        Frame {
+            header,
+            pdu: pdu.to_vec(),
+        }
+    }
+
+    /// Borrow this frame as a `Packet`, for functions like `server::dispatch`
+    pub fn as_packet(&self) -> Packet<'_, P> {
+        Packet {
+            header: self.header.clone(),
+            pdu: &self.pdu,
+        }
+    }
+}
+
+impl<'p, P: ModbusProtocol> From<Packet<'p, P>> for Frame<P> {
+    fn from(packet: Packet<'p, P>) -> Self {
+        Frame::new(packet.header, packet.pdu)
+    }
+}
+
+// Derived impls would require the protocol type itself to implement these traits
+impl<P: ModbusProtocol> Clone for Frame<P> {
+    fn clone(&self) -> Self {
+        Frame {
+            header: self.header.clone(),
+            pdu: self.pdu.clone(),
+        }
+    }
+}
+
+impl<P: ModbusProtocol> PartialEq for Frame<P>
+where
+    P::Header: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.pdu == other.pdu && self.header == other.header
+    }
+}
+
+impl<P: ModbusProtocol> core::fmt::Debug for Frame<P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Frame")
+            .field("header", &self.header)
+            .field("pdu", &self.pdu)
+            .finish()
+    }
+}
+
+/// A `Decoder` and `Encoder` for the ADUs of a MODBUS protocol
+///
+/// Decoding uses a `RecvBuffer`, so ADUs are found exactly as they are by `RecvBuffer::process`.
+/// Any error other than `NotEnoughData` is returned as an `io::Error` with kind `InvalidData`,
+/// and the data buffered so far is thrown away.
+///
+/// # Examples
+///
+/// ```
+/// use bytes::BytesMut;
+/// use modbus_core::codec::*;
+/// use modbus_core::protocols::*;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// let mut codec: ModbusCodec<TcpModbus> = ModbusCodec::new();
+/// let mut data = BytesMut::from(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11][..]);
+///
+/// assert!(codec.decode(&mut data).unwrap().is_none());
+///
+/// data.extend_from_slice(&[0x03, 0x00, 0x6b, 0x00, 0x03]);
+/// let frame = codec.decode(&mut data).unwrap().unwrap();
+///
+/// assert_eq!(frame.header.unit_id, 0x11);
+/// assert_eq!(frame.pdu, &[0x03, 0x00, 0x6b, 0x00, 0x03]);
+///
+/// let mut out = BytesMut::new();
+/// codec.encode(frame, &mut out).unwrap();
+///
+/// assert_eq!(&out[..], &[0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03]);
+/// ```
+pub struct ModbusCodec<P: ModbusProtocol> {
+    buffer: RecvBuffer<P>,
+}
+
+impl<P: ModbusProtocol> ModbusCodec<P> {
+    /// Create a codec that decodes ADUs travelling in either direction
+    pub fn new() -> Self {
+        RecvBuffer::new().into()
+    }
+
+    /// Create a codec that decodes ADUs travelling in a known direction
+    ///
+    /// See `RecvBuffer::with_direction`.
+    pub fn with_direction(direction: Direction) -> Self {
+        RecvBuffer::with_direction(direction).into()
+    }
+
+    /// The buffer used for decoding, for changing settings like hunt mode
+    pub fn buffer_mut(&mut self) -> &mut RecvBuffer<P> {
+        &mut self.buffer
+    }
+}
+
+impl<P: ModbusProtocol> Default for ModbusCodec<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: ModbusProtocol> From<RecvBuffer<P>> for ModbusCodec<P> {
+    fn from(buffer: RecvBuffer<P>) -> Self {
+        ModbusCodec { buffer }
+    }
+}
+
+impl<P: ModbusProtocol> Decoder for ModbusCodec<P> {
+    type Item = Frame<P>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // Even with no new data, there can be leftover bytes in the buffer from hunt mode
+        let result = match self.buffer.process(src) {
+            Ok((packet, rest)) => Ok((Frame::from(packet), src.len() - rest.len())),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok((frame, consumed)) => {
+                src.advance(consumed);
+
+                Ok(Some(frame))
+            }
+
+            // All of the data is in the buffer now
+            Err(ModbusError::NotEnoughData) => {
+                src.clear();
+
+                Ok(None)
+            }
+
+            Err(e) => {
+                src.clear();
+
+                Err(e.into())
+            }
+        }
+    }
+}
+
+impl<P: ModbusProtocol> Encoder<Frame<P>> for ModbusCodec<P> {
+    type Error = io::Error;
+
+    fn encode(&mut self, frame: Frame<P>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let mut adu = P::Buffer::zeroed();
+        let length = P::write_adu(&frame.header, &frame.pdu, adu.as_mut())?;
+
+        dst.extend_from_slice(&adu.as_ref()[..length]);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::{ModbusRtu, TcpModbus};
+    use crate::test_data::*;
+
+    #[test]
+    fn decode_split_and_joined() {
+        let mut codec: ModbusCodec<TcpModbus> = ModbusCodec::new();
+
+        let mut data = BytesMut::new();
+        data.extend_from_slice(ADU1_TCP);
+        data.extend_from_slice(ADU2_TCP);
+        data.extend_from_slice(&ADU1_TCP[..10]);
+
+        let first = codec.decode(&mut data).unwrap().unwrap();
+        assert_eq!(first.header, ADU1_HEADER);
+        assert_eq!(first.pdu, ADU1_PDU());
+
+        let second = codec.decode(&mut data).unwrap().unwrap();
+        assert_eq!(second.as_packet().header, ADU2_HEADER);
+        assert_eq!(second.as_packet().pdu, ADU2_PDU());
+
+        assert!(codec.decode(&mut data).unwrap().is_none());
+        assert!(data.is_empty());
+
+        data.extend_from_slice(&ADU1_TCP[10..]);
+        assert_eq!(codec.decode(&mut data).unwrap(), Some(first));
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut codec: ModbusCodec<ModbusRtu> = ModbusCodec::with_direction(Direction::Query);
+
+        let mut data = BytesMut::from(ADU3_RTU);
+        data[2] ^= 1;
+
+        let error = codec.decode(&mut data).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(data.is_empty());
+
+        // Hunt mode finds the next good ADU
+        codec.buffer_mut().set_hunt_mode(true);
+
+        data.extend_from_slice(&[0xFF, 0xFF]);
+        data.extend_from_slice(ADU3_RTU);
+
+        let frame = codec.decode(&mut data).unwrap().unwrap();
+        assert_eq!(frame.header, ADU3_HEADER);
+    }
+
+    #[test]
+    fn encode() {
+        let mut codec: ModbusCodec<ModbusRtu> = ModbusCodec::default();
+        let mut out = BytesMut::new();
+
+        codec
+            .encode(Frame::new(ADU3_HEADER, ADU3_PDU()), &mut out)
+            .unwrap();
+        codec
+            .encode(Frame::new(ADU4_HEADER, ADU4_PDU()), &mut out)
+            .unwrap();
+
+        assert_eq!(&out[..ADU3_ADU_LENGTH], ADU3_RTU);
+        assert_eq!(&out[ADU3_ADU_LENGTH..], ADU4_RTU);
+
+        let error = codec
+            .encode(Frame::new(ADU3_HEADER, &[]), &mut out)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
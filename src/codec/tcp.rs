@@ -0,0 +1,266 @@
+use super::{Frame, ModbusCodec};
+use crate::pdu::{Request, Response, PDU_MAX_LENGTH};
+use crate::protocols::{TcpModbus, TcpModbusHeader};
+use crate::server::{process_request, ModbusDevice};
+use crate::Direction;
+use futures_util::{SinkExt, StreamExt};
+use std::io;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_util::codec::Framed;
+
+/// A simple async MODBUS TCP client, sending one request at a time
+///
+/// For pipelining, or more control over timeouts, use `client::TcpClient` with a `ModbusCodec`
+/// instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use modbus_core::codec::AsyncTcpClient;
+/// use modbus_core::pdu::Response;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let mut client = AsyncTcpClient::connect("192.168.1.10:502").await?;
+///
+/// // Read 2 holding registers at address 0x10 from unit 1
+/// let frame = client.request(1, &[0x03, 0x00, 0x10, 0x00, 0x02]).await?;
+/// let response = Response::parse(&frame.pdu).unwrap();
+///
+/// if let Some(registers) = response.registers() {
+///     println!("{:?}", registers.to_vec());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncTcpClient<S> {
+    framed: Framed<S, ModbusCodec<TcpModbus>>,
+    next_transaction_id: u16,
+}
+
+impl AsyncTcpClient<TcpStream> {
+    /// Connect to a MODBUS TCP server
+    pub async fn connect<A: ToSocketAddrs>(address: A) -> io::Result<Self> {
+        Ok(Self::new(TcpStream::connect(address).await?))
+    }
+}
+
+impl<S> AsyncTcpClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Use an existing connection to a MODBUS TCP server
+    pub fn new(stream: S) -> Self {
+        AsyncTcpClient {
+            framed: Framed::new(stream, ModbusCodec::with_direction(Direction::Response)),
+            next_transaction_id: 1,
+        }
+    }
+
+    /// Send a request PDU to `unit_id`, and wait for the response
+    ///
+    /// Responses to other transactions are skipped. The response is checked with
+    /// `Response::parse_for`, and returned if it answers the request. Exception responses are
+    /// returned too.
+    ///
+    /// Invalid requests and responses are returned as errors with kind `InvalidData`. If the
+    /// connection is closed before the response arrives, returns an error with kind
+    /// `UnexpectedEof`.
+    pub async fn request(&mut self, unit_id: u8, pdu: &[u8]) -> io::Result<Frame<TcpModbus>> {
+        let request = Request::parse(pdu)?;
+
+        let header = TcpModbusHeader {
+            transaction_id: self.next_transaction_id,
+            protocol_id: 0,
+            length: (pdu.len() + 1) as u16,
+            unit_id,
+        };
+        self.next_transaction_id = self.next_transaction_id.wrapping_add(1);
+
+        self.framed.send(Frame::new(header.clone(), pdu)).await?;
+
+        while let Some(frame) = self.framed.next().await {
+            let frame = frame?;
+
+            if frame.header.transaction_id != header.transaction_id
+                || frame.header.unit_id != header.unit_id
+            {
+                continue;
+            }
+
+            Response::parse_for(&frame.pdu, &request)?;
+
+            return Ok(frame);
+        }
+
+        Err(io::ErrorKind::UnexpectedEof.into())
+    }
+
+    /// Get the connection back
+    pub fn into_inner(self) -> S {
+        self.framed.into_inner()
+    }
+}
+
+/// Answer MODBUS TCP requests from a single connection with `device`, until it's closed
+///
+/// Each request is handled with `server::process_request`, and the response is sent with the
+/// request's header. Every unit ID is answered, since the connection is to this device alone.
+/// Serial protocols like `RtuOverTcp` need address filtering and broadcast handling, so they
+/// aren't supported.
+///
+/// Returns `Ok` once the connection is closed, or an error if reading or writing fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use modbus_core::codec::serve;
+/// use modbus_core::server::DataBank;
+/// use tokio::net::TcpListener;
+///
+/// # async fn example() -> std::io::Result<()> {
+/// let listener = TcpListener::bind("0.0.0.0:502").await?;
+/// let mut bank: DataBank<[u8; 8], [u16; 16]> = DataBank::new();
+///
+/// loop {
+///     let (stream, _) = listener.accept().await?;
+///     serve(stream, &mut bank).await?;
+/// }
+/// # }
+/// ```
+pub async fn serve<S, D>(stream: S, device: &mut D) -> io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    D: ModbusDevice + ?Sized,
+{
+    let mut framed = Framed::new(
+        stream,
+        ModbusCodec::<TcpModbus>::with_direction(Direction::Query),
+    );
+    let mut response = [0; PDU_MAX_LENGTH];
+
+    while let Some(frame) = framed.next().await {
+        let frame = frame?;
+
+        // Only an empty PDU fails, and there's no way to answer that
+        let length = match process_request(device, &frame.pdu, &mut response) {
+            Ok(length) => length,
+            Err(_) => continue,
+        };
+
+        framed
+            .send(Frame::new(frame.header, &response[..length]))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdu::{Exception, ExceptionCode};
+    use crate::server::DataBank;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    async fn start_server() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut bank: DataBank<[u8; 2], [u16; 4]> = DataBank::new();
+            bank.input_registers.set(1, 0x1234).unwrap();
+
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                serve(stream, &mut bank).await.unwrap();
+            }
+        });
+
+        address
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let address = start_server().await;
+        let mut client = AsyncTcpClient::connect(address).await.unwrap();
+
+        let frame = client
+            .request(1, &[0x04, 0x00, 0x01, 0x00, 0x01])
+            .await
+            .unwrap();
+        assert_eq!(frame.header.transaction_id, 1);
+        assert_eq!(frame.header.unit_id, 1);
+        assert_eq!(frame.pdu, &[0x04, 0x02, 0x12, 0x34]);
+
+        // Write a register, and read it back
+        client
+            .request(1, &[0x06, 0x00, 0x02, 0xAB, 0xCD])
+            .await
+            .unwrap();
+
+        let frame = client
+            .request(1, &[0x03, 0x00, 0x02, 0x00, 0x01])
+            .await
+            .unwrap();
+        assert_eq!(frame.header.transaction_id, 3);
+        assert_eq!(frame.pdu, &[0x03, 0x02, 0xAB, 0xCD]);
+
+        let frame = client
+            .request(1, &[0x03, 0x00, 0x04, 0x00, 0x01])
+            .await
+            .unwrap();
+        assert_eq!(
+            Response::parse(&frame.pdu),
+            Ok(Response::Exception(Exception {
+                function_code: 0x03,
+                code: ExceptionCode::IllegalDataAddress
+            }))
+        );
+
+        let error = client.request(1, &[0x03, 0x00]).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn skips_other_transactions() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut request = [0; 12];
+            stream.read_exact(&mut request).await.unwrap();
+            assert_eq!(&request[..2], &[0x00, 0x01]);
+
+            // A stray response, then the real one, then hang up after the next request
+            let stray = [
+                0x00, 0x09, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x00, 0x00,
+            ];
+            let real = [
+                0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x01, 0x04, 0x02, 0x56, 0x78,
+            ];
+
+            stream.write_all(&stray).await.unwrap();
+            stream.write_all(&real).await.unwrap();
+
+            stream.read_exact(&mut request).await.unwrap();
+        });
+
+        let mut client = AsyncTcpClient::connect(address).await.unwrap();
+
+        let frame = client
+            .request(1, &[0x04, 0x00, 0x00, 0x00, 0x01])
+            .await
+            .unwrap();
+        assert_eq!(frame.pdu, &[0x04, 0x02, 0x56, 0x78]);
+
+        let error = client
+            .request(1, &[0x04, 0x00, 0x00, 0x00, 0x01])
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
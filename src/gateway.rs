@@ -0,0 +1,718 @@
+//! Tools for implementing a MODBUS TCP to RTU gateway
+//!
+//! A gateway receives requests from MODBUS TCP clients, and forwards them to devices on one or
+//! more serial lines. See the `Gateway` struct for details.
+
+use crate::client::{RtuEvent, RtuMaster};
+use crate::pdu::function_code::EXCEPTION_FLAG;
+use crate::pdu::{Exception, ExceptionCode, Request, PDU_MAX_LENGTH};
+use crate::protocols::{ModbusProtocol, ModbusRtu, TcpModbus, TcpModbusHeader};
+use crate::recv_buffer::Packet;
+use crate::server::request_exception;
+use crate::ModbusError;
+
+/// Where requests for a unit ID are sent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    /// The index of the serial port
+    pub port: usize,
+
+    /// The RTU address of the device on that port
+    ///
+    /// Address 0 broadcasts the request to every device on the port, and no response is sent
+    /// back to the client.
+    pub address: u8,
+}
+
+/// Something the caller of a `Gateway` needs to do
+#[derive(Debug, PartialEq, Eq)]
+pub enum GatewayEvent<'a> {
+    /// Write an RTU ADU to a serial port
+    Serial { port: usize, adu: &'a [u8] },
+
+    /// Send a MBAP ADU back to a TCP client
+    Tcp { connection: usize, adu: &'a [u8] },
+}
+
+/// A TCP request that's being forwarded
+#[derive(Clone, Debug)]
+struct Forwarded {
+    connection: usize,
+    header: TcpModbusHeader,
+    function_code: u8,
+
+    /// The client disconnected, so the response should be thrown away
+    orphaned: bool,
+}
+
+/// A TCP request waiting for its serial port
+#[derive(Clone, Debug)]
+struct Queued {
+    forwarded: Forwarded,
+    address: u8,
+    pdu: [u8; PDU_MAX_LENGTH],
+    pdu_length: usize,
+}
+
+/// A serial port, and the requests for it
+struct Port<const QUEUE: usize> {
+    master: RtuMaster,
+
+    /// The request on the line right now. `None` for broadcasts, since they aren't answered.
+    current: Option<Forwarded>,
+
+    /// Requests waiting for the line, oldest first, with no gaps
+    queue: [Option<Queued>; QUEUE],
+}
+
+/// The core of a MODBUS TCP to RTU gateway, with `PORTS` serial ports that can each have
+/// `QUEUE` requests waiting
+///
+/// Each unit ID is routed to a device on one of the serial ports (see `set_route`). Requests
+/// for a port are sent one at a time by its `RtuMaster`, so requests from different TCP clients
+/// take turns. Responses are sent back to the client with the original MBAP header, so they
+/// have the transaction ID and unit ID the client expects.
+///
+/// The gateway doesn't do any I/O itself. Pass it the requests received from TCP clients with
+/// `request`, the bytes received on each serial port with `receive`, and call `poll` regularly
+/// until it returns `None`. Each of these can return a `GatewayEvent` telling you what to send
+/// where. TCP connections are identified by a number of your choosing.
+///
+/// If the gateway can't forward a request, the client gets an exception response:
+///
+/// - `GatewayPathUnavailable` if there's no route for the unit ID, or the port's queue is full
+/// - `GatewayTargetFailedToRespond` if the device doesn't respond in time, or its response is
+///   invalid
+/// - `IllegalFunction` or `IllegalDataValue` if the request itself is invalid
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::client::RtuMaster;
+/// use modbus_core::gateway::*;
+/// use modbus_core::protocols::*;
+/// use modbus_core::recv_buffer::*;
+///
+/// // One serial port, with room for 4 waiting requests
+/// let mut gateway: Gateway<1, 4> = Gateway::new([RtuMaster::new(1000, 100)]);
+/// gateway.set_route(1, Route { port: 0, address: 0x11 }).unwrap();
+///
+/// // Read 1 holding register from unit 1
+/// let request = [0x12, 0x34, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x6b, 0x00, 0x01];
+/// let mut buf: RecvBuffer<TcpModbus> = RecvBuffer::new();
+/// let (packet, _) = buf.process(&request).unwrap();
+///
+/// assert_eq!(
+///     gateway.request(7, &packet, 0),
+///     Some(GatewayEvent::Serial {
+///         port: 0,
+///         adu: &[0x11, 0x03, 0x00, 0x6b, 0x00, 0x01, 0xf7, 0x46]
+///     })
+/// );
+///
+/// assert_eq!(
+///     gateway.receive(0, &[0x11, 0x03, 0x02, 0x12, 0x34, 0x74, 0xf0], 50),
+///     Some(GatewayEvent::Tcp {
+///         connection: 7,
+///         adu: &[0x12, 0x34, 0x00, 0x00, 0x00, 0x05, 0x01, 0x03, 0x02, 0x12, 0x34]
+///     })
+/// );
+/// ```
+pub struct Gateway<const PORTS: usize, const QUEUE: usize> {
+    routes: [Option<Route>; 256],
+    ports: [Port<QUEUE>; PORTS],
+    adu: <TcpModbus as ModbusProtocol>::Buffer,
+}
+
+impl<const PORTS: usize, const QUEUE: usize> Gateway<PORTS, QUEUE> {
+    /// Create a gateway, with a master for each serial port
+    ///
+    /// The masters' timeouts, turnaround delays, and retry policies are used for their ports.
+    /// No unit IDs are routed yet.
+    pub fn new(masters: [RtuMaster; PORTS]) -> Self {
+        Gateway {
+            routes: [None; 256],
+            ports: masters.map(|master| Port {
+                master,
+                current: None,
+                queue: core::array::from_fn(|_| None),
+            }),
+            adu: [0; TcpModbus::ADU_MAX_LENGTH],
+        }
+    }
+
+    /// Get the route for a unit ID
+    pub fn route(&self, unit_id: u8) -> Option<Route> {
+        self.routes[usize::from(unit_id)]
+    }
+
+    /// Send requests for `unit_id` to `route`
+    ///
+    /// If the port doesn't exist, or the address is reserved (above 247), returns
+    /// `Err(BadValue)`.
+    pub fn set_route(&mut self, unit_id: u8, route: Route) -> Result<(), ModbusError> {
+        if route.port >= PORTS || route.address > ModbusRtu::MAX_ADDRESS {
+            return Err(ModbusError::BadValue);
+        }
+
+        self.routes[usize::from(unit_id)] = Some(route);
+
+        Ok(())
+    }
+
+    /// Stop routing requests for `unit_id`
+    pub fn remove_route(&mut self, unit_id: u8) {
+        self.routes[usize::from(unit_id)] = None;
+    }
+
+    /// Handle a request received from TCP client `connection` at time `now`
+    ///
+    /// Returns the RTU ADU to send if the serial port is free, or an exception response if the
+    /// request can't be forwarded. Otherwise, the request waits for its turn in `poll`.
+    ///
+    /// Requests with an empty PDU can't be answered, and are ignored.
+    pub fn request(
+        &mut self,
+        connection: usize,
+        packet: &Packet<TcpModbus>,
+        now: u64,
+    ) -> Option<GatewayEvent<'_>> {
+        let function_code = *packet.pdu.first()?;
+
+        let forwarded = Forwarded {
+            connection,
+            header: packet.header.clone(),
+            function_code,
+            orphaned: false,
+        };
+
+        let route = match self.routes[usize::from(packet.header.unit_id)] {
+            Some(route) => route,
+            None => {
+                return self.exception(forwarded, ExceptionCode::GatewayPathUnavailable);
+            }
+        };
+
+        if let Err(e) = Request::parse(packet.pdu) {
+            let code = request_exception(e).unwrap_or(ExceptionCode::IllegalDataValue);
+            return self.exception(forwarded, code);
+        }
+
+        let mut queued = Queued {
+            forwarded,
+            address: route.address,
+            pdu: [0; PDU_MAX_LENGTH],
+            pdu_length: packet.pdu.len(),
+        };
+        queued.pdu[..packet.pdu.len()].copy_from_slice(packet.pdu);
+
+        let Gateway { ports, adu, .. } = self;
+        let port = &mut ports[route.port];
+
+        match port.queue.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = Some(queued),
+            None => {
+                let forwarded = queued.forwarded;
+                return write_exception(forwarded, ExceptionCode::GatewayPathUnavailable, adu);
+            }
+        }
+
+        // Send it straight away if nothing else is waiting
+        start_next(route.port, port, now, adu)
+    }
+
+    /// Handle bytes received on serial port `port` at time `now`
+    ///
+    /// Returns the MBAP ADU to send back to the client once the response has been received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the port doesn't exist.
+    pub fn receive(&mut self, port: usize, data: &[u8], now: u64) -> Option<GatewayEvent<'_>> {
+        let Gateway { ports, adu, .. } = self;
+        let port_index = port;
+        let port = &mut ports[port_index];
+
+        let event = port.master.receive(data, now)?;
+
+        handle_event(port_index, &mut port.current, event, adu)
+    }
+
+    /// Check for timeouts, retries, and waiting requests at time `now`
+    ///
+    /// Call this until it returns `None`, since only one event is returned at a time.
+    pub fn poll(&mut self, now: u64) -> Option<GatewayEvent<'_>> {
+        let Gateway { ports, adu, .. } = self;
+
+        for (port_index, port) in ports.iter_mut().enumerate() {
+            if port.master.is_ready(now) && port.queue[0].is_some() {
+                return start_next(port_index, port, now, adu);
+            }
+
+            if let Some(event) = port.master.poll(now) {
+                return handle_event(port_index, &mut port.current, event, adu);
+            }
+        }
+
+        None
+    }
+
+    /// Forget the requests from TCP client `connection`, because it disconnected
+    ///
+    /// Waiting requests are dropped, and responses to requests already sent are thrown away.
+    pub fn disconnect(&mut self, connection: usize) {
+        for port in self.ports.iter_mut() {
+            if let Some(current) = &mut port.current {
+                if current.connection == connection {
+                    current.orphaned = true;
+                }
+            }
+
+            for slot in port.queue.iter_mut() {
+                if slot.as_ref().map(|queued| queued.forwarded.connection) == Some(connection) {
+                    *slot = None;
+                }
+            }
+
+            // Keep the queue in order with no gaps, by moving the empty slots to the end
+            let mut kept = 0;
+
+            for index in 0..QUEUE {
+                if port.queue[index].is_some() {
+                    port.queue.swap(kept, index);
+                    kept += 1;
+                }
+            }
+        }
+    }
+
+    /// Send an exception response for a request that couldn't be forwarded
+    fn exception(&mut self, forwarded: Forwarded, code: ExceptionCode) -> Option<GatewayEvent<'_>> {
+        write_exception(forwarded, code, &mut self.adu)
+    }
+}
+
+/// Send the oldest waiting request on a port, if the line is free
+fn start_next<'a, const QUEUE: usize>(
+    port_index: usize,
+    port: &'a mut Port<QUEUE>,
+    now: u64,
+    adu: &'a mut [u8],
+) -> Option<GatewayEvent<'a>> {
+    if port.current.is_some() || !port.master.is_ready(now) {
+        return None;
+    }
+
+    let queued = port.queue[0].take()?;
+    port.queue.rotate_left(1);
+
+    let pdu = &queued.pdu[..queued.pdu_length];
+
+    match port.master.send(queued.address, pdu, now) {
+        Ok(rtu_adu) => {
+            // Broadcasts aren't answered
+            if queued.address != 0 {
+                port.current = Some(queued.forwarded);
+            }
+
+            Some(GatewayEvent::Serial {
+                port: port_index,
+                adu: rtu_adu,
+            })
+        }
+
+        // The request was checked before it was queued, so this shouldn't happen
+        Err(_) => write_exception(queued.forwarded, ExceptionCode::ServerDeviceFailure, adu),
+    }
+}
+
+/// Turn something that happened on a serial port into an event for the caller
+fn handle_event<'a>(
+    port_index: usize,
+    current: &mut Option<Forwarded>,
+    event: RtuEvent<'a>,
+    adu: &'a mut [u8],
+) -> Option<GatewayEvent<'a>> {
+    let code = match event {
+        RtuEvent::Resend(rtu_adu) => {
+            return Some(GatewayEvent::Serial {
+                port: port_index,
+                adu: rtu_adu,
+            });
+        }
+
+        RtuEvent::Response { pdu, .. } => {
+            let forwarded = current.take()?;
+
+            if forwarded.orphaned {
+                return None;
+            }
+
+            // Every PDU fits in a MBAP ADU, so this can't fail
+            let length = TcpModbus::write_adu(&forwarded.header, pdu, adu).ok()?;
+
+            return Some(GatewayEvent::Tcp {
+                connection: forwarded.connection,
+                adu: &adu[..length],
+            });
+        }
+
+        RtuEvent::TimedOut | RtuEvent::Invalid(_) => ExceptionCode::GatewayTargetFailedToRespond,
+    };
+
+    let forwarded = current.take()?;
+
+    write_exception(forwarded, code, adu)
+}
+
+/// Write an exception response to a forwarded request
+fn write_exception(
+    forwarded: Forwarded,
+    code: ExceptionCode,
+    adu: &mut [u8],
+) -> Option<GatewayEvent<'_>> {
+    if forwarded.orphaned {
+        return None;
+    }
+
+    let mut pdu = [0; Exception::LENGTH];
+
+    let exception = Exception {
+        function_code: forwarded.function_code & !EXCEPTION_FLAG,
+        code,
+    };
+
+    // These are the right size for an exception, so they can't fail
+    exception.write(&mut pdu).ok()?;
+    let length = TcpModbus::write_adu(&forwarded.header, &pdu, adu).ok()?;
+
+    Some(GatewayEvent::Tcp {
+        connection: forwarded.connection,
+        adu: &adu[..length],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::client::RetryPolicy;
+    use crate::protocols::ModbusRtuHeader;
+
+    const READ_REQUEST: &[u8] = &[0x04, 0x00, 0x00, 0x00, 0x01];
+
+    fn header(transaction_id: u16, unit_id: u8) -> TcpModbusHeader {
+        TcpModbusHeader {
+            transaction_id,
+            protocol_id: 0,
+            length: 0,
+            unit_id,
+        }
+    }
+
+    fn request<'p>(header: &TcpModbusHeader, pdu: &'p [u8]) -> Packet<'p, TcpModbus> {
+        Packet {
+            pdu,
+            header: header.clone(),
+        }
+    }
+
+    fn rtu(address: u8, pdu: &[u8]) -> ([u8; 256], usize) {
+        let mut out = [0; 256];
+        let header = ModbusRtuHeader { address, crc: 0 };
+        let length = ModbusRtu::write_adu(&header, pdu, &mut out).unwrap();
+
+        (out, length)
+    }
+
+    fn tcp(header: &TcpModbusHeader, pdu: &[u8]) -> ([u8; 260], usize) {
+        let mut out = [0; 260];
+        let length = TcpModbus::write_adu(header, pdu, &mut out).unwrap();
+
+        (out, length)
+    }
+
+    fn gateway() -> Gateway<2, 2> {
+        let mut gateway = Gateway::new([RtuMaster::new(100, 10), RtuMaster::new(100, 10)]);
+
+        gateway
+            .set_route(
+                1,
+                Route {
+                    port: 0,
+                    address: 0x11,
+                },
+            )
+            .unwrap();
+        gateway
+            .set_route(
+                2,
+                Route {
+                    port: 0,
+                    address: 0x12,
+                },
+            )
+            .unwrap();
+        gateway
+            .set_route(
+                3,
+                Route {
+                    port: 1,
+                    address: 0x11,
+                },
+            )
+            .unwrap();
+        gateway
+            .set_route(
+                0,
+                Route {
+                    port: 1,
+                    address: 0,
+                },
+            )
+            .unwrap();
+
+        gateway
+    }
+
+    fn assert_serial(event: Option<GatewayEvent>, port: usize, address: u8, pdu: &[u8]) {
+        let (adu, length) = rtu(address, pdu);
+
+        assert_eq!(
+            event,
+            Some(GatewayEvent::Serial {
+                port,
+                adu: &adu[..length]
+            })
+        );
+    }
+
+    fn assert_tcp(
+        event: Option<GatewayEvent>,
+        connection: usize,
+        header: &TcpModbusHeader,
+        pdu: &[u8],
+    ) {
+        let (adu, length) = tcp(header, pdu);
+
+        assert_eq!(
+            event,
+            Some(GatewayEvent::Tcp {
+                connection,
+                adu: &adu[..length]
+            })
+        );
+    }
+
+    #[test]
+    fn routes() {
+        let mut gateway = gateway();
+
+        assert_eq!(
+            gateway.route(3),
+            Some(Route {
+                port: 1,
+                address: 0x11
+            })
+        );
+        assert_eq!(gateway.route(4), None);
+
+        assert_eq!(
+            gateway.set_route(
+                4,
+                Route {
+                    port: 2,
+                    address: 1
+                }
+            ),
+            Err(ModbusError::BadValue)
+        );
+        assert_eq!(
+            gateway.set_route(
+                4,
+                Route {
+                    port: 0,
+                    address: 248
+                }
+            ),
+            Err(ModbusError::BadValue)
+        );
+
+        gateway.remove_route(3);
+        assert_eq!(gateway.route(3), None);
+
+        let header = header(1, 3);
+        let event = gateway.request(0, &request(&header, READ_REQUEST), 0);
+        assert_tcp(event, 0, &header, &[0x84, 0x0A]);
+    }
+
+    #[test]
+    fn serializes_each_port() {
+        let mut gateway = gateway();
+        let first = header(100, 1);
+        let second = header(200, 2);
+        let third = header(300, 3);
+
+        let event = gateway.request(5, &request(&first, READ_REQUEST), 0);
+        assert_serial(event, 0, 0x11, READ_REQUEST);
+
+        // Port 0 is busy, but port 1 isn't
+        assert_eq!(gateway.request(6, &request(&second, READ_REQUEST), 0), None);
+
+        let event = gateway.request(6, &request(&third, READ_REQUEST), 0);
+        assert_serial(event, 1, 0x11, READ_REQUEST);
+
+        assert_eq!(gateway.poll(1), None);
+
+        // Responses go back with the original headers
+        let (adu, length) = rtu(0x11, &[0x04, 0x02, 0x12, 0x34]);
+        assert_tcp(
+            gateway.receive(1, &adu[..length], 2),
+            6,
+            &third,
+            &[0x04, 0x02, 0x12, 0x34],
+        );
+        assert_tcp(
+            gateway.receive(0, &adu[..length], 2),
+            5,
+            &first,
+            &[0x04, 0x02, 0x12, 0x34],
+        );
+
+        // Now the second request can go
+        assert_serial(gateway.poll(3), 0, 0x12, READ_REQUEST);
+        assert_eq!(gateway.poll(3), None);
+
+        let (adu, length) = rtu(0x12, &[0x84, 0x02]);
+        assert_tcp(
+            gateway.receive(0, &adu[..length], 4),
+            6,
+            &second,
+            &[0x84, 0x02],
+        );
+    }
+
+    #[test]
+    fn queue_full() {
+        let mut gateway = gateway();
+
+        for transaction_id in 0..3 {
+            gateway.request(0, &request(&header(transaction_id, 1), READ_REQUEST), 0);
+        }
+
+        // One on the line, and two waiting
+        let header = header(3, 2);
+        let event = gateway.request(0, &request(&header, READ_REQUEST), 0);
+        assert_tcp(event, 0, &header, &[0x84, 0x0A]);
+    }
+
+    #[test]
+    fn timeouts_and_bad_responses() {
+        let mut gateway = gateway();
+        let first = header(1, 1);
+        let second = header(2, 1);
+
+        gateway.request(0, &request(&first, READ_REQUEST), 0);
+        gateway.request(0, &request(&second, READ_REQUEST), 0);
+
+        assert_eq!(gateway.poll(99), None);
+        assert_tcp(gateway.poll(100), 0, &first, &[0x84, 0x0B]);
+        assert_serial(gateway.poll(100), 0, 0x11, READ_REQUEST);
+
+        // Bad CRC
+        let (mut adu, length) = rtu(0x11, &[0x04, 0x02, 0x12, 0x34]);
+        adu[3] ^= 1;
+        assert_tcp(
+            gateway.receive(0, &adu[..length], 101),
+            0,
+            &second,
+            &[0x84, 0x0B],
+        );
+    }
+
+    #[test]
+    fn retries() {
+        let mut master = RtuMaster::new(100, 10);
+        master.set_retry_policy(RetryPolicy {
+            retries: 1,
+            retry_invalid: false,
+        });
+
+        let mut gateway: Gateway<1, 1> = Gateway::new([master]);
+        gateway
+            .set_route(
+                9,
+                Route {
+                    port: 0,
+                    address: 9,
+                },
+            )
+            .unwrap();
+
+        let header = header(1, 9);
+        gateway.request(0, &request(&header, READ_REQUEST), 0);
+
+        assert_serial(gateway.poll(100), 0, 9, READ_REQUEST);
+        assert_tcp(gateway.poll(200), 0, &header, &[0x84, 0x0B]);
+    }
+
+    #[test]
+    fn broadcast() {
+        let mut gateway = gateway();
+        let broadcast = header(1, 0);
+        let next = header(2, 3);
+        let write = [0x06, 0x00, 0x01, 0x00, 0x03];
+
+        assert_serial(
+            gateway.request(0, &request(&broadcast, &write), 0),
+            1,
+            0,
+            &write,
+        );
+
+        // No response, but the next request waits for the turnaround delay
+        assert_eq!(gateway.request(0, &request(&next, READ_REQUEST), 1), None);
+        assert_eq!(gateway.poll(9), None);
+        assert_serial(gateway.poll(10), 1, 0x11, READ_REQUEST);
+    }
+
+    #[test]
+    fn bad_requests() {
+        let mut gateway = gateway();
+
+        let header = header(1, 1);
+        let event = gateway.request(0, &request(&header, &[0x30, 0x00]), 0);
+        assert_tcp(event, 0, &header, &[0xB0, 0x01]);
+
+        let event = gateway.request(0, &request(&header, &[0x03, 0x00, 0x00, 0x00, 0x7E]), 0);
+        assert_tcp(event, 0, &header, &[0x83, 0x03]);
+
+        assert_eq!(gateway.request(0, &request(&header, &[]), 0), None);
+    }
+
+    #[test]
+    fn disconnect() {
+        let mut gateway = gateway();
+        let first = header(1, 1);
+        let second = header(2, 1);
+        let third = header(3, 2);
+
+        gateway.request(0, &request(&first, READ_REQUEST), 0);
+        gateway.request(0, &request(&second, READ_REQUEST), 0);
+        gateway.request(1, &request(&third, READ_REQUEST), 0);
+
+        gateway.disconnect(0);
+
+        // The response to the first request is thrown away, and the second is never sent
+        let (adu, length) = rtu(0x11, &[0x04, 0x02, 0x12, 0x34]);
+        assert_eq!(gateway.receive(0, &adu[..length], 1), None);
+        assert_serial(gateway.poll(1), 0, 0x12, READ_REQUEST);
+
+        let (adu, length) = rtu(0x12, &[0x04, 0x02, 0x12, 0x34]);
+        assert_tcp(
+            gateway.receive(0, &adu[..length], 2),
+            1,
+            &third,
+            &[0x04, 0x02, 0x12, 0x34],
+        );
+    }
+}
@@ -1,8 +1,20 @@
-//#![no_std]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
 
 pub mod bit_pack;
+pub mod client;
+#[cfg(feature = "tokio")]
+pub mod codec;
+pub mod gateway;
+pub mod pdu;
 pub mod protocols;
 pub mod recv_buffer;
+pub mod registers;
+pub mod rtu_timing;
+#[cfg(feature = "embedded-io")]
+pub mod serial;
+pub mod server;
+#[cfg(feature = "std")]
+pub mod stream;
 
 #[cfg(test)]
 mod test_data;
@@ -13,22 +25,29 @@ pub enum Coil {
     Off,
 }
 
+/// The direction a MODBUS message is travelling in
+///
+/// Queries are sent from a client (master) to a server (slave), and responses are sent back.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Direction {
     Query,
     Response,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum ModbusError {
     /// Unrecognized function code
     BadFuncCode,
 
     /// Error checking failed
     ///
-    /// This could be a CRC check (for example, for MODBUS RTU), or just correct-length check
+    /// This could be an LRC check (for example, for MODBUS ASCII), or just correct-length check.
+    /// CRC failures are reported as `CrcMismatch`.
     BadErrorCheck,
 
+    /// The CRC at the end of an ADU doesn't match the one calculated from its contents
+    CrcMismatch { expected: u16, actual: u16 },
+
     /// Length is either too long or too short
     ///
     /// MODBUS sets the maximum PDU length at 253 characters.
@@ -36,4 +55,143 @@ pub enum ModbusError {
 
     /// There isn't enough data
     NotEnoughData,
+
+    /// The ADU isn't delimited or encoded correctly
+    ///
+    /// For example, a MODBUS ASCII frame that doesn't start with `:` or contains characters that
+    /// aren't hexadecimal digits.
+    BadFraming,
+
+    /// There isn't enough room in the output buffer
+    BufferFull,
+
+    /// A quantity of coils or registers is outside the range allowed for the function code
+    QuantityOutOfRange { fc: u8, quantity: u16 },
+
+    /// A byte count field doesn't match the quantity of data it's supposed to describe
+    ByteCountMismatch { expected: usize, actual: usize },
+
+    /// A field has a value that isn't allowed by the MODBUS specification
+    ///
+    /// For example, a Write Single Coil request with a value other than `0xFF00` or `0x0000`.
+    BadValue,
+
+    /// A response doesn't answer the request it was matched with
+    ///
+    /// For example, the function code is different, or a write response doesn't echo the
+    /// address that was written.
+    ResponseMismatch,
+
+    /// A MBAP header has a protocol ID other than 0, which is the only one MODBUS uses
+    BadProtocolId(u16),
+
+    /// A unit ID is in the reserved range (248 to 254)
+    BadUnitId(u8),
+
+    /// The server sent an exception response
+    ///
+    /// Parsing an exception response isn't an error in itself, so this is only returned by code
+    /// that expects the request to succeed (see `Response::into_result`).
+    Exception(pdu::ExceptionCode),
+
+    /// A client already has as many outstanding transactions as it can track
+    TooManyTransactions,
+}
+
+impl core::fmt::Display for ModbusError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        use ModbusError::*;
+
+        match self {
+            BadFuncCode => f.write_str("unrecognized function code"),
+            BadErrorCheck => f.write_str("error check failed"),
+            CrcMismatch { expected, actual } => write!(
+                f,
+                "CRC mismatch (expected {:#06x}, got {:#06x})",
+                expected, actual
+            ),
+            BadLength => f.write_str("bad length"),
+            NotEnoughData => f.write_str("not enough data"),
+            BadFraming => f.write_str("bad framing"),
+            BufferFull => f.write_str("output buffer is full"),
+            QuantityOutOfRange { fc, quantity } => write!(
+                f,
+                "quantity {} is out of range for function code {}",
+                quantity, fc
+            ),
+            ByteCountMismatch { expected, actual } => write!(
+                f,
+                "byte count mismatch (expected {}, got {})",
+                expected, actual
+            ),
+            BadValue => f.write_str("bad value"),
+            ResponseMismatch => f.write_str("response doesn't match the request"),
+            BadProtocolId(protocol_id) => write!(f, "bad protocol ID {}", protocol_id),
+            BadUnitId(unit_id) => write!(f, "reserved unit ID {}", unit_id),
+            Exception(code) => write!(f, "exception response: {}", code),
+            TooManyTransactions => f.write_str("too many outstanding transactions"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ModbusError {}
+
+#[cfg(feature = "std")]
+impl From<ModbusError> for std::io::Error {
+    fn from(error: ModbusError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdu::ExceptionCode;
+
+    #[test]
+    fn error_display() {
+        let crc = ModbusError::CrcMismatch {
+            expected: 0x4b37,
+            actual: 0x00ff,
+        };
+        assert_eq!(
+            crc.to_string(),
+            "CRC mismatch (expected 0x4b37, got 0x00ff)"
+        );
+
+        let exception = ModbusError::Exception(ExceptionCode::IllegalDataAddress);
+        assert_eq!(
+            exception.to_string(),
+            "exception response: illegal data address"
+        );
+
+        let unknown = ModbusError::Exception(ExceptionCode::Unknown(0x42));
+        assert_eq!(
+            unknown.to_string(),
+            "exception response: unknown exception code 66"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn error_into_io_error() {
+        let errors = [
+            ModbusError::CrcMismatch {
+                expected: 0x4b37,
+                actual: 0x00ff,
+            },
+            ModbusError::Exception(ExceptionCode::ServerDeviceBusy),
+        ];
+
+        for &error in &errors {
+            let io_error = std::io::Error::from(error);
+
+            assert_eq!(io_error.kind(), std::io::ErrorKind::InvalidData);
+            assert_eq!(io_error.to_string(), error.to_string());
+
+            let inner = io_error.into_inner().unwrap();
+            assert_eq!(inner.downcast_ref::<ModbusError>(), Some(&error));
+        }
+    }
 }
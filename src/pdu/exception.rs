@@ -0,0 +1,242 @@
+use super::function_code::EXCEPTION_FLAG;
+use crate::ModbusError;
+
+/// The reason a server gave for rejecting a request
+///
+/// Exception codes that aren't defined by the MODBUS specification are kept as `Unknown`, so
+/// that they can still be reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ExceptionCode {
+    /// The function code isn't supported by the server (code 1)
+    IllegalFunction,
+
+    /// The data address isn't valid for the server (code 2)
+    IllegalDataAddress,
+
+    /// A value in the request isn't allowed (code 3)
+    IllegalDataValue,
+
+    /// The server failed while trying to perform the action (code 4)
+    ServerDeviceFailure,
+
+    /// The request was accepted, but will take a long time to process (code 5)
+    Acknowledge,
+
+    /// The server is busy with a long-running command (code 6)
+    ServerDeviceBusy,
+
+    /// The server found a parity error in its extended file memory (code 8)
+    MemoryParityError,
+
+    /// A gateway couldn't find a path to the target device (code 10)
+    GatewayPathUnavailable,
+
+    /// A gateway's target device didn't respond (code 11)
+    GatewayTargetFailedToRespond,
+
+    /// An exception code not defined by the MODBUS specification
+    Unknown(u8),
+}
+
+impl From<u8> for ExceptionCode {
+    fn from(code: u8) -> Self {
+        use ExceptionCode::*;
+
+        match code {
+            0x01 => IllegalFunction,
+            0x02 => IllegalDataAddress,
+            0x03 => IllegalDataValue,
+            0x04 => ServerDeviceFailure,
+            0x05 => Acknowledge,
+            0x06 => ServerDeviceBusy,
+            0x08 => MemoryParityError,
+            0x0A => GatewayPathUnavailable,
+            0x0B => GatewayTargetFailedToRespond,
+            code => Unknown(code),
+        }
+    }
+}
+
+impl From<ExceptionCode> for u8 {
+    fn from(code: ExceptionCode) -> Self {
+        use ExceptionCode::*;
+
+        match code {
+            IllegalFunction => 0x01,
+            IllegalDataAddress => 0x02,
+            IllegalDataValue => 0x03,
+            ServerDeviceFailure => 0x04,
+            Acknowledge => 0x05,
+            ServerDeviceBusy => 0x06,
+            MemoryParityError => 0x08,
+            GatewayPathUnavailable => 0x0A,
+            GatewayTargetFailedToRespond => 0x0B,
+            Unknown(code) => code,
+        }
+    }
+}
+
+impl core::fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        use ExceptionCode::*;
+
+        match self {
+            IllegalFunction => f.write_str("illegal function"),
+            IllegalDataAddress => f.write_str("illegal data address"),
+            IllegalDataValue => f.write_str("illegal data value"),
+            ServerDeviceFailure => f.write_str("server device failure"),
+            Acknowledge => f.write_str("acknowledge"),
+            ServerDeviceBusy => f.write_str("server device busy"),
+            MemoryParityError => f.write_str("memory parity error"),
+            GatewayPathUnavailable => f.write_str("gateway path unavailable"),
+            GatewayTargetFailedToRespond => f.write_str("gateway target device failed to respond"),
+            Unknown(code) => write!(f, "unknown exception code {}", code),
+        }
+    }
+}
+
+/// An exception response PDU
+///
+/// On the wire, this is the function code of the rejected request with its high bit set,
+/// followed by an exception code.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::pdu::{Exception, ExceptionCode};
+///
+/// let exception = Exception::parse(&[0x83, 0x02]).unwrap();
+///
+/// assert_eq!(exception.function_code, 0x03);
+/// assert_eq!(exception.code, ExceptionCode::IllegalDataAddress);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Exception {
+    /// The function code of the rejected request, without the exception flag
+    pub function_code: u8,
+
+    pub code: ExceptionCode,
+}
+
+impl Exception {
+    /// The length of an exception PDU
+    pub const LENGTH: usize = 2;
+
+    /// Parse an exception PDU
+    ///
+    /// If the function code doesn't have the exception flag set, returns `Err(BadFuncCode)`. If
+    /// the PDU isn't exactly 2 bytes, returns `Err(BadLength)`.
+    pub fn parse(pdu: &[u8]) -> Result<Self, ModbusError> {
+        use ModbusError::{BadFuncCode, BadLength};
+
+        let function_code = *pdu.first().ok_or(BadLength)?;
+
+        if function_code & EXCEPTION_FLAG == 0 {
+            return Err(BadFuncCode);
+        }
+
+        if pdu.len() != Self::LENGTH {
+            return Err(BadLength);
+        }
+
+        Ok(Exception {
+            function_code: function_code & !EXCEPTION_FLAG,
+            code: pdu[1].into(),
+        })
+    }
+
+    /// Write this exception as a PDU into `out`. Returns the number of bytes written.
+    ///
+    /// If `out` is too small, returns `Err(BufferFull)`.
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, ModbusError> {
+        let out = out.get_mut(..Self::LENGTH).ok_or(ModbusError::BufferFull)?;
+
+        out[0] = self.function_code | EXCEPTION_FLAG;
+        out[1] = self.code.into();
+
+        Ok(Self::LENGTH)
+    }
+}
+
+impl From<ExceptionCode> for ModbusError {
+    fn from(code: ExceptionCode) -> Self {
+        ModbusError::Exception(code)
+    }
+}
+
+impl From<Exception> for ModbusError {
+    fn from(exception: Exception) -> Self {
+        ModbusError::Exception(exception.code)
+    }
+}
+
+/// Check whether a PDU is an exception response
+pub fn is_exception(pdu: &[u8]) -> bool {
+    match pdu.first() {
+        Some(function_code) => function_code & EXCEPTION_FLAG != 0,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ModbusError::{BadFuncCode, BadLength, BufferFull};
+
+    #[test]
+    fn exception_code_round_trip() {
+        for code in 0..=255u8 {
+            assert_eq!(u8::from(ExceptionCode::from(code)), code);
+        }
+
+        assert_eq!(
+            ExceptionCode::from(0x0B),
+            ExceptionCode::GatewayTargetFailedToRespond
+        );
+        assert_eq!(ExceptionCode::from(0x07), ExceptionCode::Unknown(0x07));
+    }
+
+    #[test]
+    fn parse_exception() {
+        assert_eq!(
+            Exception::parse(&[0x81, 0x01]),
+            Ok(Exception {
+                function_code: 0x01,
+                code: ExceptionCode::IllegalFunction
+            })
+        );
+        assert_eq!(
+            Exception::parse(&[0x90, 0x42]),
+            Ok(Exception {
+                function_code: 0x10,
+                code: ExceptionCode::Unknown(0x42)
+            })
+        );
+        assert_eq!(Exception::parse(&[0x03, 0x02]), Err(BadFuncCode));
+        assert_eq!(Exception::parse(&[0x83]), Err(BadLength));
+        assert_eq!(Exception::parse(&[0x83, 0x02, 0x00]), Err(BadLength));
+        assert_eq!(Exception::parse(&[]), Err(BadLength));
+    }
+
+    #[test]
+    fn write_exception() {
+        let exception = Exception {
+            function_code: 0x03,
+            code: ExceptionCode::GatewayPathUnavailable,
+        };
+        let mut out = [0; 4];
+
+        assert_eq!(exception.write(&mut out), Ok(2));
+        assert_eq!(&out[..2], &[0x83, 0x0A]);
+        assert_eq!(Exception::parse(&out[..2]), Ok(exception));
+
+        assert_eq!(exception.write(&mut out[..1]), Err(BufferFull));
+    }
+
+    #[test]
+    fn exception_detection() {
+        assert!(is_exception(&[0x83, 0x02]));
+        assert!(!is_exception(&[0x03, 0x02]));
+        assert!(!is_exception(&[]));
+    }
+}
@@ -0,0 +1,314 @@
+//! Tools for working with MODBUS protocol data units (PDUs).
+//!
+//! The PDU is the part of a MODBUS message that doesn't depend on the transport protocol. It
+//! always starts with a 1-byte function code, followed by data whose layout depends on the
+//! function code and on whether the PDU is a query or a response.
+
+use crate::{Coil, Direction, ModbusError};
+
+mod exception;
+mod request;
+mod response;
+mod values;
+
+pub use exception::{is_exception, Exception, ExceptionCode};
+pub use request::{
+    Request, MAX_READ_BITS, MAX_READ_REGISTERS, MAX_READ_WRITE_REGISTERS, MAX_WRITE_COILS,
+    MAX_WRITE_REGISTERS,
+};
+pub use response::{DeviceIdObjects, Response, MAX_FIFO_COUNT};
+pub use values::{CoilIter, Coils, RegisterIter, Registers};
+
+/// Public function codes defined by the MODBUS application protocol specification
+pub mod function_code {
+    pub const READ_COILS: u8 = 1;
+    pub const READ_DISCRETE_INPUTS: u8 = 2;
+    pub const READ_HOLDING_REGISTERS: u8 = 3;
+    pub const READ_INPUT_REGISTERS: u8 = 4;
+    pub const WRITE_SINGLE_COIL: u8 = 5;
+    pub const WRITE_SINGLE_REGISTER: u8 = 6;
+    pub const READ_EXCEPTION_STATUS: u8 = 7;
+    pub const DIAGNOSTICS: u8 = 8;
+    pub const GET_COMM_EVENT_COUNTER: u8 = 11;
+    pub const GET_COMM_EVENT_LOG: u8 = 12;
+    pub const WRITE_MULTIPLE_COILS: u8 = 15;
+    pub const WRITE_MULTIPLE_REGISTERS: u8 = 16;
+    pub const REPORT_SERVER_ID: u8 = 17;
+    pub const READ_FILE_RECORD: u8 = 20;
+    pub const WRITE_FILE_RECORD: u8 = 21;
+    pub const MASK_WRITE_REGISTER: u8 = 22;
+    pub const READ_WRITE_MULTIPLE_REGISTERS: u8 = 23;
+    pub const READ_FIFO_QUEUE: u8 = 24;
+    pub const ENCAPSULATED_INTERFACE_TRANSPORT: u8 = 43;
+
+    /// Set in the function code of a response to indicate an exception
+    pub const EXCEPTION_FLAG: u8 = 0x80;
+}
+
+/// MEI type for the Read Device Identification function (function code 43)
+const MEI_READ_DEVICE_ID: u8 = 0x0E;
+
+/// The maximum length of a PDU, as set by the MODBUS specification
+pub const PDU_MAX_LENGTH: usize = 253;
+
+/// The value used to turn a coil on in Write Single Coil PDUs
+const COIL_ON: u16 = 0xFF00;
+
+/// The value used to turn a coil off in Write Single Coil PDUs
+const COIL_OFF: u16 = 0x0000;
+
+/// Read a big-endian word at the given index
+///
+/// # Panics
+///
+/// Panics if `pdu` isn't long enough. Only use this once the PDU length has been checked.
+fn word(pdu: &[u8], index: usize) -> u16 {
+    u16::from_be_bytes([pdu[index], pdu[index + 1]])
+}
+
+fn coil_from_u16(value: u16) -> Result<Coil, ModbusError> {
+    match value {
+        COIL_ON => Ok(Coil::On),
+        COIL_OFF => Ok(Coil::Off),
+        _ => Err(ModbusError::BadValue),
+    }
+}
+
+/// Checks that `pdu` is exactly as long as its function code and byte counts say it should be
+fn check_length(pdu: &[u8], direction: Direction) -> Result<(), ModbusError> {
+    use ModbusError::{BadLength, NotEnoughData};
+
+    match pdu_length(pdu, direction) {
+        Ok(length) if length == pdu.len() => Ok(()),
+        Ok(_) | Err(NotEnoughData) => Err(BadLength),
+        Err(e) => Err(e),
+    }
+}
+
+/// Checks that a quantity is in the range `1..=max`
+fn check_quantity(function_code: u8, quantity: u16, max: u16) -> Result<u16, ModbusError> {
+    if (1..=max).contains(&quantity) {
+        Ok(quantity)
+    } else {
+        Err(ModbusError::QuantityOutOfRange {
+            fc: function_code,
+            quantity,
+        })
+    }
+}
+
+/// Checks that a byte count matches what its quantity implies
+fn check_byte_count(expected: usize, actual: usize) -> Result<(), ModbusError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(ModbusError::ByteCountMismatch { expected, actual })
+    }
+}
+
+fn registers(data: &[u8]) -> Result<Registers<'_>, ModbusError> {
+    Registers::new(data).ok_or(ModbusError::BadLength)
+}
+
+fn byte_at(pdu: &[u8], index: usize) -> Result<usize, ModbusError> {
+    pdu.get(index)
+        .map(|&byte| byte as usize)
+        .ok_or(ModbusError::NotEnoughData)
+}
+
+fn word_at(pdu: &[u8], index: usize) -> Result<usize, ModbusError> {
+    Ok(byte_at(pdu, index)? << 8 | byte_at(pdu, index + 1)?)
+}
+
+/// Determine the full length of a PDU from its first few bytes
+///
+/// Many transport protocols (like MODBUS RTU) don't include a length field, so the length has to
+/// be inferred from the function code and any byte count fields. Those fields are in different
+/// places for queries and responses, so the direction must be known.
+///
+/// If `pdu` isn't long enough to determine the length, returns `Err(NotEnoughData)`. `pdu` is
+/// allowed to be longer than the returned length. An unrecognized function code is represented
+/// by `Err(BadFuncCode)`.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::pdu::pdu_length;
+/// use modbus_core::Direction;
+/// use modbus_core::ModbusError::*;
+///
+/// // Read Holding Registers queries are always 5 bytes
+/// assert_eq!(pdu_length(&[0x03], Direction::Query), Ok(5));
+///
+/// // Read Holding Registers responses have a byte count
+/// assert_eq!(pdu_length(&[0x03], Direction::Response), Err(NotEnoughData));
+/// assert_eq!(pdu_length(&[0x03, 0x04], Direction::Response), Ok(6));
+/// ```
+pub fn pdu_length(pdu: &[u8], direction: Direction) -> Result<usize, ModbusError> {
+    use function_code::*;
+    use ModbusError::BadFuncCode;
+
+    let function_code = byte_at(pdu, 0)? as u8;
+
+    if direction == Direction::Response && function_code & EXCEPTION_FLAG != 0 {
+        // Function code and exception code
+        return Ok(2);
+    }
+
+    match (direction, function_code) {
+        (Direction::Query, READ_COILS)
+        | (Direction::Query, READ_DISCRETE_INPUTS)
+        | (Direction::Query, READ_HOLDING_REGISTERS)
+        | (Direction::Query, READ_INPUT_REGISTERS) => Ok(5),
+        (Direction::Response, READ_COILS)
+        | (Direction::Response, READ_DISCRETE_INPUTS)
+        | (Direction::Response, READ_HOLDING_REGISTERS)
+        | (Direction::Response, READ_INPUT_REGISTERS) => Ok(2 + byte_at(pdu, 1)?),
+
+        (_, WRITE_SINGLE_COIL) | (_, WRITE_SINGLE_REGISTER) | (_, DIAGNOSTICS) => Ok(5),
+
+        (Direction::Query, READ_EXCEPTION_STATUS) => Ok(1),
+        (Direction::Response, READ_EXCEPTION_STATUS) => Ok(2),
+
+        (Direction::Query, GET_COMM_EVENT_COUNTER) => Ok(1),
+        (Direction::Response, GET_COMM_EVENT_COUNTER) => Ok(5),
+
+        (Direction::Query, GET_COMM_EVENT_LOG) => Ok(1),
+        (Direction::Response, GET_COMM_EVENT_LOG) => Ok(2 + byte_at(pdu, 1)?),
+
+        (Direction::Query, WRITE_MULTIPLE_COILS) | (Direction::Query, WRITE_MULTIPLE_REGISTERS) => {
+            Ok(6 + byte_at(pdu, 5)?)
+        }
+        (Direction::Response, WRITE_MULTIPLE_COILS)
+        | (Direction::Response, WRITE_MULTIPLE_REGISTERS) => Ok(5),
+
+        (Direction::Query, REPORT_SERVER_ID) => Ok(1),
+        (Direction::Response, REPORT_SERVER_ID) => Ok(2 + byte_at(pdu, 1)?),
+
+        (_, READ_FILE_RECORD) | (_, WRITE_FILE_RECORD) => Ok(2 + byte_at(pdu, 1)?),
+
+        (_, MASK_WRITE_REGISTER) => Ok(7),
+
+        (Direction::Query, READ_WRITE_MULTIPLE_REGISTERS) => Ok(10 + byte_at(pdu, 9)?),
+        (Direction::Response, READ_WRITE_MULTIPLE_REGISTERS) => Ok(2 + byte_at(pdu, 1)?),
+
+        (Direction::Query, READ_FIFO_QUEUE) => Ok(3),
+        (Direction::Response, READ_FIFO_QUEUE) => Ok(3 + word_at(pdu, 1)?),
+
+        (_, ENCAPSULATED_INTERFACE_TRANSPORT) => match byte_at(pdu, 1)? as u8 {
+            MEI_READ_DEVICE_ID => device_id_length(pdu, direction),
+            _ => Err(BadFuncCode),
+        },
+
+        _ => Err(BadFuncCode),
+    }
+}
+
+/// Determine the length of a Read Device Identification PDU
+fn device_id_length(pdu: &[u8], direction: Direction) -> Result<usize, ModbusError> {
+    // Function code, MEI type, Read Device ID code, object ID
+    const QUERY_LENGTH: usize = 4;
+
+    // Function code, MEI type, Read Device ID code, conformity level, more follows, next object
+    // ID, number of objects
+    const RESPONSE_HEADER_LENGTH: usize = 7;
+
+    if direction == Direction::Query {
+        return Ok(QUERY_LENGTH);
+    }
+
+    let object_count = byte_at(pdu, RESPONSE_HEADER_LENGTH - 1)?;
+    let mut length = RESPONSE_HEADER_LENGTH;
+
+    // Each object is an ID, a length, and then that many bytes of value
+    for _ in 0..object_count {
+        length += 2 + byte_at(pdu, length + 1)?;
+
+        if length > PDU_MAX_LENGTH {
+            return Err(ModbusError::BadLength);
+        }
+    }
+
+    Ok(length)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::*;
+    use crate::Direction::*;
+    use crate::ModbusError::*;
+
+    #[test]
+    fn test_data_lengths() {
+        assert_eq!(pdu_length(ADU1_PDU(), ADU1_DIRECTION), Ok(ADU1_PDU().len()));
+        assert_eq!(pdu_length(ADU2_PDU(), ADU2_DIRECTION), Ok(ADU2_PDU().len()));
+        assert_eq!(pdu_length(ADU3_PDU(), ADU3_DIRECTION), Ok(ADU3_PDU().len()));
+        assert_eq!(pdu_length(ADU4_PDU(), ADU4_DIRECTION), Ok(ADU4_PDU().len()));
+    }
+
+    #[test]
+    fn fixed_lengths() {
+        assert_eq!(pdu_length(&[0x01], Query), Ok(5));
+        assert_eq!(pdu_length(&[0x05], Query), Ok(5));
+        assert_eq!(pdu_length(&[0x05], Response), Ok(5));
+        assert_eq!(pdu_length(&[0x07], Query), Ok(1));
+        assert_eq!(pdu_length(&[0x07], Response), Ok(2));
+        assert_eq!(pdu_length(&[0x0B], Response), Ok(5));
+        assert_eq!(pdu_length(&[0x10], Response), Ok(5));
+        assert_eq!(pdu_length(&[0x16], Query), Ok(7));
+        assert_eq!(pdu_length(&[0x18], Query), Ok(3));
+    }
+
+    #[test]
+    fn byte_count_lengths() {
+        assert_eq!(
+            pdu_length(&[0x0F, 0, 0x13, 0, 0x0A], Query),
+            Err(NotEnoughData)
+        );
+        assert_eq!(pdu_length(&[0x0F, 0, 0x13, 0, 0x0A, 2], Query), Ok(8));
+        assert_eq!(
+            pdu_length(&[0x17, 0, 0, 0, 1, 0, 0, 0, 1, 2], Query),
+            Ok(12)
+        );
+        assert_eq!(pdu_length(&[0x17, 2], Response), Ok(4));
+        assert_eq!(pdu_length(&[0x18, 0], Response), Err(NotEnoughData));
+        assert_eq!(pdu_length(&[0x18, 0, 6], Response), Ok(9));
+        assert_eq!(pdu_length(&[0x14, 0x0E], Query), Ok(16));
+    }
+
+    #[test]
+    fn device_id_lengths() {
+        assert_eq!(pdu_length(&[0x2B, 0x0E], Query), Ok(4));
+
+        let response = &[
+            0x2B, 0x0E, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x03, b'a', b'b',
+        ];
+        assert_eq!(pdu_length(&response[..7], Response), Err(NotEnoughData));
+        assert_eq!(pdu_length(&response[..9], Response), Err(NotEnoughData));
+        assert_eq!(pdu_length(response, Response), Err(NotEnoughData));
+
+        let response = &[
+            0x2B, 0x0E, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x01, b'a', 0x01, 0x02, b'b', b'c',
+        ];
+        assert_eq!(pdu_length(response, Response), Ok(14));
+    }
+
+    #[test]
+    fn exception_lengths() {
+        assert_eq!(pdu_length(&[0x83], Response), Ok(2));
+        assert_eq!(pdu_length(&[0xAB], Response), Ok(2));
+        assert_eq!(pdu_length(&[0x83], Query), Err(BadFuncCode));
+    }
+
+    #[test]
+    fn unknown_function_codes() {
+        for &function_code in &[0x00, 0x09, 0x0A, 0x0D, 0x13, 0x19, 0x2A, 0x64] {
+            assert_eq!(pdu_length(&[function_code], Query), Err(BadFuncCode));
+            assert_eq!(pdu_length(&[function_code], Response), Err(BadFuncCode));
+        }
+
+        assert_eq!(pdu_length(&[0x2B, 0x0D], Query), Err(BadFuncCode));
+        assert_eq!(pdu_length(&[], Query), Err(NotEnoughData));
+    }
+}
@@ -0,0 +1,602 @@
+use super::function_code::*;
+use super::{
+    check_byte_count, check_length, check_quantity, coil_from_u16, registers, word, Coils,
+    Registers, MEI_READ_DEVICE_ID,
+};
+use crate::{Coil, Direction, ModbusError};
+
+/// The most coils or discrete inputs that can be read in one request
+pub const MAX_READ_BITS: u16 = 2000;
+
+/// The most registers that can be read in one request
+pub const MAX_READ_REGISTERS: u16 = 125;
+
+/// The most coils that can be written in one request
+pub const MAX_WRITE_COILS: u16 = 1968;
+
+/// The most registers that can be written in one Write Multiple Registers request
+pub const MAX_WRITE_REGISTERS: u16 = 123;
+
+/// The most registers that can be written in one Read/Write Multiple Registers request
+pub const MAX_READ_WRITE_REGISTERS: u16 = 121;
+
+// File record sub-requests always use this reference type
+const FILE_REFERENCE_TYPE: u8 = 6;
+
+// Reference type, file number, record number, record length
+const FILE_SUB_REQUEST_LENGTH: usize = 7;
+
+/// A MODBUS request (query) PDU
+///
+/// This borrows any variable-length data from the PDU it was parsed from, so parsing never
+/// copies. Register data is exposed as `Registers`, which can be iterated as `u16`s, and coil
+/// data as `Coils`, which can be unpacked with `bit_pack::unpack_coils`.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::pdu::Request;
+///
+/// let request = Request::parse(&[0x03, 0x00, 0x6b, 0x00, 0x03]).unwrap();
+///
+/// assert_eq!(
+///     request,
+///     Request::ReadHoldingRegisters {
+///         address: 0x6b,
+///         quantity: 3
+///     }
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Request<'a> {
+    /// Function code 1
+    ReadCoils { address: u16, quantity: u16 },
+
+    /// Function code 2
+    ReadDiscreteInputs { address: u16, quantity: u16 },
+
+    /// Function code 3
+    ReadHoldingRegisters { address: u16, quantity: u16 },
+
+    /// Function code 4
+    ReadInputRegisters { address: u16, quantity: u16 },
+
+    /// Function code 5
+    WriteSingleCoil { address: u16, value: Coil },
+
+    /// Function code 6
+    WriteSingleRegister { address: u16, value: u16 },
+
+    /// Function code 7 (serial line only)
+    ReadExceptionStatus,
+
+    /// Function code 8 (serial line only)
+    Diagnostics { sub_function: u16, data: u16 },
+
+    /// Function code 11 (serial line only)
+    GetCommEventCounter,
+
+    /// Function code 12 (serial line only)
+    GetCommEventLog,
+
+    /// Function code 15
+    WriteMultipleCoils { address: u16, coils: Coils<'a> },
+
+    /// Function code 16
+    WriteMultipleRegisters {
+        address: u16,
+        registers: Registers<'a>,
+    },
+
+    /// Function code 17 (serial line only)
+    ReportServerId,
+
+    /// Function code 20
+    ///
+    /// `sub_requests` holds the raw 7-byte sub-requests, each made up of a reference type (always
+    /// 6), a file number, a record number, and a record length.
+    ReadFileRecord { sub_requests: &'a [u8] },
+
+    /// Function code 21
+    ///
+    /// `sub_requests` holds the raw sub-requests, each made up of a reference type (always 6), a
+    /// file number, a record number, a record length, and that many registers of record data.
+    WriteFileRecord { sub_requests: &'a [u8] },
+
+    /// Function code 22
+    MaskWriteRegister {
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    },
+
+    /// Function code 23
+    ReadWriteMultipleRegisters {
+        read_address: u16,
+        read_quantity: u16,
+        write_address: u16,
+        registers: Registers<'a>,
+    },
+
+    /// Function code 24
+    ReadFifoQueue { address: u16 },
+
+    /// Function code 43, MEI type 14
+    ReadDeviceIdentification {
+        read_device_id_code: u8,
+        object_id: u8,
+    },
+}
+
+impl<'a> Request<'a> {
+    /// Parse a request PDU
+    ///
+    /// The PDU must be exactly one request: if it's shorter or longer than its function code and
+    /// byte count fields say it should be, returns `Err(BadLength)`. Fields are checked against
+    /// the limits in the MODBUS specification:
+    ///
+    /// - An unrecognized function code is represented by `Err(BadFuncCode)`
+    /// - Quantities outside the allowed range are represented by `Err(QuantityOutOfRange)`
+    /// - Byte counts that don't match their quantity are represented by `Err(ByteCountMismatch)`
+    /// - Any other disallowed value (like a coil value other than `0xFF00` or `0x0000`) is
+    ///   represented by `Err(BadValue)`
+    pub fn parse(pdu: &'a [u8]) -> Result<Self, ModbusError> {
+        use ModbusError::BadFuncCode;
+
+        check_length(pdu, Direction::Query)?;
+
+        let function_code = pdu[0];
+
+        let request = match function_code {
+            READ_COILS => Request::ReadCoils {
+                address: word(pdu, 1),
+                quantity: check_quantity(function_code, word(pdu, 3), MAX_READ_BITS)?,
+            },
+            READ_DISCRETE_INPUTS => Request::ReadDiscreteInputs {
+                address: word(pdu, 1),
+                quantity: check_quantity(function_code, word(pdu, 3), MAX_READ_BITS)?,
+            },
+            READ_HOLDING_REGISTERS => Request::ReadHoldingRegisters {
+                address: word(pdu, 1),
+                quantity: check_quantity(function_code, word(pdu, 3), MAX_READ_REGISTERS)?,
+            },
+            READ_INPUT_REGISTERS => Request::ReadInputRegisters {
+                address: word(pdu, 1),
+                quantity: check_quantity(function_code, word(pdu, 3), MAX_READ_REGISTERS)?,
+            },
+            WRITE_SINGLE_COIL => Request::WriteSingleCoil {
+                address: word(pdu, 1),
+                value: coil_from_u16(word(pdu, 3))?,
+            },
+            WRITE_SINGLE_REGISTER => Request::WriteSingleRegister {
+                address: word(pdu, 1),
+                value: word(pdu, 3),
+            },
+            READ_EXCEPTION_STATUS => Request::ReadExceptionStatus,
+            DIAGNOSTICS => Request::Diagnostics {
+                sub_function: word(pdu, 1),
+                data: word(pdu, 3),
+            },
+            GET_COMM_EVENT_COUNTER => Request::GetCommEventCounter,
+            GET_COMM_EVENT_LOG => Request::GetCommEventLog,
+            WRITE_MULTIPLE_COILS => {
+                let quantity = check_quantity(function_code, word(pdu, 3), MAX_WRITE_COILS)?;
+                let expected = crate::bit_pack::bytes_needed(quantity as usize);
+
+                check_byte_count(expected, pdu[5] as usize)?;
+
+                Request::WriteMultipleCoils {
+                    address: word(pdu, 1),
+                    // The byte count was just checked, so this always succeeds
+                    coils: Coils::new(&pdu[6..], quantity as usize)
+                        .ok_or(ModbusError::BadLength)?,
+                }
+            }
+            WRITE_MULTIPLE_REGISTERS => {
+                let quantity = check_quantity(function_code, word(pdu, 3), MAX_WRITE_REGISTERS)?;
+
+                check_byte_count(2 * quantity as usize, pdu[5] as usize)?;
+
+                Request::WriteMultipleRegisters {
+                    address: word(pdu, 1),
+                    registers: registers(&pdu[6..])?,
+                }
+            }
+            REPORT_SERVER_ID => Request::ReportServerId,
+            READ_FILE_RECORD => Request::ReadFileRecord {
+                sub_requests: check_read_file_sub_requests(&pdu[2..])?,
+            },
+            WRITE_FILE_RECORD => Request::WriteFileRecord {
+                sub_requests: check_write_file_sub_requests(&pdu[2..])?,
+            },
+            MASK_WRITE_REGISTER => Request::MaskWriteRegister {
+                address: word(pdu, 1),
+                and_mask: word(pdu, 3),
+                or_mask: word(pdu, 5),
+            },
+            READ_WRITE_MULTIPLE_REGISTERS => {
+                let read_quantity =
+                    check_quantity(function_code, word(pdu, 3), MAX_READ_REGISTERS)?;
+                let write_quantity =
+                    check_quantity(function_code, word(pdu, 7), MAX_READ_WRITE_REGISTERS)?;
+
+                check_byte_count(2 * write_quantity as usize, pdu[9] as usize)?;
+
+                Request::ReadWriteMultipleRegisters {
+                    read_address: word(pdu, 1),
+                    read_quantity,
+                    write_address: word(pdu, 5),
+                    registers: registers(&pdu[10..])?,
+                }
+            }
+            READ_FIFO_QUEUE => Request::ReadFifoQueue {
+                address: word(pdu, 1),
+            },
+            // check_length only accepts the Read Device Identification MEI type
+            ENCAPSULATED_INTERFACE_TRANSPORT if pdu[1] == MEI_READ_DEVICE_ID => {
+                let read_device_id_code = pdu[2];
+
+                if !(1..=4).contains(&read_device_id_code) {
+                    return Err(ModbusError::BadValue);
+                }
+
+                Request::ReadDeviceIdentification {
+                    read_device_id_code,
+                    object_id: pdu[3],
+                }
+            }
+            _ => return Err(BadFuncCode),
+        };
+
+        Ok(request)
+    }
+
+    /// The function code of this request
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Request::ReadCoils { .. } => READ_COILS,
+            Request::ReadDiscreteInputs { .. } => READ_DISCRETE_INPUTS,
+            Request::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Request::ReadInputRegisters { .. } => READ_INPUT_REGISTERS,
+            Request::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Request::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Request::ReadExceptionStatus => READ_EXCEPTION_STATUS,
+            Request::Diagnostics { .. } => DIAGNOSTICS,
+            Request::GetCommEventCounter => GET_COMM_EVENT_COUNTER,
+            Request::GetCommEventLog => GET_COMM_EVENT_LOG,
+            Request::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Request::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+            Request::ReportServerId => REPORT_SERVER_ID,
+            Request::ReadFileRecord { .. } => READ_FILE_RECORD,
+            Request::WriteFileRecord { .. } => WRITE_FILE_RECORD,
+            Request::MaskWriteRegister { .. } => MASK_WRITE_REGISTER,
+            Request::ReadWriteMultipleRegisters { .. } => READ_WRITE_MULTIPLE_REGISTERS,
+            Request::ReadFifoQueue { .. } => READ_FIFO_QUEUE,
+            Request::ReadDeviceIdentification { .. } => ENCAPSULATED_INTERFACE_TRANSPORT,
+        }
+    }
+}
+
+fn check_read_file_sub_requests(sub_requests: &[u8]) -> Result<&[u8], ModbusError> {
+    use ModbusError::{BadLength, BadValue};
+
+    if sub_requests.is_empty() || !sub_requests.len().is_multiple_of(FILE_SUB_REQUEST_LENGTH) {
+        return Err(BadLength);
+    }
+
+    for sub_request in sub_requests.chunks(FILE_SUB_REQUEST_LENGTH) {
+        if sub_request[0] != FILE_REFERENCE_TYPE {
+            return Err(BadValue);
+        }
+    }
+
+    Ok(sub_requests)
+}
+
+pub(super) fn check_write_file_sub_requests(sub_requests: &[u8]) -> Result<&[u8], ModbusError> {
+    use ModbusError::{BadLength, BadValue};
+
+    if sub_requests.is_empty() {
+        return Err(BadLength);
+    }
+
+    let mut remaining = sub_requests;
+
+    while !remaining.is_empty() {
+        if remaining.len() < FILE_SUB_REQUEST_LENGTH {
+            return Err(BadLength);
+        }
+
+        if remaining[0] != FILE_REFERENCE_TYPE {
+            return Err(BadValue);
+        }
+
+        let record_length = 2 * word(remaining, 5) as usize;
+        let sub_request_length = FILE_SUB_REQUEST_LENGTH + record_length;
+
+        remaining = remaining.get(sub_request_length..).ok_or(BadLength)?;
+    }
+
+    Ok(sub_requests)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    #[test]
+    fn parse_test_data() {
+        assert_eq!(
+            Request::parse(ADU2_PDU()),
+            Ok(Request::ReadInputRegisters {
+                address: 0,
+                quantity: 100
+            })
+        );
+        assert_eq!(
+            Request::parse(ADU3_PDU()),
+            Ok(Request::ReadHoldingRegisters {
+                address: 0x6b,
+                quantity: 3
+            })
+        );
+    }
+
+    #[test]
+    fn parse_read_quantities() {
+        assert_eq!(
+            Request::parse(&[0x01, 0x00, 0x13, 0x07, 0xD0]),
+            Ok(Request::ReadCoils {
+                address: 0x13,
+                quantity: 2000
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x02, 0x00, 0x13, 0x07, 0xD1]),
+            Err(QuantityOutOfRange {
+                fc: 2,
+                quantity: 2001
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x03, 0x00, 0x00, 0x00, 0x00]),
+            Err(QuantityOutOfRange { fc: 3, quantity: 0 })
+        );
+        assert_eq!(
+            Request::parse(&[0x04, 0x00, 0x00, 0x00, 0x7E]),
+            Err(QuantityOutOfRange {
+                fc: 4,
+                quantity: 126
+            })
+        );
+    }
+
+    #[test]
+    fn parse_write_single() {
+        assert_eq!(
+            Request::parse(&[0x05, 0x00, 0xAC, 0xFF, 0x00]),
+            Ok(Request::WriteSingleCoil {
+                address: 0xAC,
+                value: Coil::On
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x05, 0x00, 0xAC, 0x00, 0x00]),
+            Ok(Request::WriteSingleCoil {
+                address: 0xAC,
+                value: Coil::Off
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x05, 0x00, 0xAC, 0x00, 0x01]),
+            Err(BadValue)
+        );
+        assert_eq!(
+            Request::parse(&[0x06, 0x00, 0x01, 0x00, 0x03]),
+            Ok(Request::WriteSingleRegister {
+                address: 1,
+                value: 3
+            })
+        );
+    }
+
+    #[test]
+    fn parse_write_multiple_coils() {
+        let request = Request::parse(&[0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD, 0x01]).unwrap();
+
+        match request {
+            Request::WriteMultipleCoils { address, coils } => {
+                assert_eq!(address, 0x13);
+                assert_eq!(coils.len(), 10);
+                assert_eq!(coils.bytes(), &[0xCD, 0x01]);
+            }
+            _ => panic!("Wrong request type: {:?}", request),
+        }
+
+        assert_eq!(
+            Request::parse(&[0x0F, 0x00, 0x13, 0x00, 0x0A, 0x03, 0xCD, 0x01, 0x00]),
+            Err(ByteCountMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x0F, 0x00, 0x13, 0x00, 0x0A, 0x02, 0xCD]),
+            Err(BadLength)
+        );
+    }
+
+    #[test]
+    fn parse_write_multiple_registers() {
+        let request =
+            Request::parse(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]).unwrap();
+
+        match request {
+            Request::WriteMultipleRegisters { address, registers } => {
+                assert_eq!(address, 1);
+                assert!(registers.iter().eq([0x000A, 0x0102].iter().copied()));
+            }
+            _ => panic!("Wrong request type: {:?}", request),
+        }
+
+        assert_eq!(
+            Request::parse(&[0x10, 0x00, 0x01, 0x00, 0x7C, 0x00]),
+            Err(QuantityOutOfRange {
+                fc: 16,
+                quantity: 124
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x10, 0x00, 0x01, 0x00, 0x01, 0x04, 0, 0, 0, 0]),
+            Err(ByteCountMismatch {
+                expected: 2,
+                actual: 4
+            })
+        );
+    }
+
+    #[test]
+    fn parse_mask_write_register() {
+        assert_eq!(
+            Request::parse(&[0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25]),
+            Ok(Request::MaskWriteRegister {
+                address: 4,
+                and_mask: 0xF2,
+                or_mask: 0x25
+            })
+        );
+    }
+
+    #[test]
+    fn parse_read_write_multiple_registers() {
+        let pdu = &[
+            0x17, 0x00, 0x03, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x03, 0x06, 0x00, 0xFF, 0x00, 0xFF,
+            0x00, 0xFF,
+        ];
+
+        match Request::parse(pdu).unwrap() {
+            Request::ReadWriteMultipleRegisters {
+                read_address,
+                read_quantity,
+                write_address,
+                registers,
+            } => {
+                assert_eq!(read_address, 3);
+                assert_eq!(read_quantity, 6);
+                assert_eq!(write_address, 14);
+                assert!(registers.iter().eq([0xFF, 0xFF, 0xFF].iter().copied()));
+            }
+            request => panic!("Wrong request type: {:?}", request),
+        }
+
+        let too_many_writes = &[0x17, 0x00, 0x03, 0x00, 0x06, 0x00, 0x0E, 0x00, 0x7A, 0x00];
+        assert_eq!(
+            Request::parse(too_many_writes),
+            Err(QuantityOutOfRange {
+                fc: 23,
+                quantity: 122
+            })
+        );
+    }
+
+    #[test]
+    fn parse_file_records() {
+        let read = &[
+            0x14, 0x0E, 0x06, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02, 0x06, 0x00, 0x03, 0x00, 0x09,
+            0x00, 0x02,
+        ];
+        assert_eq!(
+            Request::parse(read),
+            Ok(Request::ReadFileRecord {
+                sub_requests: &read[2..]
+            })
+        );
+
+        let bad_reference = &[0x14, 0x07, 0x05, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(Request::parse(bad_reference), Err(BadValue));
+
+        let write = &[
+            0x15, 0x0D, 0x06, 0x00, 0x04, 0x00, 0x07, 0x00, 0x03, 0x06, 0xAF, 0x04, 0xBE, 0x10,
+            0x0D,
+        ];
+        assert_eq!(
+            Request::parse(write),
+            Ok(Request::WriteFileRecord {
+                sub_requests: &write[2..]
+            })
+        );
+
+        let short_record = &[
+            0x15, 0x0B, 0x06, 0x00, 0x04, 0x00, 0x07, 0x00, 0x03, 0x06, 0xAF, 0x04, 0xBE,
+        ];
+        assert_eq!(Request::parse(short_record), Err(BadLength));
+    }
+
+    #[test]
+    fn parse_no_data() {
+        assert_eq!(Request::parse(&[0x07]), Ok(Request::ReadExceptionStatus));
+        assert_eq!(Request::parse(&[0x0B]), Ok(Request::GetCommEventCounter));
+        assert_eq!(Request::parse(&[0x0C]), Ok(Request::GetCommEventLog));
+        assert_eq!(Request::parse(&[0x11]), Ok(Request::ReportServerId));
+    }
+
+    #[test]
+    fn parse_misc() {
+        assert_eq!(
+            Request::parse(&[0x08, 0x00, 0x00, 0xA5, 0x37]),
+            Ok(Request::Diagnostics {
+                sub_function: 0,
+                data: 0xA537
+            })
+        );
+        assert_eq!(
+            Request::parse(&[0x18, 0x04, 0xDE]),
+            Ok(Request::ReadFifoQueue { address: 0x04DE })
+        );
+        assert_eq!(
+            Request::parse(&[0x2B, 0x0E, 0x01, 0x00]),
+            Ok(Request::ReadDeviceIdentification {
+                read_device_id_code: 1,
+                object_id: 0
+            })
+        );
+        assert_eq!(Request::parse(&[0x2B, 0x0E, 0x05, 0x00]), Err(BadValue));
+    }
+
+    #[test]
+    fn parse_bad_lengths() {
+        assert_eq!(Request::parse(&[]), Err(BadLength));
+        assert_eq!(Request::parse(&[0x03, 0x00, 0x00, 0x00]), Err(BadLength));
+        assert_eq!(
+            Request::parse(&[0x03, 0x00, 0x00, 0x00, 0x01, 0x00]),
+            Err(BadLength)
+        );
+        assert_eq!(Request::parse(&[0x07, 0x00]), Err(BadLength));
+    }
+
+    #[test]
+    fn parse_bad_func_code() {
+        assert_eq!(Request::parse(&[0x09]), Err(BadFuncCode));
+        assert_eq!(Request::parse(&[0x83, 0x02]), Err(BadFuncCode));
+        assert_eq!(Request::parse(&[0x2B, 0x0D, 0x00]), Err(BadFuncCode));
+    }
+
+    #[test]
+    fn function_codes_round_trip() {
+        let pdus: &[&[u8]] = &[
+            &[0x01, 0x00, 0x00, 0x00, 0x01],
+            &[0x05, 0x00, 0x00, 0xFF, 0x00],
+            &[0x07],
+            &[0x0F, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01],
+            &[0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25],
+            &[0x18, 0x04, 0xDE],
+            &[0x2B, 0x0E, 0x01, 0x00],
+        ];
+
+        for pdu in pdus {
+            assert_eq!(Request::parse(pdu).unwrap().function_code(), pdu[0]);
+        }
+    }
+}
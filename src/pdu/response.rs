@@ -0,0 +1,796 @@
+use super::function_code::*;
+use super::request::check_write_file_sub_requests;
+use super::{
+    check_byte_count, check_length, check_quantity, coil_from_u16, is_exception, registers, word,
+    Coils, Exception, Registers, Request, MEI_READ_DEVICE_ID,
+};
+use crate::bit_pack::bytes_needed;
+use crate::{Coil, Direction, ModbusError};
+
+/// The most values a FIFO queue can hold
+pub const MAX_FIFO_COUNT: u16 = 31;
+
+// File record sub-responses always use this reference type
+const FILE_REFERENCE_TYPE: u8 = 6;
+
+/// A MODBUS response PDU
+///
+/// Like `Request`, this borrows any variable-length data from the PDU it was parsed from.
+/// Register data is exposed as `Registers`, which can be iterated as `u16`s. Coil and discrete
+/// input data is left packed, because the response alone doesn't say how many of the bits are
+/// meaningful. Use `coils` with the quantity from the request to get at them, or unpack them
+/// directly with `bit_pack::unpack_coils`.
+///
+/// Use `parse_for` to check that a response actually answers the request that was sent.
+///
+/// Exception responses are parsed into `Response::Exception`, so that the reason the request
+/// was rejected can be reported.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::pdu::{Request, Response};
+///
+/// let request = Request::parse(&[0x03, 0x00, 0x6b, 0x00, 0x03]).unwrap();
+/// let pdu = &[0x03, 0x06, 0x02, 0x2b, 0x00, 0x00, 0x00, 0x64];
+///
+/// match Response::parse_for(pdu, &request).unwrap() {
+///     Response::ReadHoldingRegisters { registers } => {
+///         assert!(registers.iter().eq([0x022b, 0x0000, 0x0064].iter().copied()));
+///     }
+///     response => panic!("Unexpected response: {:?}", response),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Response<'a> {
+    /// Function code 1
+    ReadCoils { bytes: &'a [u8] },
+
+    /// Function code 2
+    ReadDiscreteInputs { bytes: &'a [u8] },
+
+    /// Function code 3
+    ReadHoldingRegisters { registers: Registers<'a> },
+
+    /// Function code 4
+    ReadInputRegisters { registers: Registers<'a> },
+
+    /// Function code 5
+    WriteSingleCoil { address: u16, value: Coil },
+
+    /// Function code 6
+    WriteSingleRegister { address: u16, value: u16 },
+
+    /// Function code 7 (serial line only)
+    ReadExceptionStatus { status: u8 },
+
+    /// Function code 8 (serial line only)
+    Diagnostics { sub_function: u16, data: u16 },
+
+    /// Function code 11 (serial line only)
+    GetCommEventCounter { status: u16, event_count: u16 },
+
+    /// Function code 12 (serial line only)
+    GetCommEventLog {
+        status: u16,
+        event_count: u16,
+        message_count: u16,
+        events: &'a [u8],
+    },
+
+    /// Function code 15
+    WriteMultipleCoils { address: u16, quantity: u16 },
+
+    /// Function code 16
+    WriteMultipleRegisters { address: u16, quantity: u16 },
+
+    /// Function code 17 (serial line only)
+    ///
+    /// The contents are device-specific.
+    ReportServerId { data: &'a [u8] },
+
+    /// Function code 20
+    ///
+    /// `sub_responses` holds the raw sub-responses, each made up of a length, a reference type
+    /// (always 6), and the record data.
+    ReadFileRecord { sub_responses: &'a [u8] },
+
+    /// Function code 21
+    ///
+    /// The response is an echo of the request.
+    WriteFileRecord { sub_responses: &'a [u8] },
+
+    /// Function code 22
+    MaskWriteRegister {
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    },
+
+    /// Function code 23
+    ReadWriteMultipleRegisters { registers: Registers<'a> },
+
+    /// Function code 24
+    ReadFifoQueue { registers: Registers<'a> },
+
+    /// Function code 43, MEI type 14
+    ReadDeviceIdentification {
+        read_device_id_code: u8,
+        conformity_level: u8,
+        more_follows: bool,
+        next_object_id: u8,
+        objects: DeviceIdObjects<'a>,
+    },
+
+    /// Any function code with the exception flag set
+    Exception(Exception),
+}
+
+impl<'a> Response<'a> {
+    /// Parse a response PDU
+    ///
+    /// The PDU must be exactly one response: if it's shorter or longer than its function code and
+    /// byte count fields say it should be, returns `Err(BadLength)`. Fields are checked the same
+    /// way as in `Request::parse`, but nothing is checked against the original request. Use
+    /// `parse_for` for that.
+    pub fn parse(pdu: &'a [u8]) -> Result<Self, ModbusError> {
+        use ModbusError::{BadFuncCode, BadLength, BadValue};
+
+        if is_exception(pdu) {
+            return Exception::parse(pdu).map(Response::Exception);
+        }
+
+        check_length(pdu, Direction::Response)?;
+
+        let function_code = pdu[0];
+
+        let response = match function_code {
+            READ_COILS => Response::ReadCoils { bytes: &pdu[2..] },
+            READ_DISCRETE_INPUTS => Response::ReadDiscreteInputs { bytes: &pdu[2..] },
+            READ_HOLDING_REGISTERS => Response::ReadHoldingRegisters {
+                registers: registers(&pdu[2..])?,
+            },
+            READ_INPUT_REGISTERS => Response::ReadInputRegisters {
+                registers: registers(&pdu[2..])?,
+            },
+            WRITE_SINGLE_COIL => Response::WriteSingleCoil {
+                address: word(pdu, 1),
+                value: coil_from_u16(word(pdu, 3))?,
+            },
+            WRITE_SINGLE_REGISTER => Response::WriteSingleRegister {
+                address: word(pdu, 1),
+                value: word(pdu, 3),
+            },
+            READ_EXCEPTION_STATUS => Response::ReadExceptionStatus { status: pdu[1] },
+            DIAGNOSTICS => Response::Diagnostics {
+                sub_function: word(pdu, 1),
+                data: word(pdu, 3),
+            },
+            GET_COMM_EVENT_COUNTER => Response::GetCommEventCounter {
+                status: word(pdu, 1),
+                event_count: word(pdu, 3),
+            },
+            GET_COMM_EVENT_LOG => {
+                // Byte count, status, event count, message count
+                if pdu.len() < 8 {
+                    return Err(BadLength);
+                }
+
+                Response::GetCommEventLog {
+                    status: word(pdu, 2),
+                    event_count: word(pdu, 4),
+                    message_count: word(pdu, 6),
+                    events: &pdu[8..],
+                }
+            }
+            WRITE_MULTIPLE_COILS => Response::WriteMultipleCoils {
+                address: word(pdu, 1),
+                quantity: word(pdu, 3),
+            },
+            WRITE_MULTIPLE_REGISTERS => Response::WriteMultipleRegisters {
+                address: word(pdu, 1),
+                quantity: word(pdu, 3),
+            },
+            REPORT_SERVER_ID => Response::ReportServerId { data: &pdu[2..] },
+            READ_FILE_RECORD => Response::ReadFileRecord {
+                sub_responses: check_read_file_sub_responses(&pdu[2..])?,
+            },
+            WRITE_FILE_RECORD => Response::WriteFileRecord {
+                sub_responses: check_write_file_sub_requests(&pdu[2..])?,
+            },
+            MASK_WRITE_REGISTER => Response::MaskWriteRegister {
+                address: word(pdu, 1),
+                and_mask: word(pdu, 3),
+                or_mask: word(pdu, 5),
+            },
+            READ_WRITE_MULTIPLE_REGISTERS => Response::ReadWriteMultipleRegisters {
+                registers: registers(&pdu[2..])?,
+            },
+            READ_FIFO_QUEUE => {
+                // Byte count, FIFO count
+                if pdu.len() < 5 {
+                    return Err(BadLength);
+                }
+
+                let fifo_count = check_fifo_count(word(pdu, 3))?;
+
+                // The byte count includes the FIFO count itself
+                check_byte_count(2 + 2 * fifo_count as usize, word(pdu, 1) as usize)?;
+
+                Response::ReadFifoQueue {
+                    registers: registers(&pdu[5..])?,
+                }
+            }
+            // check_length only accepts the Read Device Identification MEI type
+            ENCAPSULATED_INTERFACE_TRANSPORT if pdu[1] == MEI_READ_DEVICE_ID => {
+                let more_follows = match pdu[4] {
+                    0x00 => false,
+                    0xFF => true,
+                    _ => return Err(BadValue),
+                };
+
+                Response::ReadDeviceIdentification {
+                    read_device_id_code: pdu[2],
+                    conformity_level: pdu[3],
+                    more_follows,
+                    next_object_id: pdu[5],
+                    objects: DeviceIdObjects {
+                        count: pdu[6],
+                        data: &pdu[7..],
+                    },
+                }
+            }
+            _ => return Err(BadFuncCode),
+        };
+
+        Ok(response)
+    }
+
+    /// Parse a response PDU, checking that it answers the given request
+    ///
+    /// In addition to the checks done by `parse`:
+    ///
+    /// - If the function code doesn't match the request's, returns `Err(ResponseMismatch)`
+    /// - If a read returned a different amount of data than was requested, returns
+    ///   `Err(ByteCountMismatch)`
+    /// - If a write response doesn't echo the request's address, quantity, or value, returns
+    ///   `Err(ResponseMismatch)`
+    pub fn parse_for(pdu: &'a [u8], request: &Request) -> Result<Self, ModbusError> {
+        let response = Self::parse(pdu)?;
+
+        response.validate(request)?;
+
+        Ok(response)
+    }
+
+    /// Check that this response answers the given request
+    ///
+    /// See `parse_for` for the checks that are done. An exception response only needs to have
+    /// the same function code as the request.
+    pub fn validate(&self, request: &Request) -> Result<(), ModbusError> {
+        use ModbusError::ResponseMismatch;
+
+        if self.function_code() != request.function_code() {
+            return Err(ResponseMismatch);
+        }
+
+        match (*self, *request) {
+            (Response::ReadCoils { bytes }, Request::ReadCoils { quantity, .. })
+            | (
+                Response::ReadDiscreteInputs { bytes },
+                Request::ReadDiscreteInputs { quantity, .. },
+            ) => check_byte_count(bytes_needed(quantity as usize), bytes.len()),
+
+            (
+                Response::ReadHoldingRegisters { registers },
+                Request::ReadHoldingRegisters { quantity, .. },
+            )
+            | (
+                Response::ReadInputRegisters { registers },
+                Request::ReadInputRegisters { quantity, .. },
+            )
+            | (
+                Response::ReadWriteMultipleRegisters { registers },
+                Request::ReadWriteMultipleRegisters {
+                    read_quantity: quantity,
+                    ..
+                },
+            ) => check_byte_count(2 * quantity as usize, registers.bytes().len()),
+
+            (
+                Response::WriteSingleCoil { address, value },
+                Request::WriteSingleCoil {
+                    address: request_address,
+                    value: request_value,
+                },
+            ) => check_echo(address == request_address && value == request_value),
+
+            (
+                Response::WriteSingleRegister { address, value },
+                Request::WriteSingleRegister {
+                    address: request_address,
+                    value: request_value,
+                },
+            ) => check_echo(address == request_address && value == request_value),
+
+            (
+                Response::Diagnostics { sub_function, .. },
+                Request::Diagnostics {
+                    sub_function: request_sub_function,
+                    ..
+                },
+            ) => check_echo(sub_function == request_sub_function),
+
+            (
+                Response::WriteMultipleCoils { address, quantity },
+                Request::WriteMultipleCoils {
+                    address: request_address,
+                    coils,
+                },
+            ) => check_echo(address == request_address && quantity as usize == coils.len()),
+
+            (
+                Response::WriteMultipleRegisters { address, quantity },
+                Request::WriteMultipleRegisters {
+                    address: request_address,
+                    registers,
+                },
+            ) => check_echo(address == request_address && quantity as usize == registers.len()),
+
+            (
+                Response::WriteFileRecord { sub_responses },
+                Request::WriteFileRecord { sub_requests },
+            ) => check_echo(sub_responses == sub_requests),
+
+            (
+                Response::MaskWriteRegister {
+                    address,
+                    and_mask,
+                    or_mask,
+                },
+                Request::MaskWriteRegister {
+                    address: request_address,
+                    and_mask: request_and_mask,
+                    or_mask: request_or_mask,
+                },
+            ) => check_echo(
+                address == request_address
+                    && and_mask == request_and_mask
+                    && or_mask == request_or_mask,
+            ),
+
+            (
+                Response::ReadDeviceIdentification {
+                    read_device_id_code,
+                    ..
+                },
+                Request::ReadDeviceIdentification {
+                    read_device_id_code: request_code,
+                    ..
+                },
+            ) => check_echo(read_device_id_code == request_code),
+
+            // Nothing else in these responses can be checked against the request
+            _ => Ok(()),
+        }
+    }
+
+    /// The function code of this response
+    ///
+    /// For exception responses, this is the function code of the rejected request, without the
+    /// exception flag.
+    pub fn function_code(&self) -> u8 {
+        match self {
+            Response::ReadCoils { .. } => READ_COILS,
+            Response::ReadDiscreteInputs { .. } => READ_DISCRETE_INPUTS,
+            Response::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Response::ReadInputRegisters { .. } => READ_INPUT_REGISTERS,
+            Response::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Response::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Response::ReadExceptionStatus { .. } => READ_EXCEPTION_STATUS,
+            Response::Diagnostics { .. } => DIAGNOSTICS,
+            Response::GetCommEventCounter { .. } => GET_COMM_EVENT_COUNTER,
+            Response::GetCommEventLog { .. } => GET_COMM_EVENT_LOG,
+            Response::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Response::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+            Response::ReportServerId { .. } => REPORT_SERVER_ID,
+            Response::ReadFileRecord { .. } => READ_FILE_RECORD,
+            Response::WriteFileRecord { .. } => WRITE_FILE_RECORD,
+            Response::MaskWriteRegister { .. } => MASK_WRITE_REGISTER,
+            Response::ReadWriteMultipleRegisters { .. } => READ_WRITE_MULTIPLE_REGISTERS,
+            Response::ReadFifoQueue { .. } => READ_FIFO_QUEUE,
+            Response::ReadDeviceIdentification { .. } => ENCAPSULATED_INTERFACE_TRANSPORT,
+            Response::Exception(exception) => exception.function_code,
+        }
+    }
+
+    /// Get the exception, if this is an exception response
+    pub fn exception(&self) -> Option<Exception> {
+        match *self {
+            Response::Exception(exception) => Some(exception),
+            _ => None,
+        }
+    }
+
+    /// Turn an exception response into an error
+    ///
+    /// Returns `Err(Exception)` with the exception code for exception responses, and the
+    /// response itself otherwise. This is handy with `?` when anything but success is a failure.
+    pub fn into_result(self) -> Result<Self, ModbusError> {
+        match self {
+            Response::Exception(exception) => Err(exception.into()),
+            response => Ok(response),
+        }
+    }
+
+    /// Get the coils or discrete inputs from a Read Coils or Read Discrete Inputs response
+    ///
+    /// `quantity` should be the quantity from the request. Returns `None` if this is a different
+    /// kind of response, or if it doesn't hold the right amount of data for `quantity` coils.
+    pub fn coils(&self, quantity: u16) -> Option<Coils<'a>> {
+        match *self {
+            Response::ReadCoils { bytes } | Response::ReadDiscreteInputs { bytes } => {
+                Coils::new(bytes, quantity as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the registers from any response that carries register values
+    ///
+    /// Returns `None` if this kind of response doesn't carry register values.
+    pub fn registers(&self) -> Option<Registers<'a>> {
+        match *self {
+            Response::ReadHoldingRegisters { registers }
+            | Response::ReadInputRegisters { registers }
+            | Response::ReadWriteMultipleRegisters { registers }
+            | Response::ReadFifoQueue { registers } => Some(registers),
+            _ => None,
+        }
+    }
+}
+
+/// The objects in a Read Device Identification response
+///
+/// Iterating yields each object's ID and value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceIdObjects<'a> {
+    count: u8,
+    data: &'a [u8],
+}
+
+impl<'a> DeviceIdObjects<'a> {
+    /// The number of objects
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Whether there are no objects
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+}
+
+impl<'a> Iterator for DeviceIdObjects<'a> {
+    type Item = (u8, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.count == 0 {
+            return None;
+        }
+
+        // The PDU length was checked when parsing, so every object is complete
+        let id = self.data[0];
+        let length = self.data[1] as usize;
+        let value = &self.data[2..2 + length];
+
+        self.data = &self.data[2 + length..];
+        self.count -= 1;
+
+        Some((id, value))
+    }
+}
+
+fn check_echo(matches: bool) -> Result<(), ModbusError> {
+    if matches {
+        Ok(())
+    } else {
+        Err(ModbusError::ResponseMismatch)
+    }
+}
+
+fn check_fifo_count(fifo_count: u16) -> Result<u16, ModbusError> {
+    // An empty queue is allowed in a response, unlike quantities in requests
+    if fifo_count == 0 {
+        Ok(0)
+    } else {
+        check_quantity(READ_FIFO_QUEUE, fifo_count, MAX_FIFO_COUNT)
+    }
+}
+
+fn check_read_file_sub_responses(sub_responses: &[u8]) -> Result<&[u8], ModbusError> {
+    use ModbusError::{BadLength, BadValue};
+
+    let mut remaining = sub_responses;
+
+    while !remaining.is_empty() {
+        // The length covers the reference type and the record data
+        let length = remaining[0] as usize;
+
+        if length == 0 || !(length - 1).is_multiple_of(2) {
+            return Err(BadLength);
+        }
+
+        let sub_response = remaining.get(1..1 + length).ok_or(BadLength)?;
+
+        if sub_response[0] != FILE_REFERENCE_TYPE {
+            return Err(BadValue);
+        }
+
+        remaining = &remaining[1 + length..];
+    }
+
+    Ok(sub_responses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pdu::ExceptionCode;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    fn request(pdu: &[u8]) -> Request<'_> {
+        Request::parse(pdu).unwrap()
+    }
+
+    #[test]
+    fn parse_test_data() {
+        let response = Response::parse(ADU1_PDU()).unwrap();
+
+        assert_eq!(response.function_code(), ADU1_FUNC_CODE);
+        assert_eq!(response.registers().unwrap().len(), 100);
+
+        let response = Response::parse_for(ADU4_PDU(), &request(ADU3_PDU())).unwrap();
+
+        assert!(response
+            .registers()
+            .unwrap()
+            .iter()
+            .eq([0xAE41, 0x5652, 0x4340].iter().copied()));
+    }
+
+    #[test]
+    fn parse_read_coils() {
+        let read_coils = request(&[0x01, 0x00, 0x13, 0x00, 0x13]);
+        let pdu = &[0x01, 0x03, 0xCD, 0x6B, 0x05];
+
+        let response = Response::parse_for(pdu, &read_coils).unwrap();
+        assert_eq!(response, Response::ReadCoils { bytes: &pdu[2..] });
+
+        let coils = response.coils(19).unwrap();
+        assert_eq!(coils.len(), 19);
+        assert_eq!(coils.get(0), Some(Coil::On));
+        assert_eq!(coils.get(1), Some(Coil::Off));
+        assert_eq!(coils.get(18), Some(Coil::On));
+
+        let mut unpacked = [Coil::Off; 19];
+        crate::bit_pack::unpack_coils(coils.bytes(), &mut unpacked);
+        assert!(coils.iter().eq(unpacked.iter().copied()));
+
+        assert_eq!(response.coils(30), None);
+
+        // Too few bytes for the requested quantity
+        let short = &[0x01, 0x02, 0xCD, 0x6B];
+        assert_eq!(
+            Response::parse_for(short, &read_coils),
+            Err(ByteCountMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn parse_read_registers() {
+        let read_input = request(&[0x04, 0x00, 0x08, 0x00, 0x01]);
+
+        assert_eq!(
+            Response::parse_for(&[0x04, 0x02, 0x00, 0x0A], &read_input)
+                .unwrap()
+                .registers()
+                .unwrap()
+                .get(0),
+            Some(0x000A)
+        );
+        assert_eq!(
+            Response::parse_for(&[0x04, 0x04, 0x00, 0x0A, 0x00, 0x0B], &read_input),
+            Err(ByteCountMismatch {
+                expected: 2,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            Response::parse(&[0x04, 0x03, 0x00, 0x0A, 0x00]),
+            Err(BadLength)
+        );
+    }
+
+    #[test]
+    fn parse_writes() {
+        let write_coil = request(&[0x05, 0x00, 0xAC, 0xFF, 0x00]);
+
+        assert_eq!(
+            Response::parse_for(&[0x05, 0x00, 0xAC, 0xFF, 0x00], &write_coil),
+            Ok(Response::WriteSingleCoil {
+                address: 0xAC,
+                value: Coil::On
+            })
+        );
+        assert_eq!(
+            Response::parse_for(&[0x05, 0x00, 0xAC, 0x00, 0x00], &write_coil),
+            Err(ResponseMismatch)
+        );
+
+        let write_registers =
+            request(&[0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0x00, 0x0A, 0x01, 0x02]);
+
+        assert_eq!(
+            Response::parse_for(&[0x10, 0x00, 0x01, 0x00, 0x02], &write_registers),
+            Ok(Response::WriteMultipleRegisters {
+                address: 1,
+                quantity: 2
+            })
+        );
+        assert_eq!(
+            Response::parse_for(&[0x10, 0x00, 0x01, 0x00, 0x03], &write_registers),
+            Err(ResponseMismatch)
+        );
+
+        let mask_write = request(&[0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25]);
+
+        assert!(
+            Response::parse_for(&[0x16, 0x00, 0x04, 0x00, 0xF2, 0x00, 0x25], &mask_write).is_ok()
+        );
+    }
+
+    #[test]
+    fn parse_wrong_function_code() {
+        let read_holding = request(ADU3_PDU());
+
+        assert_eq!(
+            Response::parse_for(&[0x04, 0x02, 0x00, 0x0A], &read_holding),
+            Err(ResponseMismatch)
+        );
+    }
+
+    #[test]
+    fn parse_serial_line() {
+        assert_eq!(
+            Response::parse(&[0x07, 0x6D]),
+            Ok(Response::ReadExceptionStatus { status: 0x6D })
+        );
+        assert_eq!(
+            Response::parse(&[0x0B, 0xFF, 0xFF, 0x01, 0x08]),
+            Ok(Response::GetCommEventCounter {
+                status: 0xFFFF,
+                event_count: 0x0108
+            })
+        );
+
+        let log = &[0x0C, 0x08, 0x00, 0x00, 0x01, 0x08, 0x01, 0x21, 0x20, 0x00];
+        assert_eq!(
+            Response::parse(log),
+            Ok(Response::GetCommEventLog {
+                status: 0,
+                event_count: 0x0108,
+                message_count: 0x0121,
+                events: &[0x20, 0x00]
+            })
+        );
+        assert_eq!(Response::parse(&[0x0C, 0x02, 0x00, 0x00]), Err(BadLength));
+
+        assert_eq!(
+            Response::parse(&[0x11, 0x02, 0x42, 0xFF]),
+            Ok(Response::ReportServerId {
+                data: &[0x42, 0xFF]
+            })
+        );
+    }
+
+    #[test]
+    fn parse_fifo() {
+        let pdu = &[0x18, 0x00, 0x06, 0x00, 0x02, 0x01, 0xB8, 0x12, 0x84];
+
+        assert_eq!(
+            Response::parse(pdu).unwrap().registers().unwrap().get(1),
+            Some(0x1284)
+        );
+
+        let bad_count = &[0x18, 0x00, 0x06, 0x00, 0x03, 0x01, 0xB8, 0x12, 0x84];
+        assert_eq!(
+            Response::parse(bad_count),
+            Err(ByteCountMismatch {
+                expected: 8,
+                actual: 6
+            })
+        );
+
+        assert!(Response::parse(&[0x18, 0x00, 0x02, 0x00, 0x00]).is_ok());
+
+        // Too short to hold the FIFO count
+        assert_eq!(Response::parse(&[0x18, 0x00, 0x00]), Err(BadLength));
+        assert_eq!(Response::parse(&[0x18, 0x00, 0x01, 0x00]), Err(BadLength));
+    }
+
+    #[test]
+    fn parse_file_records() {
+        let read = &[
+            0x14, 0x0C, 0x05, 0x06, 0x0D, 0xFE, 0x00, 0x20, 0x05, 0x06, 0x33, 0xCD, 0x00, 0x40,
+        ];
+        assert_eq!(
+            Response::parse(read),
+            Ok(Response::ReadFileRecord {
+                sub_responses: &read[2..]
+            })
+        );
+
+        let bad_reference = &[0x14, 0x03, 0x02, 0x07, 0x0D];
+        assert_eq!(Response::parse(bad_reference), Err(BadLength));
+
+        let bad_reference = &[0x14, 0x04, 0x03, 0x07, 0x0D, 0xFE];
+        assert_eq!(Response::parse(bad_reference), Err(BadValue));
+    }
+
+    #[test]
+    fn parse_device_identification() {
+        let pdu = &[
+            0x2B, 0x0E, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x01, b'a', 0x01, 0x02, b'b', b'c',
+        ];
+
+        match Response::parse_for(pdu, &request(&[0x2B, 0x0E, 0x01, 0x00])).unwrap() {
+            Response::ReadDeviceIdentification {
+                read_device_id_code,
+                more_follows,
+                objects,
+                ..
+            } => {
+                assert_eq!(read_device_id_code, 1);
+                assert!(!more_follows);
+                assert_eq!(objects.len(), 2);
+                assert!(objects.eq([(0, &b"a"[..]), (1, &b"bc"[..])].iter().copied()));
+            }
+            response => panic!("Unexpected response: {:?}", response),
+        }
+
+        assert_eq!(
+            Response::parse_for(pdu, &request(&[0x2B, 0x0E, 0x02, 0x00])),
+            Err(ResponseMismatch)
+        );
+    }
+
+    #[test]
+    fn parse_exception() {
+        let read_holding = request(ADU3_PDU());
+
+        let response = Response::parse_for(&[0x83, 0x02], &read_holding).unwrap();
+        assert_eq!(response.function_code(), 0x03);
+        assert_eq!(
+            response.exception().map(|exception| exception.code),
+            Some(ExceptionCode::IllegalDataAddress)
+        );
+
+        assert_eq!(
+            Response::parse_for(&[0x84, 0x02], &read_holding),
+            Err(ResponseMismatch)
+        );
+        assert_eq!(Response::parse(&[0x83, 0x02, 0x00]), Err(BadLength));
+        assert_eq!(Response::parse(ADU4_PDU()).unwrap().exception(), None);
+
+        assert_eq!(
+            response.into_result(),
+            Err(Exception(ExceptionCode::IllegalDataAddress))
+        );
+        assert!(Response::parse(ADU4_PDU()).unwrap().into_result().is_ok());
+    }
+}
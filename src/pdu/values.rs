@@ -0,0 +1,251 @@
+use crate::bit_pack::{bytes_needed, unpack_coils};
+use crate::Coil;
+
+/// A borrowed sequence of packed coil (or discrete input) values
+///
+/// MODBUS packs coils 8 to a byte, with the first coil in the least significant bit of the first
+/// byte. See the `bit_pack` module for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Coils<'a> {
+    bytes: &'a [u8],
+    quantity: usize,
+}
+
+impl<'a> Coils<'a> {
+    /// Wrap some packed coil data
+    ///
+    /// Returns `None` if `bytes` is the wrong length for `quantity` coils.
+    pub fn new(bytes: &'a [u8], quantity: usize) -> Option<Self> {
+        if bytes.len() == bytes_needed(quantity) {
+            Some(Coils { bytes, quantity })
+        } else {
+            None
+        }
+    }
+
+    /// The number of coils
+    pub fn len(&self) -> usize {
+        self.quantity
+    }
+
+    /// Whether there are no coils
+    pub fn is_empty(&self) -> bool {
+        self.quantity == 0
+    }
+
+    /// The packed coil data, exactly as it appears in the PDU
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Get a single coil, if `index` is in range
+    pub fn get(&self, index: usize) -> Option<Coil> {
+        if index >= self.quantity {
+            return None;
+        }
+
+        let mask = 1 << (index % 8);
+
+        Some(if self.bytes[index / 8] & mask == 0 {
+            Coil::Off
+        } else {
+            Coil::On
+        })
+    }
+
+    /// Unpack the coils into the given slice
+    ///
+    /// If `coils` is shorter than the number of coils, only the first `coils.len()` are
+    /// unpacked. Returns the number of coils unpacked.
+    pub fn unpack(&self, coils: &mut [Coil]) -> usize {
+        let count = core::cmp::min(coils.len(), self.quantity);
+
+        unpack_coils(self.bytes, &mut coils[..count]);
+
+        count
+    }
+
+    /// Iterate over the coils
+    pub fn iter(&self) -> CoilIter<'a> {
+        CoilIter {
+            coils: *self,
+            index: 0,
+        }
+    }
+
+    /// Unpack the coils into a new `Vec`
+    #[cfg(feature = "std")]
+    pub fn to_vec(&self) -> Vec<Coil> {
+        self.iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for Coils<'a> {
+    type Item = Coil;
+    type IntoIter = CoilIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over borrowed coil values
+#[derive(Clone, Debug)]
+pub struct CoilIter<'a> {
+    coils: Coils<'a>,
+    index: usize,
+}
+
+impl<'a> Iterator for CoilIter<'a> {
+    type Item = Coil;
+
+    fn next(&mut self) -> Option<Coil> {
+        let coil = self.coils.get(self.index)?;
+        self.index += 1;
+
+        Some(coil)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.coils.len() - self.index;
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for CoilIter<'a> {}
+
+/// A borrowed sequence of big-endian 16-bit register values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Registers<'a> {
+    /// Wrap some register data
+    ///
+    /// Returns `None` if `bytes` has an odd length.
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        if bytes.len().is_multiple_of(2) {
+            Some(Registers { bytes })
+        } else {
+            None
+        }
+    }
+
+    /// The number of registers
+    pub fn len(&self) -> usize {
+        self.bytes.len() / 2
+    }
+
+    /// Whether there are no registers
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The register data, exactly as it appears in the PDU
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Get a single register, if `index` is in range
+    pub fn get(&self, index: usize) -> Option<u16> {
+        let high = *self.bytes.get(2 * index)?;
+        let low = *self.bytes.get(2 * index + 1)?;
+
+        Some(u16::from_be_bytes([high, low]))
+    }
+
+    /// Iterate over the registers
+    pub fn iter(&self) -> RegisterIter<'a> {
+        RegisterIter {
+            chunks: self.bytes.chunks_exact(2),
+        }
+    }
+
+    /// Copy the registers into a new `Vec`
+    #[cfg(feature = "std")]
+    pub fn to_vec(&self) -> Vec<u16> {
+        self.iter().collect()
+    }
+}
+
+impl<'a> IntoIterator for Registers<'a> {
+    type Item = u16;
+    type IntoIter = RegisterIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over borrowed register values
+#[derive(Clone, Debug)]
+pub struct RegisterIter<'a> {
+    chunks: core::slice::ChunksExact<'a, u8>,
+}
+
+impl<'a> Iterator for RegisterIter<'a> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        self.chunks
+            .next()
+            .map(|chunk| u16::from_be_bytes([chunk[0], chunk[1]]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for RegisterIter<'a> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Coil::*;
+
+    #[test]
+    fn coils() {
+        let coils = Coils::new(&[0b1100_1101, 0b0000_0001], 10).unwrap();
+
+        assert_eq!(coils.len(), 10);
+        assert_eq!(coils.get(0), Some(On));
+        assert_eq!(coils.get(1), Some(Off));
+        assert_eq!(coils.get(8), Some(On));
+        assert_eq!(coils.get(9), Some(Off));
+        assert_eq!(coils.get(10), None);
+
+        let mut unpacked = [Off; 12];
+        assert_eq!(coils.unpack(&mut unpacked), 10);
+        assert!(coils.iter().eq(unpacked[..10].iter().copied()));
+        assert_eq!(coils.iter().len(), 10);
+
+        assert_eq!(Coils::new(&[0], 9), None);
+        assert_eq!(Coils::new(&[0, 0], 8), None);
+    }
+
+    #[test]
+    fn registers() {
+        let registers = Registers::new(&[0x12, 0x34, 0xAB, 0xCD]).unwrap();
+
+        assert_eq!(registers.len(), 2);
+        assert_eq!(registers.get(0), Some(0x1234));
+        assert_eq!(registers.get(1), Some(0xABCD));
+        assert_eq!(registers.get(2), None);
+        assert!(registers.iter().eq([0x1234, 0xABCD].iter().copied()));
+
+        assert_eq!(Registers::new(&[0x12, 0x34, 0xAB]), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn to_vec() {
+        let coils = Coils::new(&[0b0000_0101], 3).unwrap();
+        assert_eq!(coils.to_vec(), vec![On, Off, On]);
+
+        let registers = Registers::new(&[0x12, 0x34, 0xAB, 0xCD]).unwrap();
+        assert_eq!(registers.to_vec(), vec![0x1234, 0xABCD]);
+    }
+}
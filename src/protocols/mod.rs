@@ -9,12 +9,16 @@
 //! trait. This defines several utility functions for ADU-PDU conversion, as well as a header type.
 //!
 //! The primary two MODBUS variants are TCP MODBUS, which uses a TCP stream as its transport, and
-//! MODBUS RTU, which uses RS-232 or RS-485 as its transport.
+//! MODBUS RTU, which uses RS-232 or RS-485 as its transport. MODBUS RTU frames are also often
+//! forwarded over TCP as-is, by serial-to-Ethernet converters.
 
-use crate::ModbusError;
+use crate::{Direction, ModbusError};
 
+mod modbus_ascii;
 mod modbus_rtu;
+mod rtu_over_tcp;
 mod tcp_modbus;
+mod udp_modbus;
 
 /// A MODBUS transport protocol.
 ///
@@ -28,6 +32,16 @@ pub trait ModbusProtocol {
     /// It is not necessarily bit-compatible with the underlying representation.
     type Header: core::fmt::Debug + Clone;
 
+    /// Storage for a single ADU, used by `RecvBuffer`.
+    ///
+    /// This should be a byte array exactly `ADU_MAX_LENGTH` long.
+    type Buffer: AduBuffer;
+
+    /// Optional extra checks on received ADUs, set with `RecvBuffer::set_policy`.
+    ///
+    /// Protocols without any use `()`.
+    type Policy: Copy + Default;
+
     /// Extracts the length of the given ADU.
     ///
     /// If determining the length information requires examining the function code, an unrecognized
@@ -36,6 +50,42 @@ pub trait ModbusProtocol {
     /// If the ADU length is out of bounds (either too big or too small), returns `Err(BadLength)`.
     fn adu_length(data: &[u8]) -> Result<usize, ModbusError>;
 
+    /// Extracts the length of the given ADU, knowing which direction it's travelling in.
+    ///
+    /// Some protocols (like MODBUS RTU) have no length field, so the length has to be inferred
+    /// from the PDU, which is laid out differently in queries and responses. Knowing the
+    /// direction makes that unambiguous.
+    ///
+    /// The default implementation ignores the direction and calls `adu_length`.
+    fn directed_adu_length(data: &[u8], direction: Direction) -> Result<usize, ModbusError> {
+        let _ = direction;
+
+        Self::adu_length(data)
+    }
+
+    /// Checks whether `data` could be the start of an ADU, using only the bytes available.
+    ///
+    /// This is used by `RecvBuffer` in hunt mode to discard garbage quickly, without waiting
+    /// for a length field to be filled in by bytes that were never part of an ADU. It should
+    /// return `true` if there isn't enough data to tell.
+    ///
+    /// The default implementation accepts anything.
+    fn adu_plausible(data: &[u8]) -> bool {
+        let _ = data;
+
+        true
+    }
+
+    /// Converts a complete ADU from its wire format into the format expected by `adu_header`,
+    /// `adu_check`, and `pdu_body`, in place. Returns the length of the converted ADU.
+    ///
+    /// Most protocols send their ADUs as raw binary, so the default implementation leaves the
+    /// data alone. Protocols that encode their ADUs on the wire (like MODBUS ASCII) decode them
+    /// here, so that the PDU can be borrowed from the decoded data.
+    fn decode_adu(data: &mut [u8]) -> Result<usize, ModbusError> {
+        Ok(data.len())
+    }
+
     /// Extracts the header data associated with the given ADU.
     ///
     /// If determining the header information requires examining the function code, an unrecognized
@@ -48,9 +98,85 @@ pub trait ModbusProtocol {
     /// function code is represented by `Err(BadFuncCode)`.
     fn adu_check(data: &[u8]) -> Result<(), ModbusError>;
 
+    /// Checks an ADU that has passed `adu_check` against a policy.
+    ///
+    /// `RecvBuffer` calls this with its own policy and direction, and treats an error like one
+    /// from `adu_check`.
+    ///
+    /// The default implementation accepts everything.
+    fn policy_check(
+        data: &[u8],
+        policy: &Self::Policy,
+        direction: Option<Direction>,
+    ) -> Result<(), ModbusError> {
+        let _ = (data, policy, direction);
+
+        Ok(())
+    }
+
     /// Get the header information the inner PDU data, checking the checksum first.
     fn pdu_body(data: &[u8]) -> Result<&[u8], ModbusError>;
+
+    /// Wraps a PDU in an ADU, writing it into `out`. Returns the number of bytes written.
+    ///
+    /// Any header fields that are derived from the PDU (like lengths and checksums) are
+    /// calculated from `pdu`, and the values in `header` are ignored.
+    ///
+    /// If the PDU is empty or longer than the MODBUS maximum of 253 bytes, returns
+    /// `Err(BadLength)`. If `out` is too small to hold the whole ADU, returns `Err(BufferFull)`.
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError>;
+
+    /// Wraps a PDU in an ADU, returning it in a new `Vec`.
+    ///
+    /// See `write_adu` for details.
+    #[cfg(feature = "std")]
+    fn adu_to_vec(header: &Self::Header, pdu: &[u8]) -> Result<Vec<u8>, ModbusError> {
+        let mut out = vec![0; Self::ADU_MAX_LENGTH];
+        let length = Self::write_adu(header, pdu, &mut out)?;
+        out.truncate(length);
+
+        Ok(out)
+    }
+
+    /// Wraps a PDU in an ADU and writes the whole ADU to `writer`.
+    ///
+    /// Errors from `write_adu` are returned as `std::io::ErrorKind::InvalidData`.
+    #[cfg(feature = "std")]
+    fn write_adu_to<W: std::io::Write>(
+        header: &Self::Header,
+        pdu: &[u8],
+        mut writer: W,
+    ) -> std::io::Result<()> {
+        writer.write_all(&Self::adu_to_vec(header, pdu)?)
+    }
+}
+
+/// Fixed-size storage for an ADU
+///
+/// This is implemented for byte arrays of any length, so a protocol can size its buffer to fit
+/// its own largest ADU.
+pub trait AduBuffer: AsRef<[u8]> + AsMut<[u8]> {
+    /// Create a new buffer, filled with zeroes
+    fn zeroed() -> Self;
+}
+
+impl<const N: usize> AduBuffer for [u8; N] {
+    fn zeroed() -> Self {
+        [0; N]
+    }
+}
+
+/// Checks that a PDU is an acceptable length to be wrapped in an ADU
+fn check_pdu_length(pdu: &[u8]) -> Result<(), ModbusError> {
+    if pdu.is_empty() || pdu.len() > crate::pdu::PDU_MAX_LENGTH {
+        Err(ModbusError::BadLength)
+    } else {
+        Ok(())
+    }
 }
 
+pub use modbus_ascii::{ModbusAscii, ModbusAsciiHeader};
 pub use modbus_rtu::{ModbusRtu, ModbusRtuHeader};
-pub use tcp_modbus::{TcpModbus, TcpModbusHeader};
+pub use rtu_over_tcp::RtuOverTcp;
+pub use tcp_modbus::{MbapPolicy, TcpModbus, TcpModbusHeader};
+pub use udp_modbus::UdpModbus;
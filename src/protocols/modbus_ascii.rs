@@ -0,0 +1,371 @@
+use super::{check_pdu_length, ModbusProtocol};
+use crate::ModbusError;
+
+/// MODBUS ASCII protocol implementation
+///
+/// MODBUS ASCII frames carry the same address and PDU as MODBUS RTU, but every byte is sent as
+/// two uppercase hexadecimal characters. The frame starts with a colon, ends with a carriage
+/// return and line feed, and is protected by a 1-byte longitudinal redundancy check (LRC)
+/// instead of a CRC.
+///
+/// Visually, a MODBUS ASCII ADU looks like this:
+///
+/// <table>
+///   <tr>
+///     <th>Characters</th>
+///     <th>Field</th>
+///     <th>Decoded offset</th>
+///   </tr>
+///   <tr>
+///     <td>1</td>
+///     <td>Start (<code>:</code>)</td>
+///     <td>-</td>
+///   </tr>
+///   <tr>
+///     <td>2</td>
+///     <td>Address</td>
+///     <td>0</td>
+///   </tr>
+///   <tr>
+///     <td>2</td>
+///     <td>Function Code</td>
+///     <td>1</td>
+///   </tr>
+///   <tr>
+///     <td>0 up to 2 x 252</td>
+///     <td>Continuing PDU Data</td>
+///     <td>2...</td>
+///   </tr>
+///   <tr>
+///     <td>2</td>
+///     <td>LRC</td>
+///     <td>n - 1</td>
+///   </tr>
+///   <tr>
+///     <td>2</td>
+///     <td>End (CR LF)</td>
+///     <td>-</td>
+///   </tr>
+/// </table>
+///
+/// Because the PDU is hex-encoded on the wire, it can't be borrowed directly from the received
+/// data. This has some implications for implementing `ModbusProtocol` for ASCII.
+/// - `adu_length` looks for the CR LF at the end of the encoded frame.
+/// - `decode_adu` converts the encoded frame into binary (address, PDU, and LRC) in place.
+/// - `adu_header`, `adu_check`, and `pdu_body` operate on the decoded frame.
+pub struct ModbusAscii;
+
+// Marks the beginning of an encoded ADU
+const START: u8 = b':';
+
+// Marks the end of an encoded ADU
+const END: &[u8] = b"\r\n";
+
+// Length of the address field at the start of the decoded ADU
+const ADDRESS_LENGTH: usize = 1;
+
+// Length of the LRC at the end of the decoded ADU
+const LRC_LENGTH: usize = 1;
+
+/// MODBUS ASCII header data
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModbusAsciiHeader {
+    pub address: u8,
+    pub lrc: u8,
+}
+
+impl ModbusAscii {
+    // Start, address, function code, LRC, end
+    const ADU_MIN_LENGTH: usize = 1 + 2 + 2 + 2 + 2;
+
+    // Address, function code, LRC
+    const DECODED_MIN_LENGTH: usize = 3;
+
+    fn address(data: &[u8]) -> Option<u8> {
+        data.first().copied()
+    }
+
+    fn lrc(data: &[u8]) -> Option<u8> {
+        data.last().copied()
+    }
+
+    /// Checks that `data` is an acceptable length for a complete decoded ADU
+    fn check_length(data: &[u8]) -> Result<(), ModbusError> {
+        use ModbusError::{BadLength, NotEnoughData};
+
+        if data.len() < Self::DECODED_MIN_LENGTH {
+            Err(NotEnoughData)
+        } else if data.len() > (Self::ADU_MAX_LENGTH - 1 - END.len()) / 2 {
+            Err(BadLength)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Calculate the MODBUS longitudinal redundancy check of the given data
+pub(crate) fn lrc(data: &[u8]) -> u8 {
+    data.iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte))
+        .wrapping_neg()
+}
+
+/// Convert the low 4 bits of the given value into an uppercase hexadecimal character
+fn hex_character(value: u8) -> u8 {
+    b"0123456789ABCDEF"[(value & 0x0F) as usize]
+}
+
+/// Convert a single hexadecimal character into its value
+fn hex_value(character: u8) -> Result<u8, ModbusError> {
+    match character {
+        b'0'..=b'9' => Ok(character - b'0'),
+        b'A'..=b'F' => Ok(character - b'A' + 10),
+        b'a'..=b'f' => Ok(character - b'a' + 10),
+        _ => Err(ModbusError::BadFraming),
+    }
+}
+
+impl ModbusProtocol for ModbusAscii {
+    const ADU_MAX_LENGTH: usize = 513;
+
+    type Header = ModbusAsciiHeader;
+
+    type Buffer = [u8; Self::ADU_MAX_LENGTH];
+
+    type Policy = ();
+
+    fn adu_length(data: &[u8]) -> Result<usize, ModbusError> {
+        use ModbusError::{BadFraming, BadLength, NotEnoughData};
+
+        match data.first() {
+            None => return Err(NotEnoughData),
+            Some(&START) => {}
+            Some(_) => return Err(BadFraming),
+        }
+
+        let search_end = core::cmp::min(data.len(), Self::ADU_MAX_LENGTH);
+
+        match data[..search_end]
+            .windows(END.len())
+            .position(|window| window == END)
+        {
+            Some(end_index) => {
+                let adu_length = end_index + END.len();
+
+                if adu_length < Self::ADU_MIN_LENGTH {
+                    Err(BadLength)
+                } else {
+                    Ok(adu_length)
+                }
+            }
+            None if data.len() < Self::ADU_MAX_LENGTH => Err(NotEnoughData),
+            None => Err(BadLength),
+        }
+    }
+
+    fn decode_adu(data: &mut [u8]) -> Result<usize, ModbusError> {
+        use ModbusError::BadFraming;
+
+        let adu_length = Self::adu_length(data)?;
+        let encoded_length = adu_length - 1 - END.len();
+
+        if !encoded_length.is_multiple_of(2) {
+            return Err(BadFraming);
+        }
+
+        let decoded_length = encoded_length / 2;
+
+        // Each decoded byte is written before the characters it came from, so nothing is
+        // overwritten before it has been read
+        for index in 0..decoded_length {
+            let high = hex_value(data[1 + 2 * index])?;
+            let low = hex_value(data[2 + 2 * index])?;
+
+            data[index] = high << 4 | low;
+        }
+
+        Ok(decoded_length)
+    }
+
+    fn adu_header(data: &[u8]) -> Result<Self::Header, ModbusError> {
+        use ModbusError::NotEnoughData;
+
+        Self::check_length(data)?;
+
+        Ok(Self::Header {
+            address: Self::address(data).ok_or(NotEnoughData)?,
+            lrc: Self::lrc(data).ok_or(NotEnoughData)?,
+        })
+    }
+
+    fn adu_check(data: &[u8]) -> Result<(), ModbusError> {
+        use ModbusError::BadErrorCheck;
+
+        Self::check_length(data)?;
+
+        // check_length guarantees that there's room for the LRC
+        let lrc_start = data.len() - LRC_LENGTH;
+
+        if Self::lrc(data) == Some(lrc(&data[..lrc_start])) {
+            Ok(())
+        } else {
+            Err(BadErrorCheck)
+        }
+    }
+
+    fn pdu_body(data: &[u8]) -> Result<&[u8], ModbusError> {
+        Self::adu_check(data)?;
+
+        // We just checked that the length is correct in adu_check, so this
+        // won't panic
+        Ok(&data[ADDRESS_LENGTH..data.len() - LRC_LENGTH])
+    }
+
+    /// The LRC is calculated from the address and PDU, and the one in `header` is ignored. The
+    /// output is the encoded frame, including the start and end characters.
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError> {
+        use ModbusError::BufferFull;
+
+        check_pdu_length(pdu)?;
+
+        let adu_length = 1 + 2 * (ADDRESS_LENGTH + pdu.len() + LRC_LENGTH) + END.len();
+        let out = out.get_mut(..adu_length).ok_or(BufferFull)?;
+
+        let lrc = lrc(&[header.address]).wrapping_add(lrc(pdu));
+
+        let decoded = core::iter::once(header.address)
+            .chain(pdu.iter().copied())
+            .chain(core::iter::once(lrc));
+
+        out[0] = START;
+
+        for (index, byte) in decoded.enumerate() {
+            out[1 + 2 * index] = hex_character(byte >> 4);
+            out[2 + 2 * index] = hex_character(byte);
+        }
+
+        out[adu_length - END.len()..].copy_from_slice(END);
+
+        Ok(adu_length)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    fn decoded(encoded: &[u8], buffer: &mut [u8]) -> usize {
+        buffer[..encoded.len()].copy_from_slice(encoded);
+
+        ModbusAscii::decode_adu(&mut buffer[..encoded.len()]).unwrap()
+    }
+
+    #[test]
+    fn ascii_lrc() {
+        assert_eq!(lrc(&[]), 0);
+        assert_eq!(lrc(&[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03]), 0x7E);
+        assert_eq!(lrc(&[0xFF, 0x01]), 0x00);
+    }
+
+    #[test]
+    fn ascii_adu_length() {
+        for i in 0..=ADU5_ASCII.len() {
+            let len = ModbusAscii::adu_length(&ADU5_ASCII[..i]);
+
+            if i < ADU5_ADU_LENGTH {
+                assert_eq!(len, Err(NotEnoughData));
+            } else {
+                assert_eq!(len, Ok(ADU5_ADU_LENGTH));
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_adu_bad_length() {
+        let mut garbage = [b'0'; 600];
+        garbage[0] = START;
+
+        assert_eq!(ModbusAscii::adu_length(&garbage[..512]), Err(NotEnoughData));
+        assert_eq!(ModbusAscii::adu_length(&garbage[..513]), Err(BadLength));
+        assert_eq!(ModbusAscii::adu_length(&garbage[..600]), Err(BadLength));
+
+        assert_eq!(ModbusAscii::adu_length(b":0103\r\n"), Err(BadLength));
+        assert_eq!(
+            ModbusAscii::adu_length(b"1103006B00037E\r\n"),
+            Err(BadFraming)
+        );
+    }
+
+    #[test]
+    fn ascii_decode_adu() {
+        let mut buffer = [0; 32];
+        let length = decoded(ADU5_ASCII, &mut buffer);
+
+        assert_eq!(
+            &buffer[..length],
+            &[0x11, 0x03, 0x00, 0x6B, 0x00, 0x03, 0x7E]
+        );
+
+        let mut buffer = *b":1103006b00037e\r\n";
+        assert_eq!(ModbusAscii::decode_adu(&mut buffer), Ok(7));
+
+        let mut buffer = *b":1103006B00037\r\n";
+        assert_eq!(ModbusAscii::decode_adu(&mut buffer), Err(BadFraming));
+
+        let mut buffer = *b":1103006B0003XE\r\n";
+        assert_eq!(ModbusAscii::decode_adu(&mut buffer), Err(BadFraming));
+    }
+
+    #[test]
+    fn ascii_adu_header() {
+        let mut buffer = [0; 32];
+        let length = decoded(ADU5_ASCII, &mut buffer);
+
+        assert_eq!(ModbusAscii::adu_header(&buffer[..length]), Ok(ADU5_HEADER));
+        assert_eq!(ModbusAscii::adu_header(&buffer[..2]), Err(NotEnoughData));
+    }
+
+    #[test]
+    fn ascii_adu_check() {
+        let mut buffer = [0; 32];
+        let length = decoded(ADU5_ASCII, &mut buffer);
+
+        assert_eq!(ModbusAscii::adu_check(&buffer[..length]), Ok(()));
+
+        buffer[3] ^= 0x01;
+
+        assert_eq!(
+            ModbusAscii::adu_check(&buffer[..length]),
+            Err(BadErrorCheck)
+        );
+        assert_eq!(ModbusAscii::pdu_body(&buffer[..length]), Err(BadErrorCheck));
+    }
+
+    #[test]
+    fn ascii_pdu_body() {
+        let mut buffer = [0; 32];
+        let length = decoded(ADU5_ASCII, &mut buffer);
+
+        assert_eq!(ModbusAscii::pdu_body(&buffer[..length]), Ok(ADU5_PDU()));
+    }
+
+    #[test]
+    fn ascii_write_adu() {
+        let mut out = [0; 600];
+
+        let length = ModbusAscii::write_adu(&ADU5_HEADER, ADU5_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU5_ASCII);
+
+        assert_eq!(
+            ModbusAscii::write_adu(&ADU5_HEADER, ADU5_PDU(), &mut out[..ADU5_ADU_LENGTH - 1]),
+            Err(BufferFull)
+        );
+        assert_eq!(
+            ModbusAscii::write_adu(&ADU5_HEADER, &[0; 253], &mut out),
+            Ok(ModbusAscii::ADU_MAX_LENGTH)
+        );
+    }
+}
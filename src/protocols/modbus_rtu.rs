@@ -1,48 +1,435 @@
-use super::ModbusProtocol;
-use crate::ModbusError;
+use super::{check_pdu_length, ModbusProtocol};
+use crate::pdu::pdu_length;
+use crate::{Direction, ModbusError};
 
 /// MODBUS RTU protocol implementation
 ///
-/// This currently consists of unimplemented stubs, and will panic if used.
+/// MODBUS RTU frames are made up of a 1-byte slave address, followed by the protocol data unit
+/// (PDU), followed by a 2-byte CRC. The CRC is sent low byte first, unlike every other
+/// multi-byte value in MODBUS.
+///
+/// Visually, a MODBUS RTU ADU looks like this:
+///
+/// <table>
+///   <tr>
+///     <th>Offset</th>
+///     <th>Field</th>
+///     <th>Section</th>
+///   </tr>
+///   <tr>
+///     <td>0</td>
+///     <td>Address</td>
+///     <td>Header</td>
+///   </tr>
+///   <tr>
+///     <td>1</td>
+///     <td>Function Code</td>
+///     <td rowspan="2" style="vertical-align:middle">PDU</td>
+///   </tr>
+///   <tr>
+///     <td>2...</td>
+///     <td>Continuing PDU Data</td>
+///   </tr>
+///   <tr>
+///     <td>n - 2</td>
+///     <td>CRC (low byte)</td>
+///     <td rowspan="2" style="vertical-align:middle">Trailer</td>
+///   </tr>
+///   <tr>
+///     <td>n - 1</td>
+///     <td>CRC (high byte)</td>
+///   </tr>
+/// </table>
+///
+/// There is no length field, so the ADU length is inferred from the function code and any byte
+/// count fields in the PDU. Those are laid out differently in queries and responses, so
+/// `directed_adu_length` should be used where the direction is known. The other methods treat
+/// `data` as exactly one ADU.
 pub struct ModbusRtu;
 
+// Length of the address field at the start of the ADU
+const ADDRESS_LENGTH: usize = 1;
+
+// Length of the CRC at the end of the ADU
+const CRC_LENGTH: usize = 2;
+
 /// MODBUS RTU header data
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ModbusRtuHeader {
     pub address: u8,
     pub crc: u16,
 }
 
+impl ModbusRtu {
+    const ADU_MIN_LENGTH: usize = 4;
+
+    // Addresses above this are reserved
+    pub(crate) const MAX_ADDRESS: u8 = 247;
+
+    fn address(data: &[u8]) -> Option<u8> {
+        data.first().copied()
+    }
+
+    fn crc(data: &[u8]) -> Option<u16> {
+        let crc_start = data.len().checked_sub(CRC_LENGTH)?;
+
+        Some(u16::from_le_bytes([data[crc_start], data[crc_start + 1]]))
+    }
+
+    /// Checks that `data` is an acceptable length for a complete ADU
+    fn check_length(data: &[u8]) -> Result<(), ModbusError> {
+        use ModbusError::{BadLength, NotEnoughData};
+
+        if data.len() < Self::ADU_MIN_LENGTH {
+            Err(NotEnoughData)
+        } else if data.len() > Self::ADU_MAX_LENGTH {
+            Err(BadLength)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Add one byte to a running MODBUS CRC-16 calculation
+fn crc_update(crc: u16, byte: u8) -> u16 {
+    let mut crc = crc ^ u16::from(byte);
+
+    for _ in 0..8 {
+        crc = if crc & 1 == 0 {
+            crc >> 1
+        } else {
+            (crc >> 1) ^ 0xA001
+        };
+    }
+
+    crc
+}
+
+/// Calculate the MODBUS CRC-16 of the given data
+pub(crate) fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &byte| crc_update(crc, byte))
+}
+
 impl ModbusProtocol for ModbusRtu {
     const ADU_MAX_LENGTH: usize = 256;
 
     type Header = ModbusRtuHeader;
 
+    type Buffer = [u8; Self::ADU_MAX_LENGTH];
+
+    type Policy = ();
+
+    /// If the direction isn't known, both the query and response layouts are tried, and the one
+    /// whose CRC is valid is chosen, even if the other one would need more data. If neither is
+    /// valid, the shorter one is returned so that the bad frame can be reported by `adu_check`.
     fn adu_length(data: &[u8]) -> Result<usize, ModbusError> {
-        panic!(
-            "Not yet implemented: adu_length ({}-byte argument)",
-            data.len()
-        );
+        use ModbusError::NotEnoughData;
+
+        let query = Self::directed_adu_length(data, Direction::Query);
+        let response = Self::directed_adu_length(data, Direction::Response);
+
+        let candidates = [query, response];
+
+        // A complete, valid frame wins even if the other layout would need more data
+        for &candidate in &candidates {
+            if let Ok(length) = candidate {
+                if length <= data.len() && Self::adu_check(&data[..length]).is_ok() {
+                    return Ok(length);
+                }
+            }
+        }
+
+        let mut shortest_complete = None;
+
+        for &candidate in &candidates {
+            match candidate {
+                Ok(length) if length <= data.len() => {
+                    shortest_complete = Some(match shortest_complete {
+                        Some(shortest) if shortest < length => shortest,
+                        _ => length,
+                    });
+                }
+
+                // One interpretation could still turn out to be valid once more data arrives
+                Ok(_) | Err(NotEnoughData) => return Err(NotEnoughData),
+
+                Err(_) => {}
+            }
+        }
+
+        // Either both interpretations are complete but fail their CRC, or neither is valid
+        match shortest_complete {
+            Some(length) => Ok(length),
+            None => query.and(response),
+        }
+    }
+
+    fn directed_adu_length(data: &[u8], direction: Direction) -> Result<usize, ModbusError> {
+        use ModbusError::{BadLength, NotEnoughData};
+
+        let pdu = data.get(ADDRESS_LENGTH..).ok_or(NotEnoughData)?;
+        let adu_length = ADDRESS_LENGTH + pdu_length(pdu, direction)? + CRC_LENGTH;
+
+        if (Self::ADU_MIN_LENGTH..=Self::ADU_MAX_LENGTH).contains(&adu_length) {
+            Ok(adu_length)
+        } else {
+            Err(BadLength)
+        }
+    }
+
+    /// Reserved addresses (248 to 255) can't be the start of an ADU
+    fn adu_plausible(data: &[u8]) -> bool {
+        match Self::address(data) {
+            Some(address) => address <= Self::MAX_ADDRESS,
+            None => true,
+        }
     }
 
     fn adu_header(data: &[u8]) -> Result<Self::Header, ModbusError> {
-        panic!(
-            "Not yet implemented: adu_header ({}-byte argument)",
-            data.len()
-        );
+        use ModbusError::NotEnoughData;
+
+        Self::check_length(data)?;
+
+        Ok(Self::Header {
+            address: Self::address(data).ok_or(NotEnoughData)?,
+            crc: Self::crc(data).ok_or(NotEnoughData)?,
+        })
     }
 
     fn adu_check(data: &[u8]) -> Result<(), ModbusError> {
-        panic!(
-            "Not yet implemented: adu_check ({}-byte argument)",
-            data.len()
-        );
+        use ModbusError::{CrcMismatch, NotEnoughData};
+
+        Self::check_length(data)?;
+
+        // check_length guarantees that there's room for the CRC
+        let crc_start = data.len() - CRC_LENGTH;
+
+        let expected = crc16(&data[..crc_start]);
+        let actual = Self::crc(data).ok_or(NotEnoughData)?;
+
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(CrcMismatch { expected, actual })
+        }
     }
 
     fn pdu_body(data: &[u8]) -> Result<&[u8], ModbusError> {
-        panic!(
-            "Not yet implemented: pdu_body ({}-byte argument)",
-            data.len()
+        Self::adu_check(data)?;
+
+        // We just checked that the length is correct in adu_check, so this
+        // won't panic
+        Ok(&data[ADDRESS_LENGTH..data.len() - CRC_LENGTH])
+    }
+
+    /// The CRC is calculated from the address and PDU, and the one in `header` is ignored.
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError> {
+        use ModbusError::BufferFull;
+
+        check_pdu_length(pdu)?;
+
+        let adu_length = ADDRESS_LENGTH + pdu.len() + CRC_LENGTH;
+        let out = out.get_mut(..adu_length).ok_or(BufferFull)?;
+        let crc_start = adu_length - CRC_LENGTH;
+
+        out[0] = header.address;
+        out[ADDRESS_LENGTH..crc_start].copy_from_slice(pdu);
+
+        let crc = crc16(&out[..crc_start]);
+        out[crc_start..].copy_from_slice(&crc.to_le_bytes());
+
+        Ok(adu_length)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    #[test]
+    fn rtu_crc16() {
+        assert_eq!(crc16(&[]), 0xFFFF);
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A]), 0xCDC5);
+        assert_eq!(crc16(&ADU3_RTU[..ADU3_ADU_LENGTH - 2]), ADU3_HEADER.crc);
+        assert_eq!(crc16(&ADU4_RTU[..ADU4_ADU_LENGTH - 2]), ADU4_HEADER.crc);
+    }
+
+    #[test]
+    fn rtu_adu_length() {
+        for i in 0..=ADU3_RTU.len() {
+            let len = ModbusRtu::adu_length(&ADU3_RTU[..i]);
+
+            if i < ADU3_ADU_LENGTH {
+                assert_eq!(len, Err(NotEnoughData));
+            } else {
+                assert_eq!(len, Ok(ADU3_ADU_LENGTH));
+            }
+        }
+
+        for i in 0..=ADU4_RTU.len() {
+            let len = ModbusRtu::adu_length(&ADU4_RTU[..i]);
+
+            if i < ADU4_ADU_LENGTH {
+                assert_eq!(len, Err(NotEnoughData));
+            } else {
+                assert_eq!(len, Ok(ADU4_ADU_LENGTH));
+            }
+        }
+    }
+
+    #[test]
+    fn rtu_adu_length_trailing_data() {
+        let mut data = [0; 19];
+        data[..ADU3_ADU_LENGTH].copy_from_slice(ADU3_RTU);
+        data[ADU3_ADU_LENGTH..].copy_from_slice(ADU4_RTU);
+
+        assert_eq!(ModbusRtu::adu_length(&data), Ok(ADU3_ADU_LENGTH));
+    }
+
+    #[test]
+    fn rtu_adu_length_short_responses() {
+        // Responses that are complete before the query layout of the same function code would be
+        let read_coils: &[u8] = &[0x11, 0x01, 0x01, 0x05, 0x95, 0x4B];
+        let read_holding: &[u8] = &[0x11, 0x03, 0x02, 0x12, 0x34, 0x74, 0xF0];
+
+        for &adu in &[read_coils, read_holding] {
+            assert_eq!(ModbusRtu::adu_length(adu), Ok(adu.len()));
+            assert_eq!(
+                ModbusRtu::adu_length(&adu[..adu.len() - 1]),
+                Err(NotEnoughData)
+            );
+        }
+    }
+
+    #[test]
+    fn rtu_adu_bad_length() {
+        // Read Holding Registers responses with byte counts of 251 and 252
+        let adu_len_256: &[u8] = &[0x11, 0x03, 251];
+        let adu_len_257: &[u8] = &[0x11, 0x03, 252];
+
+        assert_eq!(
+            ModbusRtu::directed_adu_length(adu_len_256, Direction::Response),
+            Ok(256)
+        );
+        assert_eq!(
+            ModbusRtu::directed_adu_length(adu_len_257, Direction::Response),
+            Err(BadLength)
+        );
+
+        let garbage = [0xAA; 300];
+
+        assert_eq!(ModbusRtu::adu_check(&garbage[..3]), Err(NotEnoughData));
+        assert_eq!(ModbusRtu::adu_check(&garbage[..257]), Err(BadLength));
+    }
+
+    #[test]
+    fn rtu_adu_bad_func_code() {
+        let adu: &[u8] = &[0x11, 0x64, 0x00, 0x00, 0x00, 0x00];
+
+        assert_eq!(ModbusRtu::adu_length(&adu[..1]), Err(NotEnoughData));
+        assert_eq!(ModbusRtu::adu_length(adu), Err(BadFuncCode));
+        assert_eq!(
+            ModbusRtu::directed_adu_length(adu, Direction::Query),
+            Err(BadFuncCode)
+        );
+    }
+
+    #[test]
+    fn rtu_directed_adu_length() {
+        for i in 0..=ADU3_RTU.len() {
+            let len = ModbusRtu::directed_adu_length(&ADU3_RTU[..i], ADU3_DIRECTION);
+
+            if i < 2 {
+                assert_eq!(len, Err(NotEnoughData));
+            } else {
+                assert_eq!(len, Ok(ADU3_ADU_LENGTH));
+            }
+        }
+
+        for i in 0..=ADU4_RTU.len() {
+            let len = ModbusRtu::directed_adu_length(&ADU4_RTU[..i], ADU4_DIRECTION);
+
+            if i < 3 {
+                assert_eq!(len, Err(NotEnoughData));
+            } else {
+                assert_eq!(len, Ok(ADU4_ADU_LENGTH));
+            }
+        }
+    }
+
+    #[test]
+    fn rtu_exception_length() {
+        // Illegal Data Address exception for Read Holding Registers
+        let adu: &[u8] = &[0x0A, 0x83, 0x02, 0xB1, 0x33];
+
+        assert_eq!(
+            ModbusRtu::directed_adu_length(adu, Direction::Response),
+            Ok(5)
+        );
+        assert_eq!(ModbusRtu::adu_length(adu), Ok(5));
+        assert_eq!(ModbusRtu::adu_check(adu), Ok(()));
+    }
+
+    #[test]
+    fn rtu_adu_header() {
+        for i in 0..ModbusRtu::ADU_MIN_LENGTH {
+            assert_eq!(ModbusRtu::adu_header(&ADU3_RTU[..i]), Err(NotEnoughData));
+        }
+
+        assert_eq!(ModbusRtu::adu_header(ADU3_RTU), Ok(ADU3_HEADER));
+        assert_eq!(ModbusRtu::adu_header(ADU4_RTU), Ok(ADU4_HEADER));
+    }
+
+    #[test]
+    fn rtu_adu_check() {
+        assert_eq!(ModbusRtu::adu_check(ADU3_RTU), Ok(()));
+        assert_eq!(ModbusRtu::adu_check(ADU4_RTU), Ok(()));
+
+        let mut corrupted = [0; ADU3_ADU_LENGTH];
+        corrupted.copy_from_slice(ADU3_RTU);
+        corrupted[3] ^= 0x01;
+
+        let mismatch = CrcMismatch {
+            expected: 0x4727,
+            actual: ADU3_HEADER.crc,
+        };
+
+        assert_eq!(ModbusRtu::adu_check(&corrupted), Err(mismatch));
+        assert_eq!(ModbusRtu::pdu_body(&corrupted), Err(mismatch));
+    }
+
+    #[test]
+    fn rtu_pdu_body() {
+        assert_eq!(ModbusRtu::pdu_body(ADU3_RTU), Ok(ADU3_PDU()));
+        assert_eq!(ModbusRtu::pdu_body(ADU4_RTU), Ok(ADU4_PDU()));
+
+        assert_eq!(ModbusRtu::pdu_body(&ADU3_RTU[..3]), Err(NotEnoughData));
+    }
+
+    #[test]
+    fn rtu_write_adu() {
+        let mut out = [0; 300];
+
+        let length = ModbusRtu::write_adu(&ADU3_HEADER, ADU3_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU3_RTU);
+
+        let header = ModbusRtuHeader {
+            crc: 0,
+            ..ADU4_HEADER
+        };
+        let length = ModbusRtu::write_adu(&header, ADU4_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU4_RTU);
+
+        assert_eq!(
+            ModbusRtu::write_adu(&ADU3_HEADER, ADU3_PDU(), &mut out[..ADU3_ADU_LENGTH - 1]),
+            Err(BufferFull)
+        );
+        assert_eq!(
+            ModbusRtu::write_adu(&ADU3_HEADER, &[], &mut out),
+            Err(BadLength)
         );
     }
 }
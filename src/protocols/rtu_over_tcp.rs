@@ -0,0 +1,126 @@
+use super::{ModbusProtocol, ModbusRtu, ModbusRtuHeader};
+use crate::{Direction, ModbusError};
+
+/// MODBUS RTU over TCP protocol implementation
+///
+/// Many serial-to-Ethernet converters forward MODBUS RTU frames over a TCP connection exactly as
+/// they appear on the serial line: address, PDU, and CRC, without a MBAP header. See
+/// `ModbusRtu` for the frame layout.
+///
+/// There are no silent intervals on a TCP stream, so the ADU length always has to be inferred
+/// from the PDU, and a frame can be split across reads in any way. `RecvBuffer` handles the
+/// splitting. As with `ModbusRtu`, create the buffer with `with_direction` if you know which
+/// direction the data is travelling in.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::recv_buffer::*;
+/// use modbus_core::protocols::*;
+/// use modbus_core::Direction;
+///
+/// let mut buf: RecvBuffer<RtuOverTcp> = RecvBuffer::with_direction(Direction::Query);
+///
+/// assert!(buf.process(&[0x11, 0x03, 0x00]).is_err());
+///
+/// let (packet, _) = buf.process(&[0x6b, 0x00, 0x03, 0x76, 0x87]).unwrap();
+/// assert_eq!(packet.header.address, 0x11);
+/// assert_eq!(packet.pdu, &[0x03, 0x00, 0x6b, 0x00, 0x03]);
+/// ```
+pub struct RtuOverTcp;
+
+impl ModbusProtocol for RtuOverTcp {
+    const ADU_MAX_LENGTH: usize = ModbusRtu::ADU_MAX_LENGTH;
+
+    type Header = ModbusRtuHeader;
+
+    type Buffer = [u8; Self::ADU_MAX_LENGTH];
+
+    type Policy = ();
+
+    fn adu_length(data: &[u8]) -> Result<usize, ModbusError> {
+        ModbusRtu::adu_length(data)
+    }
+
+    fn directed_adu_length(data: &[u8], direction: Direction) -> Result<usize, ModbusError> {
+        ModbusRtu::directed_adu_length(data, direction)
+    }
+
+    fn adu_plausible(data: &[u8]) -> bool {
+        ModbusRtu::adu_plausible(data)
+    }
+
+    fn adu_header(data: &[u8]) -> Result<Self::Header, ModbusError> {
+        ModbusRtu::adu_header(data)
+    }
+
+    fn adu_check(data: &[u8]) -> Result<(), ModbusError> {
+        ModbusRtu::adu_check(data)
+    }
+
+    fn pdu_body(data: &[u8]) -> Result<&[u8], ModbusError> {
+        ModbusRtu::pdu_body(data)
+    }
+
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError> {
+        ModbusRtu::write_adu(header, pdu, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::recv_buffer::RecvBuffer;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    #[test]
+    fn rtu_over_tcp_matches_rtu() {
+        assert_eq!(RtuOverTcp::adu_length(ADU3_RTU), Ok(ADU3_ADU_LENGTH));
+        assert_eq!(RtuOverTcp::adu_length(ADU4_RTU), Ok(ADU4_ADU_LENGTH));
+        assert_eq!(RtuOverTcp::adu_header(ADU3_RTU), Ok(ADU3_HEADER));
+        assert_eq!(RtuOverTcp::pdu_body(ADU4_RTU), Ok(ADU4_PDU()));
+        assert_eq!(
+            RtuOverTcp::adu_check(&ADU4_RTU[1..]),
+            Err(CrcMismatch {
+                expected: 0x06C3,
+                actual: ADU4_HEADER.crc
+            })
+        );
+
+        let mut out = [0; 16];
+        let length = RtuOverTcp::write_adu(&ADU3_HEADER, ADU3_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU3_RTU);
+    }
+
+    #[test]
+    fn rtu_over_tcp_stream() {
+        let adus = [ADU3_RTU, ADU4_RTU, ADU3_RTU, ADU4_RTU];
+        let headers = [ADU3_HEADER, ADU4_HEADER, ADU3_HEADER, ADU4_HEADER];
+
+        let mut input = [0; 2 * (ADU3_ADU_LENGTH + ADU4_ADU_LENGTH)];
+        let mut offset = 0;
+
+        for adu in adus.iter() {
+            input[offset..offset + adu.len()].copy_from_slice(adu);
+            offset += adu.len();
+        }
+
+        // Chunk sizes that split the ADUs in different places
+        for chunk_size in 1..=input.len() {
+            let mut buf = RecvBuffer::<RtuOverTcp>::new();
+            let mut found = 0;
+
+            for chunk in input.chunks(chunk_size) {
+                buf.drain(chunk, |packet| {
+                    assert_eq!(packet.header, headers[found]);
+                    found += 1;
+                })
+                .unwrap();
+            }
+
+            assert_eq!(found, adus.len(), "chunk size {}", chunk_size);
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use super::ModbusProtocol;
-use crate::ModbusError;
+use super::{check_pdu_length, ModbusProtocol};
+use crate::pdu::pdu_length;
+use crate::{Direction, ModbusError};
 
 /// TCP MODBUS protocol implementation
 ///
@@ -71,7 +72,7 @@ pub struct TcpModbus;
 
 // Length of the MODBUS Application Protocol header
 // 2-byte transaction ID, 2-byte protocol ID, 2-byte length, 1-byte unit ID
-const MBAP_LENGTH: usize = 7;
+pub(super) const MBAP_LENGTH: usize = 7;
 
 // Number of APU bytes excluded from the length field
 // This is slightly different from the MBAP length because the 1-byte unit ID is
@@ -88,23 +89,141 @@ pub struct TcpModbusHeader {
     pub unit_id: u8,
 }
 
+/// Extra checks on MBAP headers, beyond what's needed to find the ADU
+///
+/// `TcpModbus` (and `UdpModbus`) only look at the MBAP length field, so that ADUs from devices
+/// that bend the rules can still be received. To be stricter, set a policy on the `RecvBuffer`
+/// with `set_policy`, so that ADUs are rejected as they're received, or use `check` on packets
+/// that have already been received.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::protocols::*;
+/// use modbus_core::recv_buffer::*;
+/// use modbus_core::Direction;
+/// use modbus_core::ModbusError::*;
+///
+/// // Protocol ID 1
+/// let adu = [0x00, 0x01, 0x00, 0x01, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03];
+///
+/// let mut buf: RecvBuffer<TcpModbus> = RecvBuffer::new();
+/// let (packet, _) = buf.process(&adu).unwrap();
+///
+/// assert_eq!(MbapPolicy::LENIENT.check(&packet.header, packet.pdu, None), Ok(()));
+/// assert_eq!(
+///     MbapPolicy::STRICT.check(&packet.header, packet.pdu, Some(Direction::Query)),
+///     Err(BadProtocolId(1))
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MbapPolicy {
+    /// Reject protocol IDs other than 0 with `BadProtocolId`
+    pub check_protocol_id: bool,
+
+    /// Check that the PDU is as long as its function code and byte count fields say it should
+    /// be
+    ///
+    /// Since the PDU length comes from the MBAP length field, this catches length fields that
+    /// disagree with the PDU. A PDU whose length can't be worked out, like one with an
+    /// unrecognized function code, is rejected too.
+    pub check_length: bool,
+
+    /// Reject unit IDs in the reserved range (248 to 254) with `BadUnitId`
+    ///
+    /// 0 is the broadcast address, 1 to 247 are serial line addresses, and 255 is used when the
+    /// unit ID isn't needed.
+    pub check_unit_id: bool,
+}
+
+impl MbapPolicy {
+    /// Accept anything that `TcpModbus` can receive
+    pub const LENIENT: Self = MbapPolicy {
+        check_protocol_id: false,
+        check_length: false,
+        check_unit_id: false,
+    };
+
+    /// Do every check
+    pub const STRICT: Self = MbapPolicy {
+        check_protocol_id: true,
+        check_length: true,
+        check_unit_id: true,
+    };
+
+    /// Check a received header and PDU against this policy
+    ///
+    /// The direction is needed to check the length. If it isn't known, the PDU is accepted if
+    /// it has the right length for either a query or a response.
+    ///
+    /// A length mismatch is reported as `ByteCountMismatch`, with the length the PDU should have
+    /// had.
+    pub fn check(
+        &self,
+        header: &TcpModbusHeader,
+        pdu: &[u8],
+        direction: Option<Direction>,
+    ) -> Result<(), ModbusError> {
+        use ModbusError::{BadProtocolId, BadUnitId};
+
+        if self.check_protocol_id && header.protocol_id != 0 {
+            return Err(BadProtocolId(header.protocol_id));
+        }
+
+        if self.check_unit_id && (248..=254).contains(&header.unit_id) {
+            return Err(BadUnitId(header.unit_id));
+        }
+
+        if self.check_length {
+            match direction {
+                Some(direction) => check_pdu_length_field(pdu, direction)?,
+                None => check_pdu_length_field(pdu, Direction::Query)
+                    .or_else(|_| check_pdu_length_field(pdu, Direction::Response))?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for MbapPolicy {
+    fn default() -> Self {
+        Self::LENIENT
+    }
+}
+
+/// Checks that a PDU is exactly as long as its own fields say
+fn check_pdu_length_field(pdu: &[u8], direction: Direction) -> Result<(), ModbusError> {
+    use ModbusError::{BadLength, ByteCountMismatch, NotEnoughData};
+
+    match pdu_length(pdu, direction) {
+        Ok(expected) if expected == pdu.len() => Ok(()),
+        Ok(expected) => Err(ByteCountMismatch {
+            expected,
+            actual: pdu.len(),
+        }),
+        Err(NotEnoughData) => Err(BadLength),
+        Err(e) => Err(e),
+    }
+}
+
 impl TcpModbus {
     const ADU_MIN_LENGTH: usize = 8;
 
-    fn protocol_id(data: &[u8]) -> Option<u16> {
+    pub(super) fn protocol_id(data: &[u8]) -> Option<u16> {
         Some(u16::from_be_bytes([*data.get(2)?, *data.get(3)?]))
     }
 
-    fn transaction_id(data: &[u8]) -> Option<u16> {
-        Some(u16::from_be_bytes([*data.get(0)?, *data.get(1)?]))
+    pub(super) fn transaction_id(data: &[u8]) -> Option<u16> {
+        Some(u16::from_be_bytes([*data.first()?, *data.get(1)?]))
     }
 
-    fn length(data: &[u8]) -> Option<u16> {
+    pub(super) fn length(data: &[u8]) -> Option<u16> {
         Some(u16::from_be_bytes([*data.get(4)?, *data.get(5)?]))
     }
 
-    fn unit_id(data: &[u8]) -> Option<u8> {
-        data.get(6).map(|&x| x)
+    pub(super) fn unit_id(data: &[u8]) -> Option<u8> {
+        data.get(6).copied()
     }
 }
 
@@ -113,6 +232,10 @@ impl ModbusProtocol for TcpModbus {
 
     type Header = TcpModbusHeader;
 
+    type Buffer = [u8; Self::ADU_MAX_LENGTH];
+
+    type Policy = MbapPolicy;
+
     fn adu_length(data: &[u8]) -> Result<usize, ModbusError> {
         use ModbusError::{BadLength, NotEnoughData};
 
@@ -121,13 +244,19 @@ impl ModbusProtocol for TcpModbus {
         let adu_length = Self::length(data).ok_or(NotEnoughData)? as usize + EXCLUDED_LENGTH;
 
         // Check if the length is not too long or too short
-        if Self::ADU_MIN_LENGTH <= adu_length && adu_length <= Self::ADU_MAX_LENGTH {
+        if (Self::ADU_MIN_LENGTH..=Self::ADU_MAX_LENGTH).contains(&adu_length) {
             Ok(adu_length)
         } else {
             Err(BadLength)
         }
     }
 
+    /// MODBUS always uses a protocol ID of 0, so anything else can't be the start of an ADU
+    fn adu_plausible(data: &[u8]) -> bool {
+        // Check whichever protocol ID bytes have arrived so far
+        data.iter().skip(2).take(2).all(|&byte| byte == 0)
+    }
+
     fn adu_header(data: &[u8]) -> Result<Self::Header, ModbusError> {
         use ModbusError::NotEnoughData;
 
@@ -160,6 +289,36 @@ impl ModbusProtocol for TcpModbus {
         // won't panic
         Ok(&data[MBAP_LENGTH..])
     }
+
+    fn policy_check(
+        data: &[u8],
+        policy: &MbapPolicy,
+        direction: Option<Direction>,
+    ) -> Result<(), ModbusError> {
+        policy.check(&Self::adu_header(data)?, Self::pdu_body(data)?, direction)
+    }
+
+    /// The length field is calculated from the PDU length. The other header fields are written
+    /// as-is.
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError> {
+        use ModbusError::BufferFull;
+
+        check_pdu_length(pdu)?;
+
+        let adu_length = MBAP_LENGTH + pdu.len();
+        let out = out.get_mut(..adu_length).ok_or(BufferFull)?;
+
+        // The length field covers the unit ID and the PDU
+        let length = (adu_length - EXCLUDED_LENGTH) as u16;
+
+        out[0..2].copy_from_slice(&header.transaction_id.to_be_bytes());
+        out[2..4].copy_from_slice(&header.protocol_id.to_be_bytes());
+        out[4..6].copy_from_slice(&length.to_be_bytes());
+        out[6] = header.unit_id;
+        out[MBAP_LENGTH..].copy_from_slice(pdu);
+
+        Ok(adu_length)
+    }
 }
 
 #[cfg(test)]
@@ -281,4 +440,138 @@ mod test {
         assert_eq!(TcpModbus::adu_length(adu_len_261), Err(BadLength));
         assert_eq!(TcpModbus::adu_length(adu_len_262), Err(BadLength));
     }
+
+    #[test]
+    fn tcp_write_adu() {
+        let mut out = [0; 300];
+
+        let length = TcpModbus::write_adu(&ADU1_HEADER, ADU1_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU1_TCP);
+
+        let length = TcpModbus::write_adu(&ADU2_HEADER, ADU2_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU2_TCP);
+
+        // The length field is always calculated, even if the header disagrees
+        let header = TcpModbusHeader {
+            length: 1000,
+            ..ADU2_HEADER
+        };
+        let length = TcpModbus::write_adu(&header, ADU2_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU2_TCP);
+    }
+
+    #[test]
+    fn tcp_write_adu_errors() {
+        let mut out = [0; 300];
+
+        assert_eq!(
+            TcpModbus::write_adu(&ADU2_HEADER, ADU2_PDU(), &mut out[..ADU2_ADU_LENGTH - 1]),
+            Err(BufferFull)
+        );
+        assert_eq!(
+            TcpModbus::write_adu(&ADU2_HEADER, &[], &mut out),
+            Err(BadLength)
+        );
+        assert_eq!(
+            TcpModbus::write_adu(&ADU2_HEADER, &[0; 254], &mut out),
+            Err(BadLength)
+        );
+        assert_eq!(
+            TcpModbus::write_adu(&ADU2_HEADER, &[0; 253], &mut out),
+            Ok(260)
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn tcp_write_adu_to() {
+        let mut out = Vec::new();
+
+        TcpModbus::write_adu_to(&ADU2_HEADER, ADU2_PDU(), &mut out).unwrap();
+        assert_eq!(out, ADU2_TCP);
+        assert_eq!(TcpModbus::adu_to_vec(&ADU2_HEADER, ADU2_PDU()), Ok(out));
+
+        let error = TcpModbus::write_adu_to(&ADU2_HEADER, &[], Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn mbap_policy() {
+        use crate::Direction::*;
+
+        let pdu = ADU1_PDU();
+        let header = |protocol_id, unit_id| TcpModbusHeader {
+            protocol_id,
+            unit_id,
+            ..ADU1_HEADER
+        };
+
+        for &policy in &[MbapPolicy::LENIENT, MbapPolicy::STRICT] {
+            assert_eq!(
+                policy.check(&ADU1_HEADER, pdu, Some(ADU1_DIRECTION)),
+                Ok(())
+            );
+            assert_eq!(policy.check(&ADU1_HEADER, pdu, None), Ok(()));
+            assert_eq!(policy.check(&header(0, 0), pdu, None), Ok(()));
+            assert_eq!(policy.check(&header(0, 247), pdu, None), Ok(()));
+            assert_eq!(policy.check(&header(0, 255), pdu, None), Ok(()));
+        }
+
+        let lenient = MbapPolicy::LENIENT;
+        let strict = MbapPolicy::STRICT;
+
+        assert_eq!(lenient.check(&header(1, 248), &[0x03, 0x00], None), Ok(()));
+
+        assert_eq!(
+            strict.check(&header(0x1234, 0x11), pdu, None),
+            Err(BadProtocolId(0x1234))
+        );
+        assert_eq!(
+            strict.check(&header(0, 248), pdu, None),
+            Err(BadUnitId(248))
+        );
+        assert_eq!(
+            strict.check(&header(0, 254), pdu, None),
+            Err(BadUnitId(254))
+        );
+
+        // A Read Holding Registers query is always 5 bytes
+        assert_eq!(
+            strict.check(
+                &ADU1_HEADER,
+                &[0x03, 0x00, 0x6b, 0x00, 0x03, 0x00],
+                Some(Query)
+            ),
+            Err(ByteCountMismatch {
+                expected: 5,
+                actual: 6
+            })
+        );
+
+        // The byte count says 4 bytes of registers, but there are only 2
+        assert_eq!(
+            strict.check(&ADU1_HEADER, &[0x03, 0x04, 0x00, 0x01], Some(Response)),
+            Err(ByteCountMismatch {
+                expected: 6,
+                actual: 4
+            })
+        );
+        assert_eq!(
+            strict.check(&ADU1_HEADER, &[0x03, 0x02, 0x00, 0x01], Some(Response)),
+            Ok(())
+        );
+        assert_eq!(
+            strict.check(&ADU1_HEADER, &[0x03, 0x02, 0x00, 0x01], Some(Query)),
+            Err(ByteCountMismatch {
+                expected: 5,
+                actual: 4
+            })
+        );
+
+        assert_eq!(strict.check(&ADU1_HEADER, &[0x30], None), Err(BadFuncCode));
+        assert_eq!(
+            strict.check(&ADU1_HEADER, &[0x03], Some(Response)),
+            Err(BadLength)
+        );
+    }
 }
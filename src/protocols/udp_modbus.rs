@@ -0,0 +1,153 @@
+use super::tcp_modbus::MBAP_LENGTH;
+use super::{MbapPolicy, ModbusProtocol, TcpModbus, TcpModbusHeader};
+use crate::recv_buffer::Packet;
+use crate::{Direction, ModbusError};
+
+/// MODBUS UDP protocol implementation
+///
+/// MODBUS UDP uses the same MBAP header as TCP MODBUS (see `TcpModbus`), but every datagram
+/// holds exactly one ADU. There's no stream to reassemble, so datagrams can be parsed directly
+/// with `parse_datagram`, without a `RecvBuffer`.
+///
+/// Because an ADU can't continue in a later datagram, `adu_check` and `pdu_body` require `data`
+/// to be exactly as long as the MBAP length field says. Missing or trailing bytes are
+/// `Err(BadLength)`.
+pub struct UdpModbus;
+
+impl UdpModbus {
+    /// Parse a complete ADU from a single datagram
+    ///
+    /// If the datagram is shorter or longer than the MBAP length field says, returns
+    /// `Err(BadLength)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::protocols::*;
+    ///
+    /// let datagram = [0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03];
+    ///
+    /// let packet = UdpModbus::parse_datagram(&datagram).unwrap();
+    /// assert_eq!(packet.header.transaction_id, 1);
+    /// assert_eq!(packet.header.unit_id, 0x11);
+    /// assert_eq!(packet.pdu, &[0x03, 0x00, 0x6b, 0x00, 0x03]);
+    ///
+    /// assert!(UdpModbus::parse_datagram(&datagram[..11]).is_err());
+    /// ```
+    pub fn parse_datagram(datagram: &[u8]) -> Result<Packet<'_, Self>, ModbusError> {
+        Ok(Packet {
+            header: Self::adu_header(datagram)?,
+            pdu: Self::pdu_body(datagram)?,
+        })
+    }
+}
+
+impl ModbusProtocol for UdpModbus {
+    const ADU_MAX_LENGTH: usize = TcpModbus::ADU_MAX_LENGTH;
+
+    type Header = TcpModbusHeader;
+
+    type Buffer = [u8; Self::ADU_MAX_LENGTH];
+
+    type Policy = MbapPolicy;
+
+    fn adu_length(data: &[u8]) -> Result<usize, ModbusError> {
+        TcpModbus::adu_length(data)
+    }
+
+    fn adu_plausible(data: &[u8]) -> bool {
+        TcpModbus::adu_plausible(data)
+    }
+
+    fn adu_header(data: &[u8]) -> Result<Self::Header, ModbusError> {
+        use ModbusError::BadLength;
+
+        Ok(Self::Header {
+            transaction_id: TcpModbus::transaction_id(data).ok_or(BadLength)?,
+            protocol_id: TcpModbus::protocol_id(data).ok_or(BadLength)?,
+            length: TcpModbus::length(data).ok_or(BadLength)?,
+            unit_id: TcpModbus::unit_id(data).ok_or(BadLength)?,
+        })
+    }
+
+    /// MODBUS UDP doesn't have application-layer checksums, so this just confirms that `data`
+    /// is exactly one whole ADU
+    fn adu_check(data: &[u8]) -> Result<(), ModbusError> {
+        use ModbusError::{BadLength, NotEnoughData};
+
+        match Self::adu_length(data) {
+            Ok(length) if length == data.len() => Ok(()),
+            Ok(_) | Err(NotEnoughData) => Err(BadLength),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn pdu_body(data: &[u8]) -> Result<&[u8], ModbusError> {
+        Self::adu_check(data)?;
+
+        // We just checked that the length is correct in adu_check, so this
+        // won't panic
+        Ok(&data[MBAP_LENGTH..])
+    }
+
+    fn policy_check(
+        data: &[u8],
+        policy: &MbapPolicy,
+        direction: Option<Direction>,
+    ) -> Result<(), ModbusError> {
+        policy.check(&Self::adu_header(data)?, Self::pdu_body(data)?, direction)
+    }
+
+    /// This is the same as TCP MODBUS. The length field is calculated from the PDU length, and
+    /// the other header fields are written as-is.
+    fn write_adu(header: &Self::Header, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError> {
+        TcpModbus::write_adu(header, pdu, out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    #[test]
+    fn udp_parse_datagram() {
+        let packet = UdpModbus::parse_datagram(ADU1_TCP).unwrap();
+        assert_eq!(packet.header, ADU1_HEADER);
+        assert_eq!(packet.pdu, ADU1_PDU());
+
+        let packet = UdpModbus::parse_datagram(ADU2_TCP).unwrap();
+        assert_eq!(packet.header, ADU2_HEADER);
+        assert_eq!(packet.pdu, ADU2_PDU());
+    }
+
+    #[test]
+    fn udp_missing_or_trailing_bytes() {
+        let mut long = [0; 300];
+        long[..ADU1_TCP.len()].copy_from_slice(ADU1_TCP);
+
+        for length in 0..long.len() {
+            let result = UdpModbus::parse_datagram(&long[..length]);
+
+            if length == ADU1_TCP.len() {
+                assert!(result.is_ok());
+            } else {
+                assert_eq!(result.unwrap_err(), BadLength);
+            }
+        }
+    }
+
+    #[test]
+    fn udp_write_adu() {
+        let mut out = [0; 300];
+
+        let length = UdpModbus::write_adu(&ADU2_HEADER, ADU2_PDU(), &mut out).unwrap();
+        assert_eq!(&out[..length], ADU2_TCP);
+        assert_eq!(
+            UdpModbus::parse_datagram(&out[..length]).unwrap().header,
+            ADU2_HEADER
+        );
+    }
+}
@@ -2,23 +2,8 @@
 //!
 //! See the `RecvBuffer` struct for details.
 
-use crate::protocols::ModbusProtocol;
-use crate::ModbusError;
-
-// See https://stackoverflow.com/questions/53619695/
-const fn const_max(a: usize, b: usize) -> usize {
-    [a, b][(a < b) as usize]
-}
-
-// This just has to be the largest value used by any protocol
-// If additional protocols are added, this value should be increased by adding
-// nested const_max calls
-//
-// Hack until https://github.com/rust-lang/rust/issues/43408 is resolved
-const BUFFER_LEN: usize = const_max(
-    crate::protocols::TcpModbus::ADU_MAX_LENGTH,
-    crate::protocols::ModbusRtu::ADU_MAX_LENGTH,
-);
+use crate::protocols::{AduBuffer, ModbusProtocol};
+use crate::{Direction, ModbusError};
 
 /// Converts a raw byte stream into a sequence of MODBUS packets
 ///
@@ -26,27 +11,146 @@ const BUFFER_LEN: usize = const_max(
 /// packets. Each packet corresponds to an application data unit (ADU), and contains some header
 /// data (dependent on the underlying transport protocol) and a protocol data unit (PDU) that does
 /// not depend on the underlying transport protocol.
+///
+/// Some protocols (like MODBUS RTU) can only find the end of an ADU by looking inside the PDU,
+/// which is laid out differently for queries and responses. If you know which direction your
+/// data is travelling in, create the buffer with `with_direction` so the right layout is used.
+///
+/// The buffer's storage is the protocol's `Buffer` type, so it's only as large as the largest
+/// ADU of that protocol.
+///
+/// By default, any invalid data causes everything in the buffer to be thrown away. On noisy
+/// links, that can throw away the start of a good ADU along with the garbage in front of it. See
+/// `set_hunt_mode` for an alternative.
 pub struct RecvBuffer<P: ModbusProtocol> {
     // This is a critical invariant:
-    // If the buffer ever contains a complete APU, contains_complete must be true and size_used
-    // must hold its actual length.
-    raw_buffer: [u8; BUFFER_LEN],
+    // If the buffer ever contains a complete ADU, it starts at index 0, and packet_length must
+    // hold its length as it was received. Any bytes after it haven't been processed yet.
+    raw_buffer: P::Buffer,
     size_used: usize,
-    contains_complete: bool,
+    packet_length: Option<usize>,
+    direction: Option<Direction>,
+    hunt_mode: bool,
+    skipped: usize,
+    policy: P::Policy,
     _protocol: core::marker::PhantomData<P>,
 }
 
 impl<P: ModbusProtocol> RecvBuffer<P> {
     /// Create a new receive buffer
+    ///
+    /// The buffer doesn't assume anything about which direction the data is travelling in.
     pub fn new() -> Self {
         RecvBuffer {
-            raw_buffer: [0; BUFFER_LEN],
+            raw_buffer: P::Buffer::zeroed(),
             size_used: 0,
-            contains_complete: false,
+            packet_length: None,
+            direction: None,
+            hunt_mode: false,
+            skipped: 0,
+            policy: Default::default(),
             _protocol: Default::default(),
         }
     }
 
+    /// Create a new receive buffer for data travelling in a known direction
+    ///
+    /// A server receives queries, and a client receives responses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::recv_buffer::*;
+    /// use modbus_core::protocols::*;
+    /// use modbus_core::Direction;
+    ///
+    /// let mut buf: RecvBuffer<ModbusRtu> = RecvBuffer::with_direction(Direction::Query);
+    ///
+    /// let (packet, _) = buf.process(&[0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87]).unwrap();
+    /// assert_eq!(packet.header.address, 0x11);
+    /// assert_eq!(packet.pdu, &[0x03, 0x00, 0x6b, 0x00, 0x03]);
+    /// ```
+    pub fn with_direction(direction: Direction) -> Self {
+        RecvBuffer {
+            direction: Some(direction),
+            ..Self::new()
+        }
+    }
+
+    /// The direction this buffer was created for, if any
+    pub fn direction(&self) -> Option<Direction> {
+        self.direction
+    }
+
+    /// Turn hunt mode on or off
+    ///
+    /// Normally, invalid data causes `process` to return an error and clear the whole buffer. In
+    /// hunt mode, invalid data is discarded one byte at a time instead, until the buffer starts
+    /// with something that could be an ADU. This lets the buffer regain frame sync without
+    /// losing a good ADU that arrived in the same chunk as some garbage.
+    ///
+    /// While hunting, `process` never returns errors other than `NotEnoughData`. The number of
+    /// bytes thrown away is counted by `skipped`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::recv_buffer::*;
+    /// use modbus_core::protocols::*;
+    ///
+    /// let mut buf: RecvBuffer<ModbusRtu> = RecvBuffer::new();
+    /// buf.set_hunt_mode(true);
+    ///
+    /// // Line noise, followed by a good ADU
+    /// let data = [0xFF, 0x00, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+    ///
+    /// let (packet, _) = buf.process(&data).unwrap();
+    /// assert_eq!(packet.header.address, 0x11);
+    /// assert_eq!(buf.skipped(), 2);
+    /// ```
+    pub fn set_hunt_mode(&mut self, hunt_mode: bool) {
+        self.hunt_mode = hunt_mode;
+    }
+
+    /// Whether hunt mode is on
+    pub fn hunt_mode(&self) -> bool {
+        self.hunt_mode
+    }
+
+    /// The total number of bytes that have been discarded in hunt mode
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Set the protocol's extra checks on received ADUs
+    ///
+    /// ADUs that fail them are treated like any other invalid data. For `TcpModbus` and
+    /// `UdpModbus`, this is an `MbapPolicy`, which is lenient by default. Other protocols don't
+    /// have any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::recv_buffer::*;
+    /// use modbus_core::protocols::*;
+    /// use modbus_core::ModbusError::*;
+    ///
+    /// let mut buf: RecvBuffer<TcpModbus> = RecvBuffer::new();
+    /// buf.set_policy(MbapPolicy::STRICT);
+    ///
+    /// // Protocol ID 1
+    /// let adu = [0x00, 0x01, 0x00, 0x01, 0x00, 0x06, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03];
+    /// assert_eq!(buf.process(&adu).unwrap_err(), BadProtocolId(1));
+    /// ```
+    pub fn set_policy(&mut self, policy: P::Policy) {
+        self.policy = policy;
+    }
+
+    /// The protocol's extra checks on received ADUs
+    pub fn policy(&self) -> P::Policy {
+        self.policy
+    }
+
     /// Process some received data through the buffer
     ///
     /// Your packet data is appended to any data already in the buffer, and checked to see if it
@@ -63,73 +167,200 @@ impl<P: ModbusProtocol> RecvBuffer<P> {
     /// - It's somehow invalid (length too long, bad function code, etc.)
     ///     - You get `Err` with some other error
     ///     - All data in the buffer is cleared, including whatever you passed in
+    ///
+    /// In hunt mode, invalid data is skipped instead (see `set_hunt_mode`). Skipping can expose
+    /// ADUs in data that was already buffered, so some of those bytes can be left over after an
+    /// ADU is found, and the excess slice won't contain them. Calling `process` with an empty
+    /// slice returns the next ADU from the leftover bytes, if there is one.
     pub fn process<'p, 'b>(
         &'b mut self,
         data: &'p [u8],
     ) -> Result<(Packet<'b, P>, &'p [u8]), ModbusError> {
-        use crate::ModbusError::NotEnoughData;
+        use crate::ModbusError::{BadLength, NotEnoughData};
 
-        if self.contains_complete {
-            self.clear_buffer();
-            self.contains_complete = false;
+        if let Some(packet_length) = self.packet_length.take() {
+            self.discard(packet_length);
         }
 
-        let original_buffer_size = self.used();
-        let length_to_add = core::cmp::min(self.space_left(), data.len());
+        // How much of data has been added to the buffer
+        let mut consumed = 0;
 
-        self.add_data(&data[..length_to_add]);
+        // How many bytes at the end of the buffer came from data
+        let mut new_bytes = 0;
 
-        let adu_length = match P::adu_length(&self.buffer()) {
-            Ok(l) => l,
+        let (adu_length, decoded_length) = loop {
+            let length_to_add = core::cmp::min(self.space_left(), data.len() - consumed);
 
-            // Not enough data to determine ADU length
-            // For TCP MODBUS, we need 6 bytes
-            // For MODBUS RTU, it might be more
-            Err(NotEnoughData) => return Err(NotEnoughData),
+            self.add_data(&data[consumed..consumed + length_to_add]);
+            consumed += length_to_add;
+            new_bytes += length_to_add;
 
-            // Something is very wrong. Give up and purge any bad data
-            Err(e) => {
-                self.clear_buffer();
-                return Err(e);
+            if self.hunt_mode && !P::adu_plausible(self.buffer()) {
+                self.skip(1, &mut new_bytes);
+                continue;
             }
-        };
 
-        // We got something in between enough to determine the length and a full ADU
-        if self.used() < adu_length {
-            return Err(NotEnoughData);
-        }
+            let result = match self.adu_length() {
+                // We got something in between enough to determine the length and a full ADU
+                Ok(l) if self.used() < l => Err(NotEnoughData),
+                result => result,
+            };
+
+            let adu_length = match result {
+                Ok(l) => l,
+
+                // Not enough data to determine ADU length
+                // For TCP MODBUS, we need 6 bytes
+                // For MODBUS RTU, it might be more
+                Err(NotEnoughData) if self.space_left() > 0 => return Err(NotEnoughData),
+
+                // Something is very wrong. Skip it, or give up and purge any bad data
+                Err(e) => {
+                    let e = if e == NotEnoughData { BadLength } else { e };
+
+                    if self.hunt_mode && self.used() > 0 {
+                        self.skip(1, &mut new_bytes);
+                        continue;
+                    }
+
+                    self.clear_buffer();
+                    return Err(e);
+                }
+            };
+
+            // Protocols that encode the ADU on the wire need to decode it before the PDU can be
+            // borrowed
+            let decoded_length = match P::decode_adu(&mut self.raw_buffer.as_mut()[..adu_length]) {
+                Ok(l) => l,
+
+                // The ADU has been partly overwritten, so all of it has to go
+                Err(_) if self.hunt_mode => {
+                    self.skip(adu_length, &mut new_bytes);
+                    continue;
+                }
+
+                Err(e) => {
+                    self.clear_buffer();
+                    return Err(e);
+                }
+            };
+
+            let adu = &self.raw_buffer.as_ref()[..decoded_length];
+
+            let result =
+                P::adu_check(adu).and_then(|()| P::policy_check(adu, &self.policy, self.direction));
+
+            match result {
+                Ok(()) => break (adu_length, decoded_length),
+
+                // If decoding changed the ADU, the original bytes are gone, so the whole ADU has
+                // to be skipped
+                Err(_) if self.hunt_mode => {
+                    let count = if decoded_length == adu_length {
+                        1
+                    } else {
+                        adu_length
+                    };
+
+                    self.skip(count, &mut new_bytes);
+                }
+
+                Err(e) => {
+                    self.clear_buffer();
+                    return Err(e);
+                }
+            }
+        };
 
-        // This is where the remaining data starts, but it's also the amount of data added to the
-        // buffer that became part of the ADU.
-        // No underflow because if adu_length < original_buffer_size, that violates an invariant
-        // of this struct
-        let remaining_data_index = adu_length - original_buffer_size;
+        // Any data past the ADU that was just added is returned to the caller. Anything before
+        // that stays in the buffer to be processed next time.
+        let returned = core::cmp::min(self.used() - adu_length, new_bytes);
+        self.trim_to(self.used() - returned);
+        self.packet_length = Some(adu_length);
 
-        // At this point, we know we have a complete ADU
-        // Set up the tracking fields to maintain our invariants
-        self.contains_complete = true;
-        self.trim_to(adu_length);
+        let adu = &self.raw_buffer.as_ref()[..decoded_length];
 
         Ok((
             Packet {
-                header: P::adu_header(self.buffer())?,
-                pdu: P::pdu_body(self.buffer())?,
+                header: P::adu_header(adu)?,
+                pdu: P::pdu_body(adu)?,
             },
-            &data[remaining_data_index..],
+            &data[consumed - returned..],
         ))
     }
 
+    /// Process some received data, yielding every complete packet in it
+    ///
+    /// This is a wrapper around `process` that takes care of feeding the excess data back in.
+    /// Call `next_packet` on the result until it returns `None`, at which point any unfinished
+    /// data has been buffered for next time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::recv_buffer::*;
+    /// use modbus_core::protocols::*;
+    ///
+    /// let mut buf: RecvBuffer<ModbusRtu> = RecvBuffer::new();
+    ///
+    /// // Two ADUs and the start of a third
+    /// let adu = [0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+    /// let data = [&adu[..], &adu[..], &adu[..3]].concat();
+    ///
+    /// let mut packets = buf.packets(&data);
+    /// let mut count = 0;
+    ///
+    /// while let Some(packet) = packets.next_packet() {
+    ///     assert_eq!(packet.unwrap().header.address, 0x11);
+    ///     count += 1;
+    /// }
+    ///
+    /// assert_eq!(count, 2);
+    /// assert_eq!(buf.used(), 3);
+    /// ```
+    pub fn packets<'b, 'p>(&'b mut self, data: &'p [u8]) -> Packets<'b, 'p, P> {
+        Packets {
+            buffer: self,
+            data,
+            done: false,
+        }
+    }
+
+    /// Process some received data, calling `f` with every complete packet in it
+    ///
+    /// Any unfinished data is buffered for next time. If `process` returns an error other than
+    /// `NotEnoughData`, processing stops and the error is returned.
+    pub fn drain<F>(&mut self, data: &[u8], mut f: F) -> Result<(), ModbusError>
+    where
+        F: FnMut(Packet<P>),
+    {
+        let mut packets = self.packets(data);
+
+        while let Some(packet) = packets.next_packet() {
+            f(packet?);
+        }
+
+        Ok(())
+    }
+
+    fn adu_length(&self) -> Result<usize, ModbusError> {
+        match self.direction {
+            Some(direction) => P::directed_adu_length(self.buffer(), direction),
+            None => P::adu_length(self.buffer()),
+        }
+    }
+
     fn space_left(&self) -> usize {
-        debug_assert!(self.size_used < self.raw_buffer.len());
+        debug_assert!(self.size_used <= self.raw_buffer.as_ref().len());
 
-        self.raw_buffer.len() - self.size_used
+        self.raw_buffer.as_ref().len() - self.size_used
     }
 
     /// # Panics
     ///
     /// Panics if `data` is longer than the available length
     fn add_data(&mut self, data: &[u8]) {
-        self.raw_buffer[self.size_used..self.size_used + data.len()].copy_from_slice(data);
+        self.raw_buffer.as_mut()[self.size_used..self.size_used + data.len()].copy_from_slice(data);
         self.size_used += data.len();
     }
 
@@ -139,13 +370,34 @@ impl<P: ModbusProtocol> RecvBuffer<P> {
 
     fn trim_to(&mut self, length: usize) {
         debug_assert!(length <= self.size_used);
-        debug_assert!(self.size_used <= self.raw_buffer.len());
+        debug_assert!(self.size_used <= self.raw_buffer.as_ref().len());
 
         self.size_used = length;
     }
 
+    /// Remove `count` bytes from the start of the buffer
+    fn discard(&mut self, count: usize) {
+        debug_assert!(count <= self.size_used);
+
+        self.raw_buffer
+            .as_mut()
+            .copy_within(count..self.size_used, 0);
+        self.size_used -= count;
+    }
+
+    /// Discard `count` bytes of invalid data while hunting
+    ///
+    /// `new_bytes` is the number of bytes at the end of the buffer that came from the data being
+    /// processed, which can shrink if all of the older data has been skipped.
+    fn skip(&mut self, count: usize, new_bytes: &mut usize) {
+        self.discard(count);
+        self.skipped = self.skipped.saturating_add(count);
+
+        *new_bytes = core::cmp::min(*new_bytes, self.size_used);
+    }
+
     fn buffer(&self) -> &[u8] {
-        &self.raw_buffer[..self.size_used]
+        &self.raw_buffer.as_ref()[..self.size_used]
     }
 
     /// Determine how much of the buffer is currently in use
@@ -171,7 +423,54 @@ impl<P: ModbusProtocol> RecvBuffer<P> {
     }
 }
 
-#[derive(PartialEq)]
+impl<P: ModbusProtocol> Default for RecvBuffer<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The packets in a chunk of received data
+///
+/// Created by `RecvBuffer::packets`. This can't implement `Iterator`, because each packet
+/// borrows from the buffer and is only valid until the next one is requested.
+pub struct Packets<'b, 'p, P: ModbusProtocol> {
+    buffer: &'b mut RecvBuffer<P>,
+    data: &'p [u8],
+    done: bool,
+}
+
+impl<'b, 'p, P: ModbusProtocol> Packets<'b, 'p, P> {
+    /// Get the next complete packet
+    ///
+    /// Returns `None` once all of the data has been processed. Errors other than
+    /// `NotEnoughData` are returned once, and then no more packets are produced from this data.
+    pub fn next_packet(&mut self) -> Option<Result<Packet<'_, P>, ModbusError>> {
+        if self.done {
+            return None;
+        }
+
+        // Keep going even once all the data has been used, because hunt mode can leave complete
+        // ADUs in the buffer
+        match self.buffer.process(self.data) {
+            Ok((packet, rest)) => {
+                self.data = rest;
+
+                Some(Ok(packet))
+            }
+            Err(ModbusError::NotEnoughData) => {
+                self.done = true;
+
+                None
+            }
+            Err(e) => {
+                self.done = true;
+
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 /// A representation of a single MODBUS ADU
 ///
 /// Consists of a protocol-dependent header, as well as a protocol data unit that is the same
@@ -181,6 +480,16 @@ pub struct Packet<'p, P: ModbusProtocol> {
     pub header: P::Header,
 }
 
+// Derived PartialEq would require the protocol type itself to be PartialEq
+impl<'p, P: ModbusProtocol> PartialEq for Packet<'p, P>
+where
+    P::Header: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.pdu == other.pdu && self.header == other.header
+    }
+}
+
 impl<'p, P: ModbusProtocol> core::fmt::Debug for Packet<'p, P> {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("Packet")
@@ -195,7 +504,7 @@ mod test {
     use super::*;
     use crate::protocols::*;
     use crate::test_data::*;
-    use crate::ModbusError::NotEnoughData;
+    use crate::ModbusError::{BadFuncCode, BadProtocolId, ByteCountMismatch, NotEnoughData};
 
     const FOUR_ADUS_LEN: usize = 2 * (ADU1_TCP.len() + ADU2_TCP.len());
 
@@ -207,7 +516,7 @@ mod test {
         for slice in [ADU1_TCP, ADU2_TCP, ADU2_TCP, ADU1_TCP].iter() {
             let next = current + slice.len();
 
-            &mut buffer[current..next].copy_from_slice(slice);
+            buffer[current..next].copy_from_slice(slice);
 
             current = next;
         }
@@ -321,6 +630,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::if_same_then_else)]
     fn tcp_four_adus_byte_by_byte() {
         let mut input: [u8; FOUR_ADUS_LEN] = [0; FOUR_ADUS_LEN];
         four_tcp_adus(&mut input);
@@ -362,4 +672,324 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn rtu_query_then_response() {
+        let mut input = [0; ADU3_ADU_LENGTH + ADU4_ADU_LENGTH];
+        input[..ADU3_ADU_LENGTH].copy_from_slice(ADU3_RTU);
+        input[ADU3_ADU_LENGTH..].copy_from_slice(ADU4_RTU);
+
+        let mut buf = RecvBuffer::<ModbusRtu>::new();
+
+        let (packet, slice) = buf.process(&input).unwrap();
+        assert_eq!(slice, ADU4_RTU);
+        assert_eq!(packet.pdu, ADU3_PDU());
+        assert_eq!(packet.header, ADU3_HEADER);
+
+        for (index, byte) in slice.chunks(1).enumerate() {
+            let result = buf.process(byte);
+
+            if index == ADU4_ADU_LENGTH - 1 {
+                let (packet, slice) = result.unwrap();
+
+                assert_eq!(slice, &[]);
+                assert_eq!(packet.pdu, ADU4_PDU());
+                assert_eq!(packet.header, ADU4_HEADER);
+            } else {
+                assert_eq!(result.unwrap_err(), NotEnoughData);
+            }
+        }
+    }
+
+    #[test]
+    fn rtu_directed() {
+        let mut buf = RecvBuffer::<ModbusRtu>::with_direction(ADU4_DIRECTION);
+
+        for (index, byte) in ADU4_RTU.chunks(1).enumerate() {
+            let result = buf.process(byte);
+
+            if index == ADU4_ADU_LENGTH - 1 {
+                let (packet, slice) = result.unwrap();
+
+                assert_eq!(slice, &[]);
+                assert_eq!(packet.pdu, ADU4_PDU());
+                assert_eq!(packet.header, ADU4_HEADER);
+            } else {
+                assert_eq!(result.unwrap_err(), NotEnoughData);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_byte_by_byte() {
+        let mut buf = RecvBuffer::<ModbusAscii>::new();
+
+        for _ in 0..2 {
+            for (index, byte) in ADU5_ASCII.chunks(1).enumerate() {
+                let result = buf.process(byte);
+
+                if index == ADU5_ADU_LENGTH - 1 {
+                    let (packet, slice) = result.unwrap();
+
+                    assert_eq!(slice, &[]);
+                    assert_eq!(packet.pdu, ADU5_PDU());
+                    assert_eq!(packet.header, ADU5_HEADER);
+                } else {
+                    assert_eq!(result.unwrap_err(), NotEnoughData);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_sized_per_protocol() {
+        fn buffer_len<P: ModbusProtocol>() -> usize {
+            RecvBuffer::<P>::new().raw_buffer.as_ref().len()
+        }
+
+        assert_eq!(buffer_len::<TcpModbus>(), TcpModbus::ADU_MAX_LENGTH);
+        assert_eq!(buffer_len::<ModbusRtu>(), ModbusRtu::ADU_MAX_LENGTH);
+        assert_eq!(buffer_len::<ModbusAscii>(), ModbusAscii::ADU_MAX_LENGTH);
+    }
+
+    #[test]
+    fn largest_adus_fit() {
+        fn check<P: ModbusProtocol>(header: P::Header) {
+            // A Read Holding Registers response with the largest possible byte count
+            let mut pdu = [0; crate::pdu::PDU_MAX_LENGTH];
+            pdu[0] = 0x03;
+            pdu[1] = 251;
+
+            let mut adu = [0; 600];
+            let length = P::write_adu(&header, &pdu, &mut adu).unwrap();
+            assert_eq!(length, P::ADU_MAX_LENGTH);
+
+            let mut buf = RecvBuffer::<P>::with_direction(Direction::Response);
+            let (packet, slice) = buf.process(&adu[..length]).unwrap();
+
+            assert_eq!(packet.pdu, &pdu[..]);
+            assert_eq!(slice, &[]);
+        }
+
+        check::<TcpModbus>(ADU2_HEADER);
+        check::<ModbusRtu>(ADU4_HEADER);
+        check::<ModbusAscii>(ADU5_HEADER);
+    }
+
+    /// Feeds `chunks` through `buf` one at a time, and counts the packets that come out
+    fn count_packets<P: ModbusProtocol>(buf: &mut RecvBuffer<P>, chunks: &[&[u8]]) -> usize {
+        let mut count = 0;
+
+        // The empty chunk at the end collects any ADUs left over in the buffer
+        for &chunk in chunks.iter().chain(&[&[][..]]) {
+            let mut slice = chunk;
+
+            loop {
+                match buf.process(slice) {
+                    Ok((_, rest)) => {
+                        count += 1;
+                        slice = rest;
+                    }
+                    Err(NotEnoughData) => break,
+                    Err(e) => panic!("unexpected error {:?}", e),
+                }
+            }
+        }
+
+        count
+    }
+
+    #[test]
+    fn without_hunt_mode_garbage_clears_buffer() {
+        let mut buf = RecvBuffer::<ModbusRtu>::new();
+
+        assert_eq!(buf.process(&[0x11, 0x30, 0x00]).unwrap_err(), BadFuncCode);
+        assert_eq!(buf.used(), 0);
+        assert_eq!(buf.skipped(), 0);
+    }
+
+    #[test]
+    fn rtu_hunt_garbage_then_adus() {
+        let mut input = [0; 3 + ADU3_ADU_LENGTH + ADU4_ADU_LENGTH];
+        input[..3].copy_from_slice(&[0xFF, 0x11, 0x30]);
+        input[3..3 + ADU3_ADU_LENGTH].copy_from_slice(ADU3_RTU);
+        input[3 + ADU3_ADU_LENGTH..].copy_from_slice(ADU4_RTU);
+
+        let mut buf = RecvBuffer::<ModbusRtu>::new();
+        buf.set_hunt_mode(true);
+        assert!(buf.hunt_mode());
+
+        let (packet, slice) = buf.process(&input).unwrap();
+        assert_eq!(packet.header, ADU3_HEADER);
+        assert_eq!(packet.pdu, ADU3_PDU());
+        assert_eq!(slice, ADU4_RTU);
+        assert_eq!(buf.skipped(), 3);
+
+        let (packet, slice) = buf.process(slice).unwrap();
+        assert_eq!(packet.header, ADU4_HEADER);
+        assert_eq!(slice, &[]);
+        assert_eq!(buf.skipped(), 3);
+    }
+
+    #[test]
+    fn rtu_hunt_bad_crc() {
+        let mut bad = [0; ADU3_ADU_LENGTH];
+        bad.copy_from_slice(ADU3_RTU);
+        bad[ADU3_ADU_LENGTH - 1] ^= 0x01;
+
+        let mut buf = RecvBuffer::<ModbusRtu>::with_direction(Direction::Query);
+        buf.set_hunt_mode(true);
+
+        assert_eq!(count_packets(&mut buf, &[&bad, ADU3_RTU, ADU3_RTU]), 2);
+        assert_eq!(buf.skipped(), ADU3_ADU_LENGTH);
+    }
+
+    #[test]
+    fn rtu_hunt_finds_adus_in_buffered_data() {
+        // This claims a 250-byte payload, so it swallows the ADUs after it until the CRC can be
+        // checked
+        let mut chunks = [ADU4_RTU; 24];
+        chunks[0] = &[0x01, 0x03, 0xFA];
+
+        let mut buf = RecvBuffer::<ModbusRtu>::with_direction(Direction::Response);
+        buf.set_hunt_mode(true);
+
+        assert_eq!(count_packets(&mut buf, &chunks), 23);
+        assert_eq!(buf.skipped(), 3);
+    }
+
+    #[test]
+    fn tcp_hunt_bad_protocol_id() {
+        let mut input = [0; 5 + ADU1_TCP.len()];
+        input[..5].copy_from_slice(&[0x00, 0x01, 0x12, 0x34, 0x00]);
+        input[5..].copy_from_slice(ADU1_TCP);
+
+        let mut buf = RecvBuffer::<TcpModbus>::new();
+        buf.set_hunt_mode(true);
+
+        let (packet, slice) = buf.process(&input).unwrap();
+        assert_eq!(packet.header, ADU1_HEADER);
+        assert_eq!(slice, &[]);
+        assert_eq!(buf.skipped(), 5);
+    }
+
+    #[test]
+    fn tcp_strict_policy() {
+        // Protocol ID 1, and a Read Holding Registers query with an extra byte
+        let bad_protocol: &[u8] = &[0, 1, 0, 1, 0, 6, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03];
+        let bad_length: &[u8] = &[0, 1, 0, 0, 0, 7, 0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x00];
+
+        let mut buf = RecvBuffer::<TcpModbus>::with_direction(Direction::Query);
+        assert_eq!(buf.policy(), MbapPolicy::LENIENT);
+        assert!(buf.process(bad_protocol).is_ok());
+        assert!(buf.process(bad_length).is_ok());
+
+        buf.set_policy(MbapPolicy::STRICT);
+        assert_eq!(buf.process(bad_protocol).unwrap_err(), BadProtocolId(1));
+        assert_eq!(buf.used(), 0);
+        assert_eq!(
+            buf.process(bad_length).unwrap_err(),
+            ByteCountMismatch {
+                expected: 5,
+                actual: 6
+            }
+        );
+
+        let mut buf = RecvBuffer::<TcpModbus>::new();
+        buf.set_policy(MbapPolicy::STRICT);
+        assert_eq!(buf.process(ADU1_TCP).unwrap().0.header, ADU1_HEADER);
+
+        // In hunt mode, the rejected ADU is skipped
+        buf.set_hunt_mode(true);
+        assert_eq!(count_packets(&mut buf, &[bad_length, ADU1_TCP]), 1);
+    }
+
+    #[test]
+    fn ascii_hunt() {
+        let mut bad = [0; ADU5_ADU_LENGTH];
+        bad.copy_from_slice(ADU5_ASCII);
+        bad[3] = b'X';
+
+        let mut buf = RecvBuffer::<ModbusAscii>::new();
+        buf.set_hunt_mode(true);
+
+        assert_eq!(
+            count_packets(&mut buf, &[b"\r\n0", &bad, ADU5_ASCII, ADU5_ASCII]),
+            2
+        );
+        assert_eq!(buf.skipped(), 3 + ADU5_ADU_LENGTH);
+    }
+
+    #[test]
+    fn tcp_four_adus_packets() {
+        let mut input: [u8; FOUR_ADUS_LEN] = [0; FOUR_ADUS_LEN];
+        four_tcp_adus(&mut input);
+
+        let expected = [ADU1_PDU(), ADU2_PDU(), ADU2_PDU(), ADU1_PDU()];
+
+        for &chunk_size in &[1, 10, FOUR_ADUS_LEN] {
+            let mut buf = RecvBuffer::<TcpModbus>::new();
+            let mut found = 0;
+
+            for chunk in input.chunks(chunk_size) {
+                let mut packets = buf.packets(chunk);
+
+                while let Some(packet) = packets.next_packet() {
+                    assert_eq!(packet.unwrap().pdu, expected[found]);
+                    found += 1;
+                }
+            }
+
+            assert_eq!(found, expected.len());
+            assert_eq!(buf.process(&[]).unwrap_err(), NotEnoughData);
+        }
+    }
+
+    #[test]
+    fn tcp_four_adus_drain() {
+        let mut input: [u8; FOUR_ADUS_LEN] = [0; FOUR_ADUS_LEN];
+        four_tcp_adus(&mut input);
+
+        let mut buf = RecvBuffer::<TcpModbus>::new();
+        let mut headers = [None, None, None, None];
+        let mut found = 0;
+
+        let (first, second) = input.split_at(ADU1_TCP.len() + 1);
+
+        for chunk in &[first, second] {
+            buf.drain(chunk, |packet| {
+                headers[found] = Some(packet.header);
+                found += 1;
+            })
+            .unwrap();
+        }
+
+        assert_eq!(
+            headers,
+            [
+                Some(ADU1_HEADER),
+                Some(ADU2_HEADER),
+                Some(ADU2_HEADER),
+                Some(ADU1_HEADER)
+            ]
+        );
+    }
+
+    #[test]
+    fn packets_stop_at_error() {
+        let mut input = [0; ADU3_ADU_LENGTH + 3];
+        input[..ADU3_ADU_LENGTH].copy_from_slice(ADU3_RTU);
+        input[ADU3_ADU_LENGTH..].copy_from_slice(&[0x11, 0x30, 0x00]);
+
+        let mut buf = RecvBuffer::<ModbusRtu>::new();
+        let mut packets = buf.packets(&input);
+
+        assert_eq!(packets.next_packet().unwrap().unwrap().pdu, ADU3_PDU());
+        assert_eq!(packets.next_packet().unwrap().unwrap_err(), BadFuncCode);
+        assert!(packets.next_packet().is_none());
+
+        let mut count = 0;
+        assert_eq!(buf.drain(&input, |_| count += 1), Err(BadFuncCode));
+        assert_eq!(count, 1);
+    }
 }
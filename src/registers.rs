@@ -0,0 +1,513 @@
+//! Tools for values that are stored across several registers
+//!
+//! MODBUS registers are only 16 bits, so larger values are split across consecutive registers.
+//! The specification doesn't say how, and devices disagree: the registers can be in either
+//! order, and so can the bytes within each register. `RegisterOrder` describes the layout, with
+//! the common names for the four combinations (ABCD, CDAB, BADC, and DCBA).
+//!
+//! Values can be read from and written to register values (`decode` and `encode`), or the raw
+//! register bytes of a PDU, which are big-endian (`decode_bytes` and `encode_bytes`).
+//!
+//! # Examples
+//!
+//! ```
+//! use modbus_core::registers::*;
+//!
+//! // 123.456 as an f32 is 0x42F6E979, stored with the low word first
+//! let registers = [0xE979, 0x42F6];
+//!
+//! let value: f32 = decode(&registers, RegisterOrder::CDAB).unwrap();
+//! assert_eq!(value, 123.456);
+//!
+//! let mut out = [0; 2];
+//! encode(value, RegisterOrder::CDAB, &mut out);
+//! assert_eq!(out, registers);
+//! ```
+
+/// The largest number of registers used by a value
+const MAX_REGISTERS: usize = 4;
+
+// The largest values that fit in packed BCD
+const BCD_DIGITS: u32 = 8;
+const BCD_MAX: u32 = 99_999_999;
+
+// 10^18 is the largest power of 10 that fits in an i64
+const MAX_DECIMALS: u8 = 18;
+
+/// The order of the registers that make up a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WordOrder {
+    /// The most significant register comes first
+    BigEndian,
+
+    /// The least significant register comes first
+    LittleEndian,
+}
+
+/// The order of the bytes within each register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// The most significant byte comes first, as the MODBUS specification says
+    BigEndian,
+
+    /// The least significant byte comes first
+    LittleEndian,
+}
+
+/// How a value is laid out across registers
+///
+/// The names of the constants spell out where the bytes of a 32-bit value `ABCD` (most
+/// significant first) end up. For 64-bit values, the same word and byte orders apply to all
+/// four registers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegisterOrder {
+    pub word_order: WordOrder,
+    pub byte_order: ByteOrder,
+}
+
+impl RegisterOrder {
+    /// Big-endian registers in big-endian order
+    pub const ABCD: Self = RegisterOrder {
+        word_order: WordOrder::BigEndian,
+        byte_order: ByteOrder::BigEndian,
+    };
+
+    /// Big-endian registers in little-endian order, sometimes called "word swapped"
+    pub const CDAB: Self = RegisterOrder {
+        word_order: WordOrder::LittleEndian,
+        byte_order: ByteOrder::BigEndian,
+    };
+
+    /// Little-endian registers in big-endian order, sometimes called "byte swapped"
+    pub const BADC: Self = RegisterOrder {
+        word_order: WordOrder::BigEndian,
+        byte_order: ByteOrder::LittleEndian,
+    };
+
+    /// Little-endian registers in little-endian order
+    pub const DCBA: Self = RegisterOrder {
+        word_order: WordOrder::LittleEndian,
+        byte_order: ByteOrder::LittleEndian,
+    };
+}
+
+impl Default for RegisterOrder {
+    fn default() -> Self {
+        Self::ABCD
+    }
+}
+
+/// A value that can be stored across registers
+///
+/// This is implemented for the 32-bit and 64-bit integer and floating point types.
+pub trait RegisterValue: Copy {
+    /// The number of registers a value takes up
+    const REGISTERS: usize;
+
+    /// Write the value's bytes to `bytes`, most significant first
+    ///
+    /// `bytes` is exactly `2 * REGISTERS` long.
+    fn write_be_bytes(self, bytes: &mut [u8]);
+
+    /// Read a value from `bytes`, most significant first
+    ///
+    /// `bytes` is exactly `2 * REGISTERS` long.
+    fn read_be_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! register_value {
+    ($($t:ty),*) => {
+        $(
+            impl RegisterValue for $t {
+                const REGISTERS: usize = core::mem::size_of::<$t>() / 2;
+
+                fn write_be_bytes(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_be_bytes());
+                }
+
+                fn read_be_bytes(bytes: &[u8]) -> Self {
+                    let mut array = [0; core::mem::size_of::<$t>()];
+                    array.copy_from_slice(bytes);
+
+                    <$t>::from_be_bytes(array)
+                }
+            }
+        )*
+    };
+}
+
+register_value!(u32, i32, f32, u64, i64, f64);
+
+/// Read a value from the start of `registers`
+///
+/// Returns `None` if there aren't enough registers.
+pub fn decode<T: RegisterValue>(registers: &[u16], order: RegisterOrder) -> Option<T> {
+    let registers = registers.get(..T::REGISTERS)?;
+    let mut bytes = [0; 2 * MAX_REGISTERS];
+
+    for (position, &register) in registers.iter().enumerate() {
+        let index = word_index(position, T::REGISTERS, order.word_order);
+        bytes[2 * index..2 * index + 2]
+            .copy_from_slice(&register_bytes(register, order.byte_order));
+    }
+
+    Some(T::read_be_bytes(&bytes[..2 * T::REGISTERS]))
+}
+
+/// Write a value to the start of `registers`
+///
+/// Any registers after the value are left unchanged.
+///
+/// # Panics
+///
+/// Panics if there are fewer than `T::REGISTERS` registers.
+pub fn encode<T: RegisterValue>(value: T, order: RegisterOrder, registers: &mut [u16]) {
+    let registers = &mut registers[..T::REGISTERS];
+    let mut bytes = [0; 2 * MAX_REGISTERS];
+    value.write_be_bytes(&mut bytes[..2 * T::REGISTERS]);
+
+    for (position, register) in registers.iter_mut().enumerate() {
+        let index = word_index(position, T::REGISTERS, order.word_order);
+        *register = register_from_bytes([bytes[2 * index], bytes[2 * index + 1]], order.byte_order);
+    }
+}
+
+/// Read a value from the start of some register bytes, like those in a PDU
+///
+/// Each register is 2 big-endian bytes, as they are on the wire. Returns `None` if there aren't
+/// enough bytes.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::pdu::Response;
+/// use modbus_core::registers::*;
+///
+/// // A Read Holding Registers response with 2 registers
+/// let pdu = [0x03, 0x04, 0xFF, 0xFF, 0xFF, 0x85];
+/// let registers = Response::parse(&pdu).unwrap().registers().unwrap();
+///
+/// let value: i32 = decode_bytes(registers.bytes(), RegisterOrder::ABCD).unwrap();
+/// assert_eq!(value, -123);
+/// ```
+pub fn decode_bytes<T: RegisterValue>(bytes: &[u8], order: RegisterOrder) -> Option<T> {
+    let bytes = bytes.get(..2 * T::REGISTERS)?;
+    let mut registers = [0; MAX_REGISTERS];
+
+    for (register, pair) in registers.iter_mut().zip(bytes.chunks_exact(2)) {
+        *register = u16::from_be_bytes([pair[0], pair[1]]);
+    }
+
+    decode(&registers[..T::REGISTERS], order)
+}
+
+/// Write a value to the start of some register bytes, like those in a PDU
+///
+/// Each register is written as 2 big-endian bytes. Any bytes after the value are left unchanged.
+///
+/// # Panics
+///
+/// Panics if there are fewer than `2 * T::REGISTERS` bytes.
+pub fn encode_bytes<T: RegisterValue>(value: T, order: RegisterOrder, bytes: &mut [u8]) {
+    let bytes = &mut bytes[..2 * T::REGISTERS];
+    let mut registers = [0; MAX_REGISTERS];
+    encode(value, order, &mut registers);
+
+    for (pair, register) in bytes.chunks_exact_mut(2).zip(registers.iter()) {
+        pair.copy_from_slice(&register.to_be_bytes());
+    }
+}
+
+/// Decode a packed BCD value, with one decimal digit in each 4 bits
+///
+/// A single register holds up to 4 digits, and two registers (decoded as a `u32`) hold up to 8.
+/// Returns `None` if any of the digits isn't 0 to 9.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::registers::*;
+///
+/// assert_eq!(from_bcd(0x1234), Some(1234));
+/// assert_eq!(from_bcd(0x12A4), None);
+///
+/// assert_eq!(to_bcd(1234), Some(0x1234));
+/// ```
+pub fn from_bcd(bcd: u32) -> Option<u32> {
+    let mut value = 0;
+
+    for digit in (0..BCD_DIGITS).rev() {
+        let nibble = (bcd >> (4 * digit)) & 0xF;
+
+        if nibble > 9 {
+            return None;
+        }
+
+        value = value * 10 + nibble;
+    }
+
+    Some(value)
+}
+
+/// Encode a value as packed BCD, with one decimal digit in each 4 bits
+///
+/// Returns `None` if the value has more than 8 digits.
+pub fn to_bcd(value: u32) -> Option<u32> {
+    if value > BCD_MAX {
+        return None;
+    }
+
+    let mut bcd = 0;
+    let mut rest = value;
+
+    for digit in 0..BCD_DIGITS {
+        bcd |= (rest % 10) << (4 * digit);
+        rest /= 10;
+    }
+
+    Some(bcd)
+}
+
+/// Convert a fixed-point value with `decimals` decimal places to a float
+///
+/// For example, a temperature of 21.5 degrees stored in tenths is `215` with 1 decimal place.
+///
+/// # Panics
+///
+/// Panics if `decimals` is more than 18.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::registers::*;
+///
+/// assert_eq!(from_fixed(215, 1), 21.5);
+/// assert_eq!(to_fixed(21.5, 1), Some(215));
+///
+/// // Rounded to the nearest step
+/// assert_eq!(to_fixed(-0.126, 2), Some(-13));
+/// ```
+pub fn from_fixed(raw: i64, decimals: u8) -> f64 {
+    raw as f64 / scale(decimals) as f64
+}
+
+/// Convert a float to a fixed-point value with `decimals` decimal places
+///
+/// The value is rounded to the nearest step, with halfway values rounded away from zero.
+/// Returns `None` if the result doesn't fit in an `i64`, or `value` is NaN.
+///
+/// # Panics
+///
+/// Panics if `decimals` is more than 18.
+pub fn to_fixed(value: f64, decimals: u8) -> Option<i64> {
+    // Floats this big have no fractional part
+    const INTEGRAL: f64 = (1u64 << 52) as f64;
+
+    let scaled = value * scale(decimals) as f64;
+
+    // Also false for NaN
+    if !(scaled >= i64::MIN as f64 && scaled < i64::MAX as f64) {
+        return None;
+    }
+
+    if scaled >= INTEGRAL || scaled <= -INTEGRAL {
+        return Some(scaled as i64);
+    }
+
+    // There's no f64::round without std. Both of these are exact below 2^52.
+    let truncated = scaled as i64;
+    let fraction = scaled - truncated as f64;
+
+    Some(if fraction >= 0.5 {
+        truncated + 1
+    } else if fraction <= -0.5 {
+        truncated - 1
+    } else {
+        truncated
+    })
+}
+
+/// The position of the register at `position` in a value's big-endian bytes
+fn word_index(position: usize, registers: usize, word_order: WordOrder) -> usize {
+    match word_order {
+        WordOrder::BigEndian => position,
+        WordOrder::LittleEndian => registers - 1 - position,
+    }
+}
+
+/// A register's bytes, in the order they appear in the value's big-endian bytes
+fn register_bytes(register: u16, byte_order: ByteOrder) -> [u8; 2] {
+    match byte_order {
+        ByteOrder::BigEndian => register.to_be_bytes(),
+        ByteOrder::LittleEndian => register.to_le_bytes(),
+    }
+}
+
+/// The register holding `bytes` of a value's big-endian bytes
+fn register_from_bytes(bytes: [u8; 2], byte_order: ByteOrder) -> u16 {
+    match byte_order {
+        ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+    }
+}
+
+/// 10 to the power of `decimals`
+///
+/// # Panics
+///
+/// Panics if `decimals` is more than 18.
+fn scale(decimals: u8) -> i64 {
+    assert!(decimals <= MAX_DECIMALS, "too many decimal places");
+
+    10_i64.pow(u32::from(decimals))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ORDERS: [RegisterOrder; 4] = [
+        RegisterOrder::ABCD,
+        RegisterOrder::CDAB,
+        RegisterOrder::BADC,
+        RegisterOrder::DCBA,
+    ];
+
+    #[test]
+    fn orders_32() {
+        let expected: [[u16; 2]; 4] = [
+            [0x1122, 0x3344],
+            [0x3344, 0x1122],
+            [0x2211, 0x4433],
+            [0x4433, 0x2211],
+        ];
+
+        for (&order, registers) in ORDERS.iter().zip(expected.iter()) {
+            let mut out = [0; 3];
+            encode(0x1122_3344_u32, order, &mut out);
+            assert_eq!(&out[..2], registers, "{:?}", order);
+            assert_eq!(out[2], 0);
+
+            assert_eq!(decode::<u32>(registers, order), Some(0x1122_3344));
+        }
+    }
+
+    #[test]
+    fn orders_64() {
+        let expected: [[u16; 4]; 4] = [
+            [0x0102, 0x0304, 0x0506, 0x0708],
+            [0x0708, 0x0506, 0x0304, 0x0102],
+            [0x0201, 0x0403, 0x0605, 0x0807],
+            [0x0807, 0x0605, 0x0403, 0x0201],
+        ];
+
+        for (&order, registers) in ORDERS.iter().zip(expected.iter()) {
+            let mut out = [0; 4];
+            encode(0x0102_0304_0506_0708_u64, order, &mut out);
+            assert_eq!(&out, registers, "{:?}", order);
+
+            assert_eq!(decode::<u64>(registers, order), Some(0x0102_0304_0506_0708));
+        }
+    }
+
+    #[test]
+    fn types() {
+        for &order in &ORDERS {
+            let mut out = [0; 4];
+
+            encode(-2_i32, order, &mut out);
+            assert_eq!(decode::<i32>(&out, order), Some(-2));
+
+            encode(-1.5_f32, order, &mut out);
+            assert_eq!(decode::<f32>(&out, order), Some(-1.5));
+
+            encode(i64::MIN + 7, order, &mut out);
+            assert_eq!(decode::<i64>(&out, order), Some(i64::MIN + 7));
+
+            encode(core::f64::consts::PI, order, &mut out);
+            assert_eq!(decode::<f64>(&out, order), Some(core::f64::consts::PI));
+        }
+
+        // -1.5 as an f32 is 0xBFC00000
+        assert_eq!(
+            decode::<f32>(&[0xBFC0, 0x0000], RegisterOrder::ABCD),
+            Some(-1.5)
+        );
+        assert_eq!(decode::<f32>(&[0x0000], RegisterOrder::ABCD), None);
+        assert_eq!(decode::<f64>(&[0, 0, 0], RegisterOrder::ABCD), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn encode_too_short() {
+        encode(1_u64, RegisterOrder::ABCD, &mut [0; 3]);
+    }
+
+    #[test]
+    fn bytes() {
+        let mut bytes = [0; 5];
+        encode_bytes(0x1122_3344_u32, RegisterOrder::CDAB, &mut bytes);
+        assert_eq!(bytes, [0x33, 0x44, 0x11, 0x22, 0x00]);
+
+        assert_eq!(
+            decode_bytes::<u32>(&bytes, RegisterOrder::CDAB),
+            Some(0x1122_3344)
+        );
+        assert_eq!(
+            decode_bytes::<u32>(&bytes, RegisterOrder::BADC),
+            Some(0x4433_2211)
+        );
+        assert_eq!(decode_bytes::<u32>(&bytes[..3], RegisterOrder::ABCD), None);
+
+        let mut bytes = [0; 8];
+        encode_bytes(-1.0_f64, RegisterOrder::DCBA, &mut bytes);
+        assert_eq!(bytes, [0, 0, 0, 0, 0, 0, 0xF0, 0xBF]);
+        assert_eq!(decode_bytes::<f64>(&bytes, RegisterOrder::DCBA), Some(-1.0));
+    }
+
+    #[test]
+    fn bcd() {
+        assert_eq!(from_bcd(0), Some(0));
+        assert_eq!(from_bcd(0x9999_9999), Some(99_999_999));
+        assert_eq!(from_bcd(0x0000_0105), Some(105));
+        assert_eq!(from_bcd(0x1000_000A), None);
+        assert_eq!(from_bcd(0xF000_0000), None);
+
+        assert_eq!(to_bcd(0), Some(0));
+        assert_eq!(to_bcd(105), Some(0x105));
+        assert_eq!(to_bcd(99_999_999), Some(0x9999_9999));
+        assert_eq!(to_bcd(100_000_000), None);
+
+        // Two registers of BCD
+        let value = decode::<u32>(&[0x0012, 0x3456], RegisterOrder::ABCD).and_then(from_bcd);
+        assert_eq!(value, Some(123_456));
+    }
+
+    #[test]
+    fn fixed_point() {
+        assert_eq!(from_fixed(-1234, 2), -12.34);
+        assert_eq!(from_fixed(1234, 0), 1234.0);
+        assert_eq!(from_fixed(1, 18), 1e-18);
+
+        assert_eq!(to_fixed(-12.34, 2), Some(-1234));
+        assert_eq!(to_fixed(0.05, 1), Some(1));
+        assert_eq!(to_fixed(-0.05, 1), Some(-1));
+        assert_eq!(to_fixed(0.04, 1), Some(0));
+        assert_eq!(to_fixed(0.49999999999999994, 0), Some(0));
+        assert_eq!(to_fixed(-0.49999999999999994, 0), Some(0));
+        assert_eq!(to_fixed(2.5, 0), Some(3));
+        assert_eq!(to_fixed(-2.5, 0), Some(-3));
+        assert_eq!(to_fixed(4503599627370497.0, 0), Some(4503599627370497));
+        assert_eq!(to_fixed(-4503599627370497.0, 0), Some(-4503599627370497));
+        assert_eq!(to_fixed(-9223372036854775808.0, 0), Some(i64::MIN));
+        assert_eq!(to_fixed(1e18, 1), None);
+        assert_eq!(to_fixed(f64::NAN, 1), None);
+        assert_eq!(to_fixed(f64::INFINITY, 0), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_decimals() {
+        from_fixed(1, 19);
+    }
+}
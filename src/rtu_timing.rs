@@ -0,0 +1,426 @@
+//! Tools for delimiting MODBUS RTU frames by timing
+//!
+//! MODBUS RTU frames are separated by at least 3.5 character times of silence on the line, and
+//! the characters within a frame should be no more than 1.5 character times apart. See the
+//! `RtuReceiver` struct for details.
+
+use crate::protocols::{AduBuffer, ModbusProtocol, ModbusRtu};
+use crate::recv_buffer::Packet;
+use crate::ModbusError;
+
+// Each character is 11 bits: start, 8 data bits, parity (or a second stop bit), and stop
+// These are all in half-bits so that 1.5 and 3.5 characters are whole numbers
+const CHARACTER_HALF_BITS: u64 = 2 * 11;
+const T1_5_HALF_BITS: u64 = 3 * 11;
+const T3_5_HALF_BITS: u64 = 7 * 11;
+
+// Above this baud rate, the timeouts are fixed rather than based on the character time
+const FIXED_TIMEOUT_BAUD_RATE: u32 = 19200;
+const FIXED_T1_5_MICROS: u64 = 750;
+const FIXED_T3_5_MICROS: u64 = 1750;
+
+const MICROS_PER_SECOND: u64 = 1_000_000;
+
+/// The character time, t1.5, and t3.5 for a serial line, in ticks
+///
+/// Ticks can be any unit of time, as long as they match the timestamps given to `RtuReceiver`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RtuTiming {
+    character: u64,
+    t1_5: u64,
+    t3_5: u64,
+}
+
+impl RtuTiming {
+    /// Calculate the timing for the given baud rate, with ticks counting at `ticks_per_second`
+    ///
+    /// Above 19200 baud, t1.5 and t3.5 are fixed at 750µs and 1.75ms, as recommended by the
+    /// MODBUS serial line specification. Times are rounded up to the next tick.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baud_rate` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use modbus_core::rtu_timing::RtuTiming;
+    ///
+    /// // Microsecond ticks
+    /// let timing = RtuTiming::new(9600, 1_000_000);
+    ///
+    /// assert_eq!(timing.character(), 1146);
+    /// assert_eq!(timing.t1_5(), 1719);
+    /// assert_eq!(timing.t3_5(), 4011);
+    /// ```
+    pub fn new(baud_rate: u32, ticks_per_second: u64) -> Self {
+        assert!(baud_rate > 0, "baud rate must not be 0");
+
+        let half_bits = |count: u64| (count * ticks_per_second).div_ceil(2 * u64::from(baud_rate));
+        let micros = |count: u64| (count * ticks_per_second).div_ceil(MICROS_PER_SECOND);
+
+        if baud_rate > FIXED_TIMEOUT_BAUD_RATE {
+            RtuTiming {
+                character: half_bits(CHARACTER_HALF_BITS),
+                t1_5: micros(FIXED_T1_5_MICROS),
+                t3_5: micros(FIXED_T3_5_MICROS),
+            }
+        } else {
+            RtuTiming {
+                character: half_bits(CHARACTER_HALF_BITS),
+                t1_5: half_bits(T1_5_HALF_BITS),
+                t3_5: half_bits(T3_5_HALF_BITS),
+            }
+        }
+    }
+
+    /// The time taken to send one character
+    pub fn character(&self) -> u64 {
+        self.character
+    }
+
+    /// The longest silence allowed between characters in a frame
+    pub fn t1_5(&self) -> u64 {
+        self.t1_5
+    }
+
+    /// The shortest silence that separates frames
+    pub fn t3_5(&self) -> u64 {
+        self.t3_5
+    }
+}
+
+/// Delimits MODBUS RTU frames using the silent intervals between them
+///
+/// Unlike `RecvBuffer<ModbusRtu>`, this doesn't need to understand the PDU to find the end of a
+/// frame, so it works for any function code. Instead, every byte is given along with a
+/// timestamp of when it finished arriving (for example, read from a free-running timer in the
+/// UART receive interrupt). Timestamps are ticks of any unit, as configured by `RtuTiming`, and
+/// are allowed to wrap around.
+///
+/// A frame ends once the line has been silent for t3.5. That is noticed either when the next
+/// byte arrives (`receive`), or by checking the time without a new byte (`poll`). Call `poll`
+/// from a timer so the last frame before a quiet period isn't held up.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::rtu_timing::*;
+///
+/// let mut receiver = RtuReceiver::new(RtuTiming::new(9600, 1_000_000));
+/// let adu = [0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+///
+/// for (index, &byte) in adu.iter().enumerate() {
+///     assert!(receiver.receive(byte, 1146 * index as u64).is_none());
+/// }
+///
+/// // Nothing yet, because the line hasn't been quiet for long enough
+/// let end = 1146 * 7;
+/// assert!(receiver.poll(end + 4010).is_none());
+///
+/// let frame = receiver.poll(end + 4011).unwrap().unwrap();
+/// assert_eq!(frame.packet.header.address, 0x11);
+/// assert_eq!(frame.packet.pdu, &[0x03, 0x00, 0x6b, 0x00, 0x03]);
+/// assert!(!frame.t1_5_violation);
+/// ```
+pub struct RtuReceiver {
+    // If frame_ready is true, the buffer holds a frame that has been handed out, and must be
+    // cleared before anything else is added.
+    raw_buffer: <ModbusRtu as ModbusProtocol>::Buffer,
+    size_used: usize,
+    frame_ready: bool,
+    overflow: bool,
+    t1_5_violation: bool,
+    last_tick: u64,
+
+    // The byte that ended the silence after the last frame, which starts the next one
+    pending: Option<(u8, u64)>,
+
+    timing: RtuTiming,
+}
+
+/// A MODBUS RTU frame delimited by `RtuReceiver`
+#[derive(Debug, PartialEq)]
+pub struct RtuFrame<'a> {
+    pub packet: Packet<'a, ModbusRtu>,
+
+    /// Whether there was a silence longer than t1.5 between two characters in the frame
+    ///
+    /// The MODBUS serial line specification says these frames should be discarded, but some
+    /// devices produce them anyway, so it's up to you.
+    pub t1_5_violation: bool,
+}
+
+impl RtuReceiver {
+    /// Create a new receiver for a line with the given timing
+    pub fn new(timing: RtuTiming) -> Self {
+        RtuReceiver {
+            raw_buffer: AduBuffer::zeroed(),
+            size_used: 0,
+            frame_ready: false,
+            overflow: false,
+            t1_5_violation: false,
+            last_tick: 0,
+            pending: None,
+            timing,
+        }
+    }
+
+    /// The timing this receiver was created with
+    pub fn timing(&self) -> RtuTiming {
+        self.timing
+    }
+
+    /// Receive a byte that finished arriving at `now`
+    ///
+    /// If this byte came after a silence of at least t3.5, the frame before it is complete and
+    /// is returned. The byte itself is kept as the start of the next frame.
+    ///
+    /// If the frame is invalid, you get `Err` with `CrcMismatch` for a bad CRC, or
+    /// `BadLength` if it's too short or too long.
+    pub fn receive(&mut self, byte: u8, now: u64) -> Option<Result<RtuFrame<'_>, ModbusError>> {
+        self.start_next_frame();
+
+        if self.size_used == 0 {
+            self.add_byte(byte, now);
+            return None;
+        }
+
+        // The timestamp is taken at the end of the character, so the time it took to arrive
+        // isn't part of the silence
+        let silence = now
+            .wrapping_sub(self.last_tick)
+            .saturating_sub(self.timing.character);
+
+        if silence >= self.timing.t3_5 {
+            self.pending = Some((byte, now));
+
+            return Some(self.frame());
+        }
+
+        if silence > self.timing.t1_5 {
+            self.t1_5_violation = true;
+        }
+
+        self.add_byte(byte, now);
+
+        None
+    }
+
+    /// Check whether the line has been silent for long enough to end the current frame
+    ///
+    /// If it has, the frame is returned, just like `receive`.
+    pub fn poll(&mut self, now: u64) -> Option<Result<RtuFrame<'_>, ModbusError>> {
+        self.start_next_frame();
+
+        if self.size_used > 0 && now.wrapping_sub(self.last_tick) >= self.timing.t3_5 {
+            Some(self.frame())
+        } else {
+            None
+        }
+    }
+
+    /// Clear the last frame if it's been handed out, and add any byte that was waiting for it
+    fn start_next_frame(&mut self) {
+        if self.frame_ready {
+            self.size_used = 0;
+            self.frame_ready = false;
+            self.overflow = false;
+            self.t1_5_violation = false;
+        }
+
+        if self.size_used == 0 {
+            if let Some((byte, tick)) = self.pending.take() {
+                self.add_byte(byte, tick);
+            }
+        }
+    }
+
+    fn add_byte(&mut self, byte: u8, now: u64) {
+        let buffer = self.raw_buffer.as_mut();
+
+        match buffer.get_mut(self.size_used) {
+            Some(slot) => {
+                *slot = byte;
+                self.size_used += 1;
+            }
+            None => self.overflow = true,
+        }
+
+        self.last_tick = now;
+    }
+
+    fn frame(&mut self) -> Result<RtuFrame<'_>, ModbusError> {
+        use ModbusError::{BadLength, NotEnoughData};
+
+        self.frame_ready = true;
+
+        if self.overflow {
+            return Err(BadLength);
+        }
+
+        let adu = &self.raw_buffer.as_ref()[..self.size_used];
+
+        let packet = match (ModbusRtu::adu_header(adu), ModbusRtu::pdu_body(adu)) {
+            (Ok(header), Ok(pdu)) => Packet { header, pdu },
+            (Err(NotEnoughData), _) | (_, Err(NotEnoughData)) => return Err(BadLength),
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+
+        Ok(RtuFrame {
+            packet,
+            t1_5_violation: self.t1_5_violation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_data::*;
+    use crate::ModbusError::*;
+
+    // 9600 baud, with microsecond ticks
+    const CHARACTER: u64 = 1146;
+    const T1_5: u64 = 1719;
+    const T3_5: u64 = 4011;
+
+    fn receiver() -> RtuReceiver {
+        RtuReceiver::new(RtuTiming::new(9600, 1_000_000))
+    }
+
+    /// Receive `adu` with no gaps between characters, starting at `start`. Returns the time the
+    /// last byte arrived.
+    fn receive_all(receiver: &mut RtuReceiver, adu: &[u8], start: u64) -> u64 {
+        let mut now = start;
+
+        for &byte in adu {
+            now = now.wrapping_add(CHARACTER);
+            assert!(receiver.receive(byte, now).is_none());
+        }
+
+        now
+    }
+
+    #[test]
+    fn timing() {
+        assert_eq!(
+            RtuTiming::new(9600, 1_000_000),
+            RtuTiming {
+                character: CHARACTER,
+                t1_5: T1_5,
+                t3_5: T3_5,
+            }
+        );
+
+        // Fixed timeouts above 19200 baud
+        assert_eq!(
+            RtuTiming::new(38400, 1_000_000),
+            RtuTiming {
+                character: 287,
+                t1_5: 750,
+                t3_5: 1750,
+            }
+        );
+        assert_eq!(RtuTiming::new(19200, 1_000_000).t3_5(), 2006);
+
+        // Millisecond ticks round up
+        assert_eq!(
+            RtuTiming::new(115200, 1000),
+            RtuTiming {
+                character: 1,
+                t1_5: 1,
+                t3_5: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn frames_separated_by_silence() {
+        let mut receiver = receiver();
+        let end = receive_all(&mut receiver, ADU3_RTU, 0);
+
+        // The first byte of the next frame ends the last one
+        let mut now = end + CHARACTER + T3_5;
+        let frame = receiver.receive(ADU4_RTU[0], now).unwrap().unwrap();
+
+        assert_eq!(frame.packet.header, ADU3_HEADER);
+        assert_eq!(frame.packet.pdu, ADU3_PDU());
+        assert!(!frame.t1_5_violation);
+
+        for &byte in &ADU4_RTU[1..] {
+            now += CHARACTER;
+            assert!(receiver.receive(byte, now).is_none());
+        }
+
+        assert!(receiver.poll(now + T3_5 - 1).is_none());
+
+        let frame = receiver.poll(now + T3_5).unwrap().unwrap();
+        assert_eq!(frame.packet.header, ADU4_HEADER);
+        assert_eq!(frame.packet.pdu, ADU4_PDU());
+
+        assert!(receiver.poll(now + 10 * T3_5).is_none());
+    }
+
+    #[test]
+    fn t1_5_violation() {
+        let mut receiver = receiver();
+        let mut now = receive_all(&mut receiver, &ADU3_RTU[..4], 0);
+
+        // Too long inside a frame, but not long enough to end it
+        now += CHARACTER + T1_5 + 1;
+        assert!(receiver.receive(ADU3_RTU[4], now).is_none());
+
+        let now = receive_all(&mut receiver, &ADU3_RTU[5..], now);
+
+        let frame = receiver.poll(now + T3_5).unwrap().unwrap();
+        assert_eq!(frame.packet.pdu, ADU3_PDU());
+        assert!(frame.t1_5_violation);
+
+        // The flag doesn't carry over to the next frame
+        let now = receive_all(&mut receiver, ADU3_RTU, now + T3_5);
+        assert!(!receiver.poll(now + T3_5).unwrap().unwrap().t1_5_violation);
+    }
+
+    #[test]
+    fn bad_frames() {
+        let mut receiver = receiver();
+
+        let mut bad_crc = [0; ADU3_ADU_LENGTH];
+        bad_crc.copy_from_slice(ADU3_RTU);
+        bad_crc[2] ^= 0x01;
+
+        let now = receive_all(&mut receiver, &bad_crc, 0);
+        assert_eq!(
+            receiver.poll(now + T3_5).unwrap(),
+            Err(CrcMismatch {
+                expected: 0x7B77,
+                actual: ADU3_HEADER.crc
+            })
+        );
+
+        let now = receive_all(&mut receiver, &ADU3_RTU[..3], now + T3_5);
+        assert_eq!(receiver.poll(now + T3_5).unwrap(), Err(BadLength));
+
+        let now = receive_all(&mut receiver, &[0; 300], now + T3_5);
+        assert_eq!(receiver.poll(now + T3_5).unwrap(), Err(BadLength));
+
+        // Still works after all that
+        let now = receive_all(&mut receiver, ADU3_RTU, now + T3_5);
+        assert_eq!(
+            receiver.poll(now + T3_5).unwrap().unwrap().packet.pdu,
+            ADU3_PDU()
+        );
+    }
+
+    #[test]
+    fn timestamps_wrap() {
+        let mut receiver = receiver();
+        let start = u64::MAX - 3 * CHARACTER;
+
+        let now = receive_all(&mut receiver, ADU3_RTU, start);
+        assert!(now < start);
+
+        assert!(receiver.poll(now.wrapping_add(T3_5)).is_some());
+    }
+}
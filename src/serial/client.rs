@@ -0,0 +1,528 @@
+use super::{DirectionControl, SerialError};
+use crate::client::{RtuEvent, RtuMaster};
+use crate::pdu::{Response, PDU_MAX_LENGTH};
+use crate::ModbusError;
+
+/// The broadcast address, which `request` doesn't accept
+const BROADCAST_ADDRESS: u8 = 0;
+
+// How much is read from the serial port at once
+const CHUNK_LENGTH: usize = 64;
+
+/// A blocking MODBUS RTU client over an `embedded-io` serial port
+///
+/// Requests are sent and retried by an `RtuMaster`, so its timeouts and retry policy apply.
+/// Time comes from `clock`, which returns the current time in the master's ticks (for example,
+/// from a free-running timer). While waiting for a response, the client polls the serial port
+/// with `ReadReady`, so the timeout can be checked without blocking in `read`.
+///
+/// The response timeout starts when a request starts being sent, so it has to allow for the time
+/// taken to send it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use modbus_core::client::{RetryPolicy, RtuMaster};
+/// use modbus_core::serial::{RtuClient, SerialError};
+/// # use core::convert::Infallible;
+/// # struct Uart;
+/// # impl embedded_io::ErrorType for Uart {
+/// #     type Error = Infallible;
+/// # }
+/// # impl embedded_io::Read for Uart {
+/// #     fn read(&mut self, _: &mut [u8]) -> Result<usize, Infallible> { Ok(0) }
+/// # }
+/// # impl embedded_io::ReadReady for Uart {
+/// #     fn read_ready(&mut self) -> Result<bool, Infallible> { Ok(false) }
+/// # }
+/// # impl embedded_io::Write for Uart {
+/// #     fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> { Ok(buf.len()) }
+/// #     fn flush(&mut self) -> Result<(), Infallible> { Ok(()) }
+/// # }
+/// # struct DePin;
+/// # impl modbus_core::serial::DirectionControl for DePin {
+/// #     fn set_transmit(&mut self, _: bool) {}
+/// # }
+/// # struct Timer;
+/// # impl Timer {
+/// #     fn now_millis(&self) -> u64 { 0 }
+/// # }
+/// # fn main() -> Result<(), SerialError<Infallible>> {
+/// # let (uart, de_pin, timer) = (Uart, DePin, Timer);
+/// let mut master = RtuMaster::new(100, 5);
+/// master.set_retry_policy(RetryPolicy { retries: 2, retry_invalid: true });
+///
+/// let mut client = RtuClient::new(uart, master, de_pin, || timer.now_millis());
+///
+/// let response = client.request(0x11, &[0x03, 0x00, 0x6b, 0x00, 0x01])?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RtuClient<S, D, C> {
+    serial: S,
+    master: RtuMaster,
+    direction: D,
+    clock: C,
+    pdu: [u8; PDU_MAX_LENGTH],
+}
+
+impl<S, D, C> RtuClient<S, D, C>
+where
+    S: embedded_io::Read + embedded_io::ReadReady + embedded_io::Write,
+    D: DirectionControl,
+    C: FnMut() -> u64,
+{
+    /// Create a client that sends requests through `master`
+    pub fn new(serial: S, master: RtuMaster, mut direction: D, clock: C) -> Self {
+        direction.set_transmit(false);
+
+        RtuClient {
+            serial,
+            master,
+            direction,
+            clock,
+            pdu: [0; PDU_MAX_LENGTH],
+        }
+    }
+
+    /// Send a request to `address`, and wait for the response
+    ///
+    /// Exception responses are returned as `Response::Exception`. If there's no usable response
+    /// after any retries, returns `Err(TimedOut)`, or `Err(Modbus(_))` with the problem with the
+    /// last reply. If the serial port reaches end of file, returns `Err(Eof)`. Requests can't be
+    /// broadcast with this (see `broadcast`), so an `address` of 0 returns
+    /// `Err(Modbus(BadValue))`.
+    ///
+    /// Anything received before the request is sent is thrown away.
+    pub fn request(
+        &mut self,
+        address: u8,
+        pdu: &[u8],
+    ) -> Result<Response<'_>, SerialError<S::Error>> {
+        if address == BROADCAST_ADDRESS {
+            return Err(ModbusError::BadValue.into());
+        }
+
+        self.send(address, pdu)?;
+
+        let mut chunk = [0; CHUNK_LENGTH];
+
+        let length = loop {
+            let now = (self.clock)();
+
+            let event = if self.serial.read_ready().map_err(SerialError::Io)? {
+                let count = read(&mut self.serial, &mut chunk)?;
+                self.master.receive(&chunk[..count], now)
+            } else {
+                self.master.poll(now)
+            };
+
+            match event {
+                None => {}
+                Some(RtuEvent::Response { pdu, .. }) => {
+                    self.pdu[..pdu.len()].copy_from_slice(pdu);
+                    break pdu.len();
+                }
+                Some(RtuEvent::Resend(adu)) => {
+                    transmit(&mut self.serial, &mut self.direction, adu).map_err(SerialError::Io)?
+                }
+                Some(RtuEvent::TimedOut) => return Err(SerialError::TimedOut),
+                Some(RtuEvent::Invalid(e)) => return Err(e.into()),
+            }
+        };
+
+        Ok(Response::parse(&self.pdu[..length])?)
+    }
+
+    /// Send a request to every device
+    ///
+    /// Nothing answers a broadcast. The next request waits for the master's turnaround delay,
+    /// to give the devices time to handle this one.
+    pub fn broadcast(&mut self, pdu: &[u8]) -> Result<(), SerialError<S::Error>> {
+        self.send(BROADCAST_ADDRESS, pdu)
+    }
+
+    /// The master that requests are sent through, for changing its settings
+    pub fn master_mut(&mut self) -> &mut RtuMaster {
+        &mut self.master
+    }
+
+    /// Get the serial port and direction control back
+    pub fn release(self) -> (S, D) {
+        (self.serial, self.direction)
+    }
+
+    /// Wait until the master is ready, then start a request
+    fn send(&mut self, address: u8, pdu: &[u8]) -> Result<(), SerialError<S::Error>> {
+        let now = loop {
+            let now = (self.clock)();
+
+            if self.master.is_ready(now) {
+                break now;
+            }
+        };
+
+        let mut chunk = [0; CHUNK_LENGTH];
+
+        while self.serial.read_ready().map_err(SerialError::Io)? {
+            read(&mut self.serial, &mut chunk)?;
+        }
+
+        let adu = self.master.send(address, pdu, now)?;
+
+        transmit(&mut self.serial, &mut self.direction, adu).map_err(SerialError::Io)
+    }
+}
+
+/// An async MODBUS RTU client over an `embedded-io-async` serial port
+///
+/// There's no clock here, so the client doesn't time out, retry, or wait after broadcasts by
+/// itself. Use your executor's timers for those, like `embassy_time::with_timeout`. If a
+/// request is dropped before it finishes, the next one starts afresh.
+///
+/// # Examples
+///
+/// ```no_run
+/// use embassy_time::{with_timeout, Duration};
+/// use modbus_core::serial::AsyncRtuClient;
+/// # mod embassy_time {
+/// #     pub use core::time::Duration;
+/// #     pub async fn with_timeout<F: core::future::Future>(
+/// #         _: Duration,
+/// #         f: F,
+/// #     ) -> Result<F::Output, ()> {
+/// #         Ok(f.await)
+/// #     }
+/// # }
+/// # use core::convert::Infallible;
+/// # struct Uart;
+/// # impl embedded_io_async::ErrorType for Uart {
+/// #     type Error = Infallible;
+/// # }
+/// # impl embedded_io_async::Read for Uart {
+/// #     async fn read(&mut self, _: &mut [u8]) -> Result<usize, Infallible> { Ok(0) }
+/// # }
+/// # impl embedded_io_async::Write for Uart {
+/// #     async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> { Ok(buf.len()) }
+/// # }
+/// # struct DePin;
+/// # impl modbus_core::serial::DirectionControl for DePin {
+/// #     fn set_transmit(&mut self, _: bool) {}
+/// # }
+/// # async fn example(uart: Uart, de_pin: DePin) {
+/// # let pdu = [0x03, 0x00, 0x6b, 0x00, 0x01];
+/// let mut client = AsyncRtuClient::new(uart, de_pin);
+///
+/// let response = with_timeout(Duration::from_millis(100), client.request(0x11, &pdu)).await;
+/// # }
+/// ```
+pub struct AsyncRtuClient<S, D> {
+    serial: S,
+    master: RtuMaster,
+    direction: D,
+    pdu: [u8; PDU_MAX_LENGTH],
+}
+
+impl<S, D> AsyncRtuClient<S, D>
+where
+    S: embedded_io_async::Read + embedded_io_async::Write,
+    D: DirectionControl,
+{
+    /// Create a client
+    pub fn new(serial: S, mut direction: D) -> Self {
+        direction.set_transmit(false);
+
+        AsyncRtuClient {
+            serial,
+            // Time stands still, so these are never reached
+            master: RtuMaster::new(0, 0),
+            direction,
+            pdu: [0; PDU_MAX_LENGTH],
+        }
+    }
+
+    /// Send a request to `address`, and wait for the response
+    ///
+    /// Exception responses are returned as `Response::Exception`. If the reply can't be used,
+    /// returns `Err(Modbus(_))` with the problem, and if the serial port reaches end of file,
+    /// returns `Err(Eof)`. An `address` of 0 returns `Err(Modbus(BadValue))`, because nothing
+    /// would answer (see `broadcast`).
+    pub async fn request(
+        &mut self,
+        address: u8,
+        pdu: &[u8],
+    ) -> Result<Response<'_>, SerialError<S::Error>> {
+        if address == BROADCAST_ADDRESS {
+            return Err(ModbusError::BadValue.into());
+        }
+
+        self.send(address, pdu).await?;
+
+        let mut chunk = [0; CHUNK_LENGTH];
+
+        let length = loop {
+            let count = read_async(&mut self.serial, &mut chunk).await?;
+
+            match self.master.receive(&chunk[..count], 0) {
+                Some(RtuEvent::Response { pdu, .. }) => {
+                    self.pdu[..pdu.len()].copy_from_slice(pdu);
+                    break pdu.len();
+                }
+                Some(RtuEvent::Invalid(e)) => return Err(e.into()),
+                _ => {}
+            }
+        };
+
+        Ok(Response::parse(&self.pdu[..length])?)
+    }
+
+    /// Send a request to every device
+    ///
+    /// Nothing answers a broadcast. Wait for the turnaround delay before sending the next
+    /// request, to give the devices time to handle this one.
+    pub async fn broadcast(&mut self, pdu: &[u8]) -> Result<(), SerialError<S::Error>> {
+        self.send(BROADCAST_ADDRESS, pdu).await
+    }
+
+    /// Get the serial port and direction control back
+    pub fn release(self) -> (S, D) {
+        (self.serial, self.direction)
+    }
+
+    async fn send(&mut self, address: u8, pdu: &[u8]) -> Result<(), SerialError<S::Error>> {
+        // Forget about a request that was dropped while it was waiting
+        self.master.cancel();
+
+        let adu = self.master.send(address, pdu, 0)?;
+
+        self.direction.set_transmit(true);
+        let result = transmit_async(&mut self.serial, adu).await;
+        self.direction.set_transmit(false);
+
+        result.map_err(SerialError::Io)
+    }
+}
+
+/// Read some data, treating end of file as an error
+///
+/// A read of 0 bytes would otherwise be retried forever, without giving a timeout the chance to
+/// fire.
+pub(super) fn read<S>(serial: &mut S, buf: &mut [u8]) -> Result<usize, SerialError<S::Error>>
+where
+    S: embedded_io::Read,
+{
+    match serial.read(buf).map_err(SerialError::Io)? {
+        0 => Err(SerialError::Eof),
+        count => Ok(count),
+    }
+}
+
+/// Read some data asynchronously, treating end of file as an error
+pub(super) async fn read_async<S>(
+    serial: &mut S,
+    buf: &mut [u8],
+) -> Result<usize, SerialError<S::Error>>
+where
+    S: embedded_io_async::Read,
+{
+    match serial.read(buf).await.map_err(SerialError::Io)? {
+        0 => Err(SerialError::Eof),
+        count => Ok(count),
+    }
+}
+
+/// Write a whole ADU with the transceiver set to transmit
+pub(super) fn transmit<S, D>(serial: &mut S, direction: &mut D, adu: &[u8]) -> Result<(), S::Error>
+where
+    S: embedded_io::Write,
+    D: DirectionControl,
+{
+    direction.set_transmit(true);
+    let result = serial.write_all(adu).and_then(|_| serial.flush());
+    direction.set_transmit(false);
+
+    result
+}
+
+/// Write a whole ADU, and wait for it to be sent
+pub(super) async fn transmit_async<S>(serial: &mut S, adu: &[u8]) -> Result<(), S::Error>
+where
+    S: embedded_io_async::Write,
+{
+    serial.write_all(adu).await?;
+    serial.flush().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::mock::*;
+    use super::*;
+    use crate::client::RetryPolicy;
+    use crate::pdu::{Exception, ExceptionCode};
+    use crate::test_data::*;
+
+    // Read 1 holding register at 0x6b from 0x11, and the response
+    const REQUEST: &[u8] = &[0x03, 0x00, 0x6b, 0x00, 0x01];
+    const REQUEST_ADU: &[u8] = &[0x11, 0x03, 0x00, 0x6b, 0x00, 0x01, 0xf7, 0x46];
+    const RESPONSE_ADU: &[u8] = &[0x11, 0x03, 0x02, 0x12, 0x34, 0x74, 0xf0];
+
+    fn clock() -> impl FnMut() -> u64 {
+        let mut now = 0;
+
+        move || {
+            now += 1;
+            now
+        }
+    }
+
+    #[test]
+    fn request() {
+        let (mut serial, pin) = MockSerial::new(&[&[0xFF]]);
+        serial.replies.push_back(RESPONSE_ADU.to_vec());
+
+        let mut client = RtuClient::new(serial, RtuMaster::new(100, 10), pin, clock());
+
+        // The stale byte is thrown away before the request is sent
+        let response = client.request(0x11, REQUEST).unwrap();
+        assert_eq!(response.registers().unwrap().get(0), Some(0x1234));
+
+        let (serial, pin) = client.release();
+        assert_eq!(serial.output, REQUEST_ADU);
+        assert_eq!(pin.switches, 3);
+    }
+
+    #[test]
+    fn request_errors() {
+        let exception = [0x11, 0x83, 0x02, 0xc1, 0x34];
+        let (serial, pin) = MockSerial::new(&[]);
+        let mut client = RtuClient::new(serial, RtuMaster::new(100, 10), pin, clock());
+
+        assert_eq!(
+            client.request(0, REQUEST),
+            Err(SerialError::Modbus(ModbusError::BadValue))
+        );
+        assert_eq!(client.request(0x11, REQUEST), Err(SerialError::TimedOut));
+
+        // The response to another request
+        client.serial.replies.push_back(ADU4_RTU.to_vec());
+        assert_eq!(
+            client.request(0x11, REQUEST),
+            Err(SerialError::Modbus(ModbusError::ByteCountMismatch {
+                expected: 2,
+                actual: 6
+            }))
+        );
+
+        client.serial.replies.push_back(exception.to_vec());
+        assert_eq!(
+            client.request(0x11, REQUEST),
+            Ok(Response::Exception(Exception {
+                function_code: 0x03,
+                code: ExceptionCode::IllegalDataAddress
+            }))
+        );
+    }
+
+    #[test]
+    fn retries() {
+        let (serial, pin) = MockSerial::new(&[]);
+        let mut master = RtuMaster::new(100, 10);
+        master.set_retry_policy(RetryPolicy {
+            retries: 2,
+            retry_invalid: false,
+        });
+
+        let mut client = RtuClient::new(serial, master, pin, clock());
+        assert_eq!(client.request(0x11, REQUEST), Err(SerialError::TimedOut));
+
+        let (serial, pin) = client.release();
+        assert_eq!(
+            serial.output,
+            [REQUEST_ADU, REQUEST_ADU, REQUEST_ADU].concat()
+        );
+        assert_eq!(pin.switches, 7);
+
+        // Retrying after a bad reply
+        let (mut serial, pin) = MockSerial::new(&[]);
+        serial.replies.push_back(ADU4_RTU.to_vec());
+        serial.replies.push_back(RESPONSE_ADU.to_vec());
+
+        let mut master = RtuMaster::new(100, 10);
+        master.set_retry_policy(RetryPolicy {
+            retries: 1,
+            retry_invalid: true,
+        });
+
+        let mut client = RtuClient::new(serial, master, pin, clock());
+        let response = client.request(0x11, REQUEST).unwrap();
+        assert_eq!(response.registers().unwrap().get(0), Some(0x1234));
+        assert_eq!(client.release().0.output.len(), 2 * REQUEST_ADU.len());
+    }
+
+    #[test]
+    fn eof() {
+        // Before the request is sent, while stale data is being thrown away
+        let (serial, pin) = MockSerial::new(&[&[]]);
+        let mut client = RtuClient::new(serial, RtuMaster::new(100, 10), pin, clock());
+        assert_eq!(client.request(0x11, REQUEST), Err(SerialError::Eof));
+
+        // While waiting for the response
+        client.serial.replies.push_back(Vec::new());
+        assert_eq!(client.request(0x11, REQUEST), Err(SerialError::Eof));
+        assert_eq!(client.release().0.output, REQUEST_ADU);
+    }
+
+    #[test]
+    fn broadcast() {
+        let (serial, pin) = MockSerial::new(&[]);
+        let mut client = RtuClient::new(serial, RtuMaster::new(100, 10), pin, clock());
+
+        client.broadcast(&[0x06, 0x00, 0x01, 0x00, 0x03]).unwrap();
+        client.broadcast(&[0x06, 0x00, 0x01, 0x00, 0x03]).unwrap();
+
+        // The second broadcast waited for the turnaround delay
+        assert!((client.clock)() > 10);
+        assert_eq!(client.release().0.output.len(), 16);
+    }
+
+    #[tokio::test]
+    async fn async_request() {
+        let (mut serial, pin) = MockSerial::new(&[]);
+        serial.replies.push_back(ADU4_RTU.to_vec());
+        serial.replies.push_back(RESPONSE_ADU.to_vec());
+
+        let mut client = AsyncRtuClient::new(serial, pin);
+
+        assert_eq!(
+            client.request(0, REQUEST).await,
+            Err(SerialError::Modbus(ModbusError::BadValue))
+        );
+
+        // A response to another request is an error
+        assert_eq!(
+            client.request(0x11, REQUEST).await,
+            Err(SerialError::Modbus(ModbusError::ByteCountMismatch {
+                expected: 2,
+                actual: 6
+            }))
+        );
+
+        let response = client.request(0x11, REQUEST).await.unwrap();
+        assert_eq!(response.registers().unwrap().get(0), Some(0x1234));
+
+        client.broadcast(REQUEST).await.unwrap();
+
+        let (serial, pin) = client.release();
+        assert_eq!(serial.output.len(), 3 * REQUEST_ADU.len());
+        assert_eq!(pin.switches, 7);
+    }
+
+    #[tokio::test]
+    async fn async_eof() {
+        // No reply is queued, so the port has nothing more to give
+        let (serial, pin) = MockSerial::new(&[]);
+        let mut client = AsyncRtuClient::new(serial, pin);
+
+        assert_eq!(client.request(0x11, REQUEST).await, Err(SerialError::Eof));
+    }
+}
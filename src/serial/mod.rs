@@ -0,0 +1,225 @@
+//! MODBUS RTU drivers for `embedded-io` serial ports
+//!
+//! These connect `RtuMaster` and the server dispatcher to a real UART, through the
+//! `embedded_io` traits (blocking) or the `embedded_io_async` traits (async). On RS-485 lines,
+//! the transceiver can be switched between transmitting and receiving with a `DirectionControl`.
+//!
+//! This module needs the `embedded-io` feature.
+
+use crate::ModbusError;
+use core::fmt;
+
+mod client;
+mod server;
+
+pub use client::{AsyncRtuClient, RtuClient};
+pub use server::{AsyncRtuServer, RtuServer};
+
+/// Switches an RS-485 transceiver between transmitting and receiving
+///
+/// This is usually an output pin wired to the transceiver's DE and /RE pins. It's set for
+/// transmitting before an ADU is written, and back to receiving once the serial port has been
+/// flushed.
+///
+/// `()` does nothing, for transceivers that switch by themselves (and RS-232).
+///
+/// # Examples
+///
+/// ```
+/// # mod embedded_hal {
+/// #     pub mod digital {
+/// #         pub enum PinState { Low, High }
+/// #         impl From<bool> for PinState {
+/// #             fn from(high: bool) -> Self { if high { PinState::High } else { PinState::Low } }
+/// #         }
+/// #         pub trait OutputPin {
+/// #             fn set_state(&mut self, state: PinState) -> Result<(), ()>;
+/// #         }
+/// #     }
+/// # }
+/// use modbus_core::serial::DirectionControl;
+///
+/// // Using an embedded-hal output pin
+/// struct DePin<P>(P);
+///
+/// impl<P: embedded_hal::digital::OutputPin> DirectionControl for DePin<P> {
+///     fn set_transmit(&mut self, transmit: bool) {
+///         self.0.set_state(transmit.into()).ok();
+///     }
+/// }
+/// ```
+pub trait DirectionControl {
+    /// Get ready to transmit (`true`) or receive (`false`)
+    fn set_transmit(&mut self, transmit: bool);
+}
+
+impl DirectionControl for () {
+    fn set_transmit(&mut self, _transmit: bool) {}
+}
+
+impl<D: DirectionControl + ?Sized> DirectionControl for &mut D {
+    fn set_transmit(&mut self, transmit: bool) {
+        (**self).set_transmit(transmit)
+    }
+}
+
+/// An error from a serial driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SerialError<E> {
+    /// The serial port failed
+    Io(E),
+
+    /// The request couldn't be sent, or the reply couldn't be used
+    Modbus(ModbusError),
+
+    /// No response arrived in time
+    TimedOut,
+
+    /// The serial port reached end of file, so nothing more can be received from it
+    Eof,
+}
+
+impl<E> From<ModbusError> for SerialError<E> {
+    fn from(error: ModbusError) -> Self {
+        SerialError::Modbus(error)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for SerialError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SerialError::Io(e) => write!(f, "serial port error: {:?}", e),
+            SerialError::Modbus(e) => write!(f, "{}", e),
+            SerialError::TimedOut => write!(f, "timed out waiting for a response"),
+            SerialError::Eof => write!(f, "serial port reached end of file"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: fmt::Debug> std::error::Error for SerialError<E> {}
+
+#[cfg(test)]
+mod mock {
+    //! In-memory serial ports for testing the drivers
+
+    use super::DirectionControl;
+    use core::convert::Infallible;
+    use std::cell::Cell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    /// A serial port that receives chunks of data from a queue, and records what's written
+    ///
+    /// Each time the port is flushed, the next of `replies` is added to `input`, as if a device
+    /// had answered. Writing and reading check that the transceiver has been switched the right
+    /// way.
+    pub struct MockSerial {
+        pub input: VecDeque<Vec<u8>>,
+        pub replies: VecDeque<Vec<u8>>,
+        pub output: Vec<u8>,
+        pub transmitting: Rc<Cell<bool>>,
+    }
+
+    impl MockSerial {
+        pub fn new(input: &[&[u8]]) -> (Self, MockPin) {
+            let transmitting = Rc::new(Cell::new(false));
+
+            let serial = MockSerial {
+                input: input.iter().map(|chunk| chunk.to_vec()).collect(),
+                replies: VecDeque::new(),
+                output: Vec::new(),
+                transmitting: transmitting.clone(),
+            };
+
+            let pin = MockPin {
+                transmitting,
+                switches: 0,
+            };
+
+            (serial, pin)
+        }
+
+        fn take_chunk(&mut self, buf: &mut [u8]) -> usize {
+            assert!(!self.transmitting.get(), "read while transmitting");
+
+            match self.input.pop_front() {
+                Some(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    chunk.len()
+                }
+                None => 0,
+            }
+        }
+
+        fn put(&mut self, buf: &[u8]) -> usize {
+            assert!(self.transmitting.get(), "write while receiving");
+
+            self.output.extend_from_slice(buf);
+            buf.len()
+        }
+
+        fn flushed(&mut self) {
+            if let Some(reply) = self.replies.pop_front() {
+                self.input.push_back(reply);
+            }
+        }
+    }
+
+    impl embedded_io::ErrorType for MockSerial {
+        type Error = Infallible;
+    }
+
+    impl embedded_io::Read for MockSerial {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            Ok(self.take_chunk(buf))
+        }
+    }
+
+    impl embedded_io::ReadReady for MockSerial {
+        fn read_ready(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.input.is_empty())
+        }
+    }
+
+    impl embedded_io::Write for MockSerial {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            Ok(self.put(buf))
+        }
+
+        fn flush(&mut self) -> Result<(), Infallible> {
+            self.flushed();
+            Ok(())
+        }
+    }
+
+    impl embedded_io_async::Read for MockSerial {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Infallible> {
+            Ok(self.take_chunk(buf))
+        }
+    }
+
+    impl embedded_io_async::Write for MockSerial {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> {
+            Ok(self.put(buf))
+        }
+
+        async fn flush(&mut self) -> Result<(), Infallible> {
+            self.flushed();
+            Ok(())
+        }
+    }
+
+    /// A DE/RE pin, shared with a `MockSerial`, which counts how often it's set
+    pub struct MockPin {
+        pub transmitting: Rc<Cell<bool>>,
+        pub switches: usize,
+    }
+
+    impl DirectionControl for MockPin {
+        fn set_transmit(&mut self, transmit: bool) {
+            self.transmitting.set(transmit);
+            self.switches += 1;
+        }
+    }
+}
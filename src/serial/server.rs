@@ -0,0 +1,308 @@
+use super::client::{read, read_async, transmit, transmit_async};
+use super::{DirectionControl, SerialError};
+use crate::pdu::PDU_MAX_LENGTH;
+use crate::protocols::{ModbusProtocol, ModbusRtu, ModbusRtuHeader};
+use crate::recv_buffer::RecvBuffer;
+use crate::server::{process_request, ModbusDevice};
+use crate::{Direction, ModbusError};
+
+/// Requests sent to this address go to every device, and aren't answered
+const BROADCAST_ADDRESS: u8 = 0;
+
+// How much is read from the serial port at once
+const CHUNK_LENGTH: usize = 64;
+
+/// The state shared by the blocking and async servers
+struct ServerCore {
+    address: u8,
+    buffer: RecvBuffer<ModbusRtu>,
+    response: [u8; PDU_MAX_LENGTH],
+    adu: <ModbusRtu as ModbusProtocol>::Buffer,
+}
+
+impl ServerCore {
+    fn new(address: u8) -> Result<Self, ModbusError> {
+        if address == BROADCAST_ADDRESS || address > ModbusRtu::MAX_ADDRESS {
+            return Err(ModbusError::BadValue);
+        }
+
+        // Other devices' responses can be on the line too, so the buffer has to be able to skip
+        // them
+        let mut buffer = RecvBuffer::with_direction(Direction::Query);
+        buffer.set_hunt_mode(true);
+
+        Ok(ServerCore {
+            address,
+            buffer,
+            response: [0; PDU_MAX_LENGTH],
+            adu: [0; ModbusRtu::ADU_MAX_LENGTH],
+        })
+    }
+
+    /// Handle the next request in `data` with `device`
+    ///
+    /// Returns `None` once there are no more complete requests. Otherwise, returns the length of
+    /// the reply in `adu` (if there is one) and the rest of `data`, which should be handled next.
+    fn handle<'p, D>(&mut self, data: &'p [u8], device: &mut D) -> Option<(Option<usize>, &'p [u8])>
+    where
+        D: ModbusDevice + ?Sized,
+    {
+        let (packet, rest) = self.buffer.process(data).ok()?;
+        let address = packet.header.address;
+
+        if address != self.address && address != BROADCAST_ADDRESS {
+            return Some((None, rest));
+        }
+
+        // Only an empty PDU fails, and there's no way to answer that
+        let reply = match process_request(device, packet.pdu, &mut self.response) {
+            Ok(length) if address != BROADCAST_ADDRESS => {
+                let header = ModbusRtuHeader { address, crc: 0 };
+
+                ModbusRtu::write_adu(&header, &self.response[..length], &mut self.adu).ok()
+            }
+            _ => None,
+        };
+
+        Some((reply, rest))
+    }
+}
+
+/// A blocking MODBUS RTU server over an `embedded-io` serial port
+///
+/// Requests for the server's address are answered by a `ModbusDevice`. Broadcasts are handled
+/// too, but not answered, and requests for other devices are ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// use modbus_core::serial::{RtuServer, SerialError};
+/// use modbus_core::server::DataBank;
+/// # use core::convert::Infallible;
+/// # struct Uart;
+/// # impl embedded_io::ErrorType for Uart {
+/// #     type Error = Infallible;
+/// # }
+/// # impl embedded_io::Read for Uart {
+/// #     fn read(&mut self, _: &mut [u8]) -> Result<usize, Infallible> { Ok(0) }
+/// # }
+/// # impl embedded_io::Write for Uart {
+/// #     fn write(&mut self, buf: &[u8]) -> Result<usize, Infallible> { Ok(buf.len()) }
+/// #     fn flush(&mut self) -> Result<(), Infallible> { Ok(()) }
+/// # }
+/// # struct DePin;
+/// # impl modbus_core::serial::DirectionControl for DePin {
+/// #     fn set_transmit(&mut self, _: bool) {}
+/// # }
+/// # fn main() -> Result<(), SerialError<Infallible>> {
+/// # let (uart, de_pin) = (Uart, DePin);
+/// let mut bank: DataBank<[u8; 16], [u16; 64]> = DataBank::new();
+/// let mut server = RtuServer::new(uart, 0x11, de_pin).unwrap();
+///
+/// loop {
+///     server.serve(&mut bank)?;
+/// }
+/// # }
+/// ```
+pub struct RtuServer<S, D> {
+    serial: S,
+    direction: D,
+    core: ServerCore,
+}
+
+impl<S, D> RtuServer<S, D>
+where
+    S: embedded_io::Read + embedded_io::Write,
+    D: DirectionControl,
+{
+    /// Create a server for the device at `address`
+    ///
+    /// If `address` isn't a valid device address (1 to 247), returns `Err(BadValue)`.
+    pub fn new(serial: S, address: u8, mut direction: D) -> Result<Self, ModbusError> {
+        let core = ServerCore::new(address)?;
+        direction.set_transmit(false);
+
+        Ok(RtuServer {
+            serial,
+            direction,
+            core,
+        })
+    }
+
+    /// Read from the serial port once, and handle any requests that have been completed
+    ///
+    /// This blocks until the serial port has some data. Call it in a loop. If the serial port
+    /// reaches end of file, returns `Err(Eof)`.
+    pub fn serve<M>(&mut self, device: &mut M) -> Result<(), SerialError<S::Error>>
+    where
+        M: ModbusDevice + ?Sized,
+    {
+        let mut chunk = [0; CHUNK_LENGTH];
+        let count = read(&mut self.serial, &mut chunk)?;
+        let mut data = &chunk[..count];
+
+        while let Some((reply, rest)) = self.core.handle(data, device) {
+            if let Some(length) = reply {
+                let adu = &self.core.adu[..length];
+                transmit(&mut self.serial, &mut self.direction, adu).map_err(SerialError::Io)?;
+            }
+
+            data = rest;
+        }
+
+        Ok(())
+    }
+
+    /// The address the server answers to
+    pub fn address(&self) -> u8 {
+        self.core.address
+    }
+
+    /// Get the serial port and direction control back
+    pub fn release(self) -> (S, D) {
+        (self.serial, self.direction)
+    }
+}
+
+/// An async MODBUS RTU server over an `embedded-io-async` serial port
+///
+/// This works like `RtuServer`, but waits for data asynchronously.
+pub struct AsyncRtuServer<S, D> {
+    serial: S,
+    direction: D,
+    core: ServerCore,
+}
+
+impl<S, D> AsyncRtuServer<S, D>
+where
+    S: embedded_io_async::Read + embedded_io_async::Write,
+    D: DirectionControl,
+{
+    /// Create a server for the device at `address`
+    ///
+    /// If `address` isn't a valid device address (1 to 247), returns `Err(BadValue)`.
+    pub fn new(serial: S, address: u8, mut direction: D) -> Result<Self, ModbusError> {
+        let core = ServerCore::new(address)?;
+        direction.set_transmit(false);
+
+        Ok(AsyncRtuServer {
+            serial,
+            direction,
+            core,
+        })
+    }
+
+    /// Read from the serial port once, and handle any requests that have been completed
+    ///
+    /// If the serial port reaches end of file, returns `Err(Eof)`.
+    pub async fn serve<M>(&mut self, device: &mut M) -> Result<(), SerialError<S::Error>>
+    where
+        M: ModbusDevice + ?Sized,
+    {
+        let mut chunk = [0; CHUNK_LENGTH];
+        let count = read_async(&mut self.serial, &mut chunk).await?;
+        let mut data = &chunk[..count];
+
+        while let Some((reply, rest)) = self.core.handle(data, device) {
+            if let Some(length) = reply {
+                self.direction.set_transmit(true);
+                let result = transmit_async(&mut self.serial, &self.core.adu[..length]).await;
+                self.direction.set_transmit(false);
+
+                result.map_err(SerialError::Io)?;
+            }
+
+            data = rest;
+        }
+
+        Ok(())
+    }
+
+    /// The address the server answers to
+    pub fn address(&self) -> u8 {
+        self.core.address
+    }
+
+    /// Get the serial port and direction control back
+    pub fn release(self) -> (S, D) {
+        (self.serial, self.direction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::mock::*;
+    use super::*;
+    use crate::server::DataBank;
+    use crate::test_data::*;
+
+    // Write 0xABCD to holding register 1, and the response (an echo of the request)
+    const WRITE_ADU: &[u8] = &[0x11, 0x06, 0x00, 0x01, 0xab, 0xcd, 0x64, 0x3f];
+
+    fn bank() -> DataBank<[u8; 2], [u16; 4]> {
+        let mut bank = DataBank::new();
+        bank.holding_registers.set(0, 0x1234).unwrap();
+
+        bank
+    }
+
+    #[test]
+    fn addresses() {
+        for &address in &[0, 248, 255] {
+            let (serial, pin) = MockSerial::new(&[]);
+            assert!(RtuServer::new(serial, address, pin).is_err());
+        }
+
+        let (serial, pin) = MockSerial::new(&[]);
+        assert_eq!(RtuServer::new(serial, 0x11, pin).unwrap().address(), 0x11);
+    }
+
+    #[test]
+    fn serve() {
+        let mut bank = bank();
+        let broadcast = [0x00, 0x06, 0x00, 0x02, 0x00, 0x07, 0x68, 0x19];
+
+        // ADU3 reads past the end of the bank, ADU4 is a response that has to be skipped, and
+        // the last request is for another device
+        let (serial, pin) = MockSerial::new(&[
+            &ADU3_RTU[..5],
+            &[&ADU3_RTU[5..], ADU4_RTU, WRITE_ADU].concat(),
+            &broadcast,
+            &[0x12, 0x06, 0x00, 0x01, 0x00, 0x00, 0xda, 0xa9],
+        ]);
+        let mut server = RtuServer::new(serial, 0x11, pin).unwrap();
+
+        for _ in 0..4 {
+            server.serve(&mut bank).unwrap();
+        }
+
+        assert_eq!(server.serve(&mut bank), Err(SerialError::Eof));
+
+        assert_eq!(bank.holding_registers.get(1), Some(0xabcd));
+        assert_eq!(bank.holding_registers.get(2), Some(0x0007));
+
+        let (serial, pin) = server.release();
+        assert_eq!(
+            serial.output,
+            [&[0x11, 0x83, 0x02, 0xc1, 0x34], WRITE_ADU].concat()
+        );
+        assert_eq!(pin.switches, 5);
+    }
+
+    #[tokio::test]
+    async fn async_serve() {
+        let mut bank = bank();
+        let (serial, pin) = MockSerial::new(&[&WRITE_ADU[..3], &WRITE_ADU[3..]]);
+        let mut server = AsyncRtuServer::new(serial, 0x11, pin).unwrap();
+
+        server.serve(&mut bank).await.unwrap();
+        assert!(server.serial.output.is_empty());
+
+        server.serve(&mut bank).await.unwrap();
+        assert_eq!(bank.holding_registers.get(1), Some(0xabcd));
+
+        let (serial, pin) = server.release();
+        assert_eq!(serial.output, WRITE_ADU);
+        assert_eq!(pin.switches, 3);
+    }
+}
@@ -0,0 +1,685 @@
+use super::ModbusDevice;
+use crate::bit_pack::{bytes_needed, get_coils, set_coils};
+use crate::pdu::{Coils, ExceptionCode, Registers};
+use crate::Coil;
+
+/// The number of addresses in each MODBUS data table
+const ADDRESS_SPACE: usize = 0x1_0000;
+
+/// Find the offset of `address..address + quantity` in a table covering `start..start + len`
+///
+/// If any of the addresses are outside of the table, returns `Err(IllegalDataAddress)`.
+fn table_offset(
+    start: u16,
+    len: usize,
+    address: u16,
+    quantity: usize,
+) -> Result<usize, ExceptionCode> {
+    let offset = usize::from(address)
+        .checked_sub(usize::from(start))
+        .ok_or(ExceptionCode::IllegalDataAddress)?;
+
+    if offset + quantity <= len {
+        Ok(offset)
+    } else {
+        Err(ExceptionCode::IllegalDataAddress)
+    }
+}
+
+/// A table of coils or discrete inputs, stored packed eight to a byte
+///
+/// The table covers the addresses `start..start + len`. The storage `B` is usually a byte array
+/// for a fixed capacity, or a `Vec<u8>` with the `std` feature, which can be resized.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::server::BitTable;
+/// use modbus_core::Coil;
+///
+/// // 20 coils at addresses 100 to 119
+/// let mut coils = BitTable::new(100, 20, [0; 3]).unwrap();
+///
+/// coils.set(101, Coil::On).unwrap();
+///
+/// assert_eq!(coils.get(101), Some(Coil::On));
+/// assert_eq!(coils.get(120), None);
+/// assert_eq!(coils.bytes(), &[0b0000_0010, 0, 0]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BitTable<B> {
+    start: u16,
+    len: usize,
+    bytes: B,
+}
+
+impl<B> BitTable<B>
+where
+    B: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Create a table of `len` coils starting at address `start`, stored in `bytes`
+    ///
+    /// Returns `None` if `bytes` is too small to hold `len` coils, or if the table would run
+    /// past address 65535.
+    pub fn new(start: u16, len: usize, bytes: B) -> Option<Self> {
+        if usize::from(start) + len > ADDRESS_SPACE || bytes.as_ref().len() < bytes_needed(len) {
+            return None;
+        }
+
+        Some(BitTable { start, len, bytes })
+    }
+
+    /// The first address in the table
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// The number of coils in the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the table has no coils
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The packed coils, with the coil at `start` in the lowest bit of the first byte
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes.as_ref()[..bytes_needed(self.len)]
+    }
+
+    /// Get the coil at `address`, or `None` if it isn't in the table
+    pub fn get(&self, address: u16) -> Option<Coil> {
+        let offset = table_offset(self.start, self.len, address, 1).ok()?;
+        let byte = self.bytes.as_ref()[offset / 8];
+
+        Some(if byte & (1 << (offset % 8)) == 0 {
+            Coil::Off
+        } else {
+            Coil::On
+        })
+    }
+
+    /// Set the coil at `address`
+    ///
+    /// If the address isn't in the table, returns `Err(IllegalDataAddress)`.
+    pub fn set(&mut self, address: u16, value: Coil) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, 1)?;
+        let byte = &mut self.bytes.as_mut()[offset / 8];
+
+        match value {
+            Coil::On => *byte |= 1 << (offset % 8),
+            Coil::Off => *byte &= !(1 << (offset % 8)),
+        }
+
+        Ok(())
+    }
+
+    /// Copy `quantity` coils starting at `address` into `out`, packed as in a read response
+    ///
+    /// If any of the addresses aren't in the table, returns `Err(IllegalDataAddress)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `out` is shorter than `bytes_needed(quantity)`.
+    pub fn read(&self, address: u16, quantity: usize, out: &mut [u8]) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, quantity)?;
+        get_coils(self.bytes.as_ref(), offset, quantity, out);
+
+        Ok(())
+    }
+
+    /// Write `coils` starting at `address`
+    ///
+    /// If any of the addresses aren't in the table, returns `Err(IllegalDataAddress)` and leaves
+    /// the table unchanged.
+    pub fn write(&mut self, address: u16, coils: Coils) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, coils.len())?;
+        set_coils(self.bytes.as_mut(), offset, coils.len(), coils.bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl BitTable<Vec<u8>> {
+    /// Create a table of `len` coils starting at address `start`, all off
+    ///
+    /// Returns `None` if the table would run past address 65535.
+    pub fn with_len(start: u16, len: usize) -> Option<Self> {
+        Self::new(start, len, vec![0; bytes_needed(len)])
+    }
+
+    /// Change the number of coils in the table. New coils are off.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table would run past address 65535.
+    pub fn resize(&mut self, len: usize) {
+        assert!(usize::from(self.start) + len <= ADDRESS_SPACE);
+
+        // Clear the unused bits of the last byte we keep, so that new coils start off
+        let keep = len.min(self.len);
+        self.bytes.truncate(bytes_needed(keep));
+
+        if let (Some(last), true) = (self.bytes.last_mut(), !keep.is_multiple_of(8)) {
+            *last &= (1 << (keep % 8)) - 1;
+        }
+
+        self.bytes.resize(bytes_needed(len), 0);
+        self.len = len;
+    }
+}
+
+/// A table of holding registers or input registers
+///
+/// The table covers the addresses `start..start + len`. The storage `R` is usually a `u16` array
+/// for a fixed capacity, or a `Vec<u16>` with the `std` feature, which can be resized.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::server::RegisterTable;
+///
+/// // 4 registers at addresses 1000 to 1003, in storage for up to 8
+/// let mut registers = RegisterTable::new(1000, 4, [0; 8]).unwrap();
+///
+/// registers.set(1003, 0x1234).unwrap();
+///
+/// assert_eq!(registers.get(1003), Some(0x1234));
+/// assert_eq!(registers.get(1004), None);
+/// assert_eq!(registers.registers(), &[0, 0, 0, 0x1234]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RegisterTable<R> {
+    start: u16,
+    len: usize,
+    registers: R,
+}
+
+impl<R> RegisterTable<R>
+where
+    R: AsRef<[u16]> + AsMut<[u16]>,
+{
+    /// Create a table of `len` registers starting at address `start`, stored in `registers`
+    ///
+    /// Returns `None` if `registers` is too small to hold `len` registers, or if the table would
+    /// run past address 65535.
+    pub fn new(start: u16, len: usize, registers: R) -> Option<Self> {
+        if usize::from(start) + len > ADDRESS_SPACE || registers.as_ref().len() < len {
+            return None;
+        }
+
+        Some(RegisterTable {
+            start,
+            len,
+            registers,
+        })
+    }
+
+    /// The first address in the table
+    pub fn start(&self) -> u16 {
+        self.start
+    }
+
+    /// The number of registers in the table
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the table has no registers
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The registers, starting with the one at `start`
+    pub fn registers(&self) -> &[u16] {
+        &self.registers.as_ref()[..self.len]
+    }
+
+    /// The registers, starting with the one at `start`
+    pub fn registers_mut(&mut self) -> &mut [u16] {
+        &mut self.registers.as_mut()[..self.len]
+    }
+
+    /// Get the register at `address`, or `None` if it isn't in the table
+    pub fn get(&self, address: u16) -> Option<u16> {
+        let offset = table_offset(self.start, self.len, address, 1).ok()?;
+
+        Some(self.registers.as_ref()[offset])
+    }
+
+    /// Set the register at `address`
+    ///
+    /// If the address isn't in the table, returns `Err(IllegalDataAddress)`.
+    pub fn set(&mut self, address: u16, value: u16) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, 1)?;
+        self.registers.as_mut()[offset] = value;
+
+        Ok(())
+    }
+
+    /// Copy `out.len()` registers starting at `address` into `out`
+    ///
+    /// If any of the addresses aren't in the table, returns `Err(IllegalDataAddress)`.
+    pub fn read(&self, address: u16, out: &mut [u16]) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, out.len())?;
+        out.copy_from_slice(&self.registers.as_ref()[offset..offset + out.len()]);
+
+        Ok(())
+    }
+
+    /// Write `registers` starting at `address`
+    ///
+    /// If any of the addresses aren't in the table, returns `Err(IllegalDataAddress)` and leaves
+    /// the table unchanged.
+    pub fn write(&mut self, address: u16, registers: Registers) -> Result<(), ExceptionCode> {
+        let offset = table_offset(self.start, self.len, address, registers.len())?;
+        let target = &mut self.registers.as_mut()[offset..offset + registers.len()];
+
+        for (register, value) in target.iter_mut().zip(registers) {
+            *register = value;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl RegisterTable<Vec<u16>> {
+    /// Create a table of `len` registers starting at address `start`, all 0
+    ///
+    /// Returns `None` if the table would run past address 65535.
+    pub fn with_len(start: u16, len: usize) -> Option<Self> {
+        Self::new(start, len, vec![0; len])
+    }
+
+    /// Change the number of registers in the table. New registers are 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table would run past address 65535.
+    pub fn resize(&mut self, len: usize) {
+        assert!(usize::from(self.start) + len <= ADDRESS_SPACE);
+
+        self.registers.truncate(len.min(self.len));
+        self.registers.resize(len, 0);
+        self.len = len;
+    }
+}
+
+/// An in-memory MODBUS device holding the four data tables
+///
+/// Every function code that `ModbusDevice` supports is handled by reading or writing the
+/// tables. Requests for addresses outside of a table get `IllegalDataAddress`. Coils and
+/// discrete inputs are stored packed, so reading them copies the bits straight into the
+/// response.
+///
+/// With fixed-size arrays for storage, `DataBank` works without `std`. With the `std` feature,
+/// `Vec` storage lets the tables grow.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::server::*;
+///
+/// // Room for 64 coils and discrete inputs, and 16 of each kind of register
+/// let mut bank: DataBank<[u8; 8], [u16; 16]> = DataBank::new();
+///
+/// bank.input_registers.set(2, 0xABCD).unwrap();
+///
+/// let mut out = [0; 8];
+/// let length = process_request(&mut bank, &[0x04, 0x00, 0x02, 0x00, 0x01], &mut out).unwrap();
+///
+/// assert_eq!(&out[..length], &[0x04, 0x02, 0xAB, 0xCD]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DataBank<B, R> {
+    pub coils: BitTable<B>,
+    pub discrete_inputs: BitTable<B>,
+    pub holding_registers: RegisterTable<R>,
+    pub input_registers: RegisterTable<R>,
+}
+
+impl<const BYTES: usize, const REGISTERS: usize> DataBank<[u8; BYTES], [u16; REGISTERS]> {
+    /// Create a data bank with every table starting at address 0, using all of the storage
+    ///
+    /// Tables are cut short at address 65535.
+    pub fn new() -> Self {
+        let bits = (BYTES * 8).min(ADDRESS_SPACE);
+        let registers = REGISTERS.min(ADDRESS_SPACE);
+
+        // The lengths always fit in the storage and the address space, so these can't fail
+        DataBank {
+            coils: BitTable::new(0, bits, [0; BYTES]).unwrap(),
+            discrete_inputs: BitTable::new(0, bits, [0; BYTES]).unwrap(),
+            holding_registers: RegisterTable::new(0, registers, [0; REGISTERS]).unwrap(),
+            input_registers: RegisterTable::new(0, registers, [0; REGISTERS]).unwrap(),
+        }
+    }
+}
+
+impl<const BYTES: usize, const REGISTERS: usize> Default
+    for DataBank<[u8; BYTES], [u16; REGISTERS]>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for DataBank<Vec<u8>, Vec<u16>> {
+    /// Create a data bank with every table empty, starting at address 0
+    ///
+    /// Use `resize` on the tables to add addresses.
+    fn default() -> Self {
+        DataBank {
+            coils: BitTable::with_len(0, 0).unwrap(),
+            discrete_inputs: BitTable::with_len(0, 0).unwrap(),
+            holding_registers: RegisterTable::with_len(0, 0).unwrap(),
+            input_registers: RegisterTable::with_len(0, 0).unwrap(),
+        }
+    }
+}
+
+impl<B, R> ModbusDevice for DataBank<B, R>
+where
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    R: AsRef<[u16]> + AsMut<[u16]>,
+{
+    fn read_coils(&mut self, address: u16, coils: &mut [Coil]) -> Result<(), ExceptionCode> {
+        read_each(&self.coils, address, coils)
+    }
+
+    fn read_coils_packed(
+        &mut self,
+        address: u16,
+        quantity: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), ExceptionCode> {
+        self.coils.read(address, quantity.into(), bytes)
+    }
+
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        inputs: &mut [Coil],
+    ) -> Result<(), ExceptionCode> {
+        read_each(&self.discrete_inputs, address, inputs)
+    }
+
+    fn read_discrete_inputs_packed(
+        &mut self,
+        address: u16,
+        quantity: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), ExceptionCode> {
+        self.discrete_inputs.read(address, quantity.into(), bytes)
+    }
+
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.holding_registers.read(address, registers)
+    }
+
+    fn read_input_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        self.input_registers.read(address, registers)
+    }
+
+    fn write_single_coil(&mut self, address: u16, value: Coil) -> Result<(), ExceptionCode> {
+        self.coils.set(address, value)
+    }
+
+    fn write_single_register(&mut self, address: u16, value: u16) -> Result<(), ExceptionCode> {
+        self.holding_registers.set(address, value)
+    }
+
+    fn write_multiple_coils(&mut self, address: u16, coils: Coils) -> Result<(), ExceptionCode> {
+        self.coils.write(address, coils)
+    }
+
+    fn write_multiple_registers(
+        &mut self,
+        address: u16,
+        registers: Registers,
+    ) -> Result<(), ExceptionCode> {
+        self.holding_registers.write(address, registers)
+    }
+
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ExceptionCode> {
+        let current = self
+            .holding_registers
+            .get(address)
+            .ok_or(ExceptionCode::IllegalDataAddress)?;
+
+        self.holding_registers
+            .set(address, (current & and_mask) | (or_mask & !and_mask))
+    }
+
+    fn read_write_multiple_registers(
+        &mut self,
+        read_address: u16,
+        read_registers: &mut [u16],
+        write_address: u16,
+        write_registers: Registers,
+    ) -> Result<(), ExceptionCode> {
+        let table = &mut self.holding_registers;
+
+        // Check the read range first, so a failed request doesn't write anything
+        table_offset(table.start, table.len, read_address, read_registers.len())?;
+
+        table.write(write_address, write_registers)?;
+        table.read(read_address, read_registers)
+    }
+}
+
+/// Read coils from a table into a slice, one at a time
+fn read_each<B>(table: &BitTable<B>, address: u16, coils: &mut [Coil]) -> Result<(), ExceptionCode>
+where
+    B: AsRef<[u8]> + AsMut<[u8]>,
+{
+    table_offset(table.start, table.len, address, coils.len())?;
+
+    for (coil, address) in coils.iter_mut().zip(address..) {
+        // The range was checked above, so every address is in the table
+        *coil = table.get(address).unwrap_or(Coil::Off);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::server::process_request;
+    use crate::Coil::*;
+
+    fn process<B, R>(bank: &mut DataBank<B, R>, pdu: &[u8]) -> ([u8; 256], usize)
+    where
+        B: AsRef<[u8]> + AsMut<[u8]>,
+        R: AsRef<[u16]> + AsMut<[u16]>,
+    {
+        let mut out = [0; 256];
+        let length = process_request(bank, pdu, &mut out).unwrap();
+
+        (out, length)
+    }
+
+    #[test]
+    fn table_ranges() {
+        assert!(BitTable::new(0, 16, [0; 2]).is_some());
+        assert!(BitTable::new(0, 17, [0; 2]).is_none());
+        assert!(BitTable::new(0xFFF0, 16, [0; 2]).is_some());
+        assert!(BitTable::new(0xFFF1, 16, [0; 2]).is_none());
+
+        assert!(RegisterTable::new(10, 4, [0; 4]).is_some());
+        assert!(RegisterTable::new(10, 5, [0; 4]).is_none());
+        assert!(RegisterTable::new(0xFFFF, 2, [0; 4]).is_none());
+
+        let registers = RegisterTable::new(10, 4, [0; 4]).unwrap();
+        let mut out = [0; 2];
+
+        assert_eq!(registers.get(9), None);
+        assert_eq!(registers.get(10), Some(0));
+        assert_eq!(registers.get(13), Some(0));
+        assert_eq!(registers.get(14), None);
+        assert_eq!(registers.read(12, &mut out), Ok(()));
+        assert_eq!(
+            registers.read(13, &mut out),
+            Err(ExceptionCode::IllegalDataAddress)
+        );
+    }
+
+    #[test]
+    fn read_bits_packed() {
+        let mut bank = DataBank::<[u8; 4], [u16; 4]>::new();
+
+        for address in [1, 3, 9, 10, 11, 20].iter() {
+            bank.coils.set(*address, On).unwrap();
+        }
+
+        let (out, length) = process(&mut bank, &[0x01, 0x00, 0x00, 0x00, 0x0C]);
+        assert_eq!(&out[..length], &[0x01, 0x02, 0b0000_1010, 0b0000_1110]);
+
+        let (out, length) = process(&mut bank, &[0x01, 0x00, 0x03, 0x00, 0x09]);
+        assert_eq!(&out[..length], &[0x01, 0x02, 0b1100_0001, 0b0000_0001]);
+
+        // Past the end of the table
+        let (out, length) = process(&mut bank, &[0x01, 0x00, 0x1F, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x81, 0x02]);
+
+        bank.discrete_inputs.set(31, On).unwrap();
+
+        let (out, length) = process(&mut bank, &[0x02, 0x00, 0x1F, 0x00, 0x01]);
+        assert_eq!(&out[..length], &[0x02, 0x01, 0x01]);
+
+        let mut coils = [Off; 3];
+        assert_eq!(bank.read_coils(9, &mut coils), Ok(()));
+        assert_eq!(coils, [On, On, On]);
+    }
+
+    #[test]
+    fn write_bits() {
+        let mut bank = DataBank::<[u8; 4], [u16; 4]>::new();
+
+        let (out, length) = process(&mut bank, &[0x05, 0x00, 0x07, 0xFF, 0x00]);
+        assert_eq!(&out[..length], &[0x05, 0x00, 0x07, 0xFF, 0x00]);
+
+        let request = [0x0F, 0x00, 0x0E, 0x00, 0x04, 0x01, 0b0000_1011];
+        let (out, length) = process(&mut bank, &request);
+        assert_eq!(&out[..length], &request[..5]);
+
+        assert_eq!(
+            bank.coils.bytes(),
+            &[0b1000_0000, 0b1100_0000, 0b0000_0010, 0]
+        );
+
+        // Past the end of the table
+        let request = [0x0F, 0x00, 0x1E, 0x00, 0x04, 0x01, 0x0F];
+        let (out, length) = process(&mut bank, &request);
+        assert_eq!(&out[..length], &[0x8F, 0x02]);
+        assert_eq!(bank.coils.bytes()[3], 0);
+    }
+
+    #[test]
+    fn registers() {
+        let mut bank = DataBank {
+            coils: BitTable::new(0, 0, [0; 1]).unwrap(),
+            discrete_inputs: BitTable::new(0, 0, [0; 1]).unwrap(),
+            holding_registers: RegisterTable::new(100, 4, [0; 4]).unwrap(),
+            input_registers: RegisterTable::new(200, 2, [0x1111, 0x2222, 0, 0]).unwrap(),
+        };
+
+        let (out, length) = process(&mut bank, &[0x04, 0x00, 0xC8, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x04, 0x04, 0x11, 0x11, 0x22, 0x22]);
+
+        let (out, length) = process(&mut bank, &[0x04, 0x00, 0xC9, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x84, 0x02]);
+
+        let (out, length) = process(&mut bank, &[0x06, 0x00, 0x65, 0x12, 0x34]);
+        assert_eq!(&out[..length], &[0x06, 0x00, 0x65, 0x12, 0x34]);
+
+        let request = [0x10, 0x00, 0x66, 0x00, 0x02, 0x04, 0xAB, 0xCD, 0xEF, 0x01];
+        let (out, length) = process(&mut bank, &request);
+        assert_eq!(&out[..length], &request[..5]);
+        assert_eq!(
+            bank.holding_registers.registers(),
+            &[0, 0x1234, 0xABCD, 0xEF01]
+        );
+
+        // Clear the low byte, and set the lowest bit
+        let (out, length) = process(&mut bank, &[0x16, 0x00, 0x65, 0xFF, 0x00, 0x00, 0x01]);
+        assert_eq!(&out[..length], &[0x16, 0x00, 0x65, 0xFF, 0x00, 0x00, 0x01]);
+        assert_eq!(bank.holding_registers.get(101), Some(0x1201));
+
+        let (out, length) = process(&mut bank, &[0x16, 0x00, 0x64, 0xFF, 0x00, 0x00, 0x01]);
+        assert_eq!(&out[..length], &[0x16, 0x00, 0x64, 0xFF, 0x00, 0x00, 0x01]);
+        assert_eq!(bank.holding_registers.get(100), Some(0x0001));
+    }
+
+    #[test]
+    fn read_write_registers() {
+        let mut bank = DataBank::<[u8; 1], [u16; 4]>::new();
+
+        // Write 2 registers at 1, then read 3 at 0
+        let request = [
+            0x17, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x02, 0x04, 0x12, 0x34, 0x56, 0x78,
+        ];
+        let (out, length) = process(&mut bank, &request);
+        assert_eq!(
+            &out[..length],
+            &[0x17, 0x06, 0x00, 0x00, 0x12, 0x34, 0x56, 0x78]
+        );
+
+        // The read range is bad, so nothing is written
+        let request = [
+            0x17, 0x00, 0x03, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x02, 0xFF, 0xFF,
+        ];
+        let (out, length) = process(&mut bank, &request);
+        assert_eq!(&out[..length], &[0x97, 0x02]);
+        assert_eq!(bank.holding_registers.get(0), Some(0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn growable_tables() {
+        let mut bank = DataBank::<Vec<u8>, Vec<u16>>::default();
+
+        let (out, length) = process(&mut bank, &[0x01, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(&out[..length], &[0x81, 0x02]);
+
+        bank.coils.resize(12);
+        bank.coils.set(11, On).unwrap();
+        bank.coils.set(3, On).unwrap();
+
+        let (out, length) = process(&mut bank, &[0x01, 0x00, 0x00, 0x00, 0x0C]);
+        assert_eq!(&out[..length], &[0x01, 0x02, 0b0000_1000, 0b0000_1000]);
+
+        // Shrinking and growing again turns the removed coils off
+        bank.coils.resize(3);
+        assert_eq!(bank.coils.bytes(), &[0]);
+        bank.coils.resize(16);
+        assert_eq!(bank.coils.bytes(), &[0, 0]);
+
+        bank.holding_registers = RegisterTable::with_len(10, 2).unwrap();
+        bank.holding_registers.set(11, 7).unwrap();
+        bank.holding_registers.resize(1);
+        bank.holding_registers.resize(3);
+        assert_eq!(bank.holding_registers.registers(), &[0, 0, 0]);
+
+        assert!(BitTable::with_len(0xFFFF, 2).is_none());
+    }
+}
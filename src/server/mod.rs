@@ -0,0 +1,650 @@
+//! Tools for implementing a MODBUS server (slave)
+//!
+//! Implement `ModbusDevice` for your device's data, then pass each received packet to
+//! `dispatch`, which calls the right method and writes the response ADU. For simulators and
+//! test fixtures, `DataBank` implements `ModbusDevice` with plain in-memory tables.
+
+use crate::bit_pack::{bytes_needed, pack_coils};
+use crate::pdu::function_code::EXCEPTION_FLAG;
+use crate::pdu::{
+    Coils, Exception, ExceptionCode, Registers, Request, MAX_READ_BITS, MAX_READ_REGISTERS,
+    PDU_MAX_LENGTH,
+};
+use crate::protocols::ModbusProtocol;
+use crate::recv_buffer::Packet;
+use crate::{Coil, ModbusError};
+
+mod data_bank;
+
+pub use data_bank::{BitTable, DataBank, RegisterTable};
+
+/// The data held by a MODBUS server
+///
+/// Each method handles one function code. Addresses are the 0-based addresses from the PDU.
+/// `dispatch` has already checked that quantities are within the limits of the MODBUS
+/// specification, and that the addresses don't run past 65535.
+///
+/// Return an `ExceptionCode` to reject a request, usually `IllegalDataAddress` if some of the
+/// addresses don't exist. Every method returns `IllegalFunction` by default, so you only need to
+/// implement the function codes your device supports.
+pub trait ModbusDevice {
+    /// Read `coils.len()` coils, starting at `address` (function code 1)
+    fn read_coils(&mut self, address: u16, coils: &mut [Coil]) -> Result<(), ExceptionCode> {
+        let _ = (address, coils);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Read `inputs.len()` discrete inputs, starting at `address` (function code 2)
+    fn read_discrete_inputs(
+        &mut self,
+        address: u16,
+        inputs: &mut [Coil],
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, inputs);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Read `quantity` coils starting at `address`, packed into `bytes` as they appear in the
+    /// response (function code 1)
+    ///
+    /// `bytes` is exactly `bytes_needed(quantity)` long, and unused bits in the last byte must be
+    /// cleared. By default, this calls `read_coils` and packs the result. Override it if your
+    /// coils are already stored packed.
+    fn read_coils_packed(
+        &mut self,
+        address: u16,
+        quantity: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), ExceptionCode> {
+        read_unpacked(quantity, bytes, |coils| self.read_coils(address, coils))
+    }
+
+    /// Read `quantity` discrete inputs starting at `address`, packed into `bytes` as they appear
+    /// in the response (function code 2)
+    ///
+    /// This works like `read_coils_packed`, calling `read_discrete_inputs` by default.
+    fn read_discrete_inputs_packed(
+        &mut self,
+        address: u16,
+        quantity: u16,
+        bytes: &mut [u8],
+    ) -> Result<(), ExceptionCode> {
+        read_unpacked(quantity, bytes, |inputs| {
+            self.read_discrete_inputs(address, inputs)
+        })
+    }
+
+    /// Read `registers.len()` holding registers, starting at `address` (function code 3)
+    fn read_holding_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, registers);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Read `registers.len()` input registers, starting at `address` (function code 4)
+    fn read_input_registers(
+        &mut self,
+        address: u16,
+        registers: &mut [u16],
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, registers);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Write a single coil (function code 5)
+    fn write_single_coil(&mut self, address: u16, value: Coil) -> Result<(), ExceptionCode> {
+        let _ = (address, value);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Write a single holding register (function code 6)
+    fn write_single_register(&mut self, address: u16, value: u16) -> Result<(), ExceptionCode> {
+        let _ = (address, value);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Write consecutive coils, starting at `address` (function code 15)
+    fn write_multiple_coils(&mut self, address: u16, coils: Coils) -> Result<(), ExceptionCode> {
+        let _ = (address, coils);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Write consecutive holding registers, starting at `address` (function code 16)
+    fn write_multiple_registers(
+        &mut self,
+        address: u16,
+        registers: Registers,
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, registers);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Modify a holding register (function code 22)
+    ///
+    /// The new value is `(current & and_mask) | (or_mask & !and_mask)`.
+    fn mask_write_register(
+        &mut self,
+        address: u16,
+        and_mask: u16,
+        or_mask: u16,
+    ) -> Result<(), ExceptionCode> {
+        let _ = (address, and_mask, or_mask);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+
+    /// Write `write_registers` starting at `write_address`, then read `read_registers.len()`
+    /// holding registers starting at `read_address` (function code 23)
+    fn read_write_multiple_registers(
+        &mut self,
+        read_address: u16,
+        read_registers: &mut [u16],
+        write_address: u16,
+        write_registers: Registers,
+    ) -> Result<(), ExceptionCode> {
+        let _ = (read_address, read_registers, write_address, write_registers);
+
+        Err(ExceptionCode::IllegalFunction)
+    }
+}
+
+/// Handle a request packet, writing the response ADU into `out`. Returns the number of bytes
+/// written.
+///
+/// The response uses the request's header, so it has the same transaction ID, unit ID, or
+/// address. Requests that the device rejects, or that can't be handled at all, get an exception
+/// response:
+///
+/// - Unrecognized or unsupported function codes get `IllegalFunction`
+/// - Malformed requests (like a quantity out of range) get `IllegalDataValue`
+/// - Addresses that run past 65535 get `IllegalDataAddress`
+///
+/// Deciding whether to respond at all (for example, to a broadcast) is up to you.
+///
+/// If `out` is too small for the response, returns `Err(BufferFull)`.
+///
+/// # Examples
+///
+/// ```
+/// use modbus_core::protocols::*;
+/// use modbus_core::recv_buffer::*;
+/// use modbus_core::server::*;
+/// use modbus_core::pdu::ExceptionCode;
+///
+/// struct Counter(u16);
+///
+/// impl ModbusDevice for Counter {
+///     fn read_input_registers(
+///         &mut self,
+///         address: u16,
+///         registers: &mut [u16],
+///     ) -> Result<(), ExceptionCode> {
+///         if address != 0 || registers.len() != 1 {
+///             return Err(ExceptionCode::IllegalDataAddress);
+///         }
+///
+///         self.0 += 1;
+///         registers[0] = self.0;
+///
+///         Ok(())
+///     }
+/// }
+///
+/// let mut buf: RecvBuffer<ModbusRtu> = RecvBuffer::new();
+/// let (packet, _) = buf.process(&[0x11, 0x04, 0x00, 0x00, 0x00, 0x01, 0x33, 0x5a]).unwrap();
+///
+/// let mut out = [0; 256];
+/// let length = dispatch(&mut Counter(0), &packet, &mut out).unwrap();
+///
+/// assert_eq!(&out[..length], &[0x11, 0x04, 0x02, 0x00, 0x01, 0xb9, 0x33]);
+/// ```
+pub fn dispatch<P, D>(
+    device: &mut D,
+    packet: &Packet<P>,
+    out: &mut [u8],
+) -> Result<usize, ModbusError>
+where
+    P: ModbusProtocol,
+    D: ModbusDevice + ?Sized,
+{
+    let mut response = [0; PDU_MAX_LENGTH];
+    let length = process_request(device, packet.pdu, &mut response)?;
+
+    P::write_adu(&packet.header, &response[..length], out)
+}
+
+/// Handle a request PDU, writing the response PDU into `out`. Returns the number of bytes
+/// written.
+///
+/// This is the part of `dispatch` that doesn't depend on the transport protocol. If `out` is
+/// too small for the response, returns `Err(BufferFull)`.
+pub fn process_request<D>(device: &mut D, pdu: &[u8], out: &mut [u8]) -> Result<usize, ModbusError>
+where
+    D: ModbusDevice + ?Sized,
+{
+    use ModbusError::{BadLength, BufferFull};
+
+    let function_code = *pdu.first().ok_or(BadLength)?;
+
+    let result = match Request::parse(pdu) {
+        Ok(request) => Ok(request),
+        Err(e) => Err(request_exception(e).ok_or(e)?),
+    };
+
+    let mut response = [0; PDU_MAX_LENGTH];
+
+    let length = match result.and_then(|request| respond(device, request, pdu, &mut response)) {
+        Ok(length) => length,
+        Err(code) => Exception {
+            function_code: function_code & !EXCEPTION_FLAG,
+            code,
+        }
+        .write(&mut response)?,
+    };
+
+    let out = out.get_mut(..length).ok_or(BufferFull)?;
+    out.copy_from_slice(&response[..length]);
+
+    Ok(length)
+}
+
+/// The exception to respond with when a request fails to parse with `error`
+///
+/// Returns `None` for errors that can't come from parsing a request.
+pub(crate) fn request_exception(error: ModbusError) -> Option<ExceptionCode> {
+    use ModbusError::{BadFuncCode, BadLength, BadValue, ByteCountMismatch, QuantityOutOfRange};
+
+    match error {
+        BadFuncCode => Some(ExceptionCode::IllegalFunction),
+        BadLength | BadValue | QuantityOutOfRange { .. } | ByteCountMismatch { .. } => {
+            Some(ExceptionCode::IllegalDataValue)
+        }
+        _ => None,
+    }
+}
+
+/// Call the device, and write the response PDU
+///
+/// No response is longer than `PDU_MAX_LENGTH`, so writing it can't fail.
+fn respond<D>(
+    device: &mut D,
+    request: Request,
+    pdu: &[u8],
+    out: &mut [u8; PDU_MAX_LENGTH],
+) -> Result<usize, ExceptionCode>
+where
+    D: ModbusDevice + ?Sized,
+{
+    use ExceptionCode::IllegalFunction;
+
+    let function_code = request.function_code();
+
+    match request {
+        Request::ReadCoils { address, quantity } => {
+            check_range(address, quantity)?;
+
+            read_bits(function_code, quantity, out, |bytes| {
+                device.read_coils_packed(address, quantity, bytes)
+            })
+        }
+
+        Request::ReadDiscreteInputs { address, quantity } => {
+            check_range(address, quantity)?;
+
+            read_bits(function_code, quantity, out, |bytes| {
+                device.read_discrete_inputs_packed(address, quantity, bytes)
+            })
+        }
+
+        Request::ReadHoldingRegisters { address, quantity } => {
+            check_range(address, quantity)?;
+
+            read_registers(function_code, quantity, out, |registers| {
+                device.read_holding_registers(address, registers)
+            })
+        }
+
+        Request::ReadInputRegisters { address, quantity } => {
+            check_range(address, quantity)?;
+
+            read_registers(function_code, quantity, out, |registers| {
+                device.read_input_registers(address, registers)
+            })
+        }
+
+        Request::WriteSingleCoil { address, value } => {
+            device.write_single_coil(address, value)?;
+
+            echo(pdu, out)
+        }
+
+        Request::WriteSingleRegister { address, value } => {
+            device.write_single_register(address, value)?;
+
+            echo(pdu, out)
+        }
+
+        Request::WriteMultipleCoils { address, coils } => {
+            check_range(address, coils.len() as u16)?;
+            device.write_multiple_coils(address, coils)?;
+
+            // Function code, address, and quantity
+            echo(&pdu[..5], out)
+        }
+
+        Request::WriteMultipleRegisters { address, registers } => {
+            check_range(address, registers.len() as u16)?;
+            device.write_multiple_registers(address, registers)?;
+
+            // Function code, address, and quantity
+            echo(&pdu[..5], out)
+        }
+
+        Request::MaskWriteRegister {
+            address,
+            and_mask,
+            or_mask,
+        } => {
+            device.mask_write_register(address, and_mask, or_mask)?;
+
+            echo(pdu, out)
+        }
+
+        Request::ReadWriteMultipleRegisters {
+            read_address,
+            read_quantity,
+            write_address,
+            registers,
+        } => {
+            check_range(read_address, read_quantity)?;
+            check_range(write_address, registers.len() as u16)?;
+
+            read_registers(function_code, read_quantity, out, |read_registers| {
+                device.read_write_multiple_registers(
+                    read_address,
+                    read_registers,
+                    write_address,
+                    registers,
+                )
+            })
+        }
+
+        // Diagnostics and the other serial line functions aren't supported
+        _ => Err(IllegalFunction),
+    }
+}
+
+/// Checks that a range of addresses doesn't run past the end of the address space
+fn check_range(address: u16, quantity: u16) -> Result<(), ExceptionCode> {
+    if u32::from(address) + u32::from(quantity) > 0x1_0000 {
+        Err(ExceptionCode::IllegalDataAddress)
+    } else {
+        Ok(())
+    }
+}
+
+fn echo(pdu: &[u8], out: &mut [u8]) -> Result<usize, ExceptionCode> {
+    out[..pdu.len()].copy_from_slice(pdu);
+
+    Ok(pdu.len())
+}
+
+/// Write a coil or discrete input read response, using `read` to get the packed values
+fn read_bits<F>(
+    function_code: u8,
+    quantity: u16,
+    out: &mut [u8],
+    read: F,
+) -> Result<usize, ExceptionCode>
+where
+    F: FnOnce(&mut [u8]) -> Result<(), ExceptionCode>,
+{
+    let byte_count = bytes_needed(quantity as usize);
+
+    read(&mut out[2..2 + byte_count])?;
+
+    out[0] = function_code;
+    out[1] = byte_count as u8;
+
+    Ok(2 + byte_count)
+}
+
+/// Read coils or discrete inputs one by one using `read`, and pack them into `bytes`
+fn read_unpacked<F>(quantity: u16, bytes: &mut [u8], read: F) -> Result<(), ExceptionCode>
+where
+    F: FnOnce(&mut [Coil]) -> Result<(), ExceptionCode>,
+{
+    let mut coils = [Coil::Off; MAX_READ_BITS as usize];
+    let coils = &mut coils[..quantity as usize];
+
+    read(coils)?;
+    pack_coils(coils, bytes);
+
+    Ok(())
+}
+
+/// Write a register read response, using `read` to get the values
+fn read_registers<F>(
+    function_code: u8,
+    quantity: u16,
+    out: &mut [u8],
+    read: F,
+) -> Result<usize, ExceptionCode>
+where
+    F: FnOnce(&mut [u16]) -> Result<(), ExceptionCode>,
+{
+    let mut registers = [0; MAX_READ_REGISTERS as usize];
+    let registers = &mut registers[..quantity as usize];
+
+    read(registers)?;
+
+    let byte_count = 2 * registers.len();
+
+    out[0] = function_code;
+    out[1] = byte_count as u8;
+
+    for (chunk, register) in out[2..].chunks_exact_mut(2).zip(registers.iter()) {
+        chunk.copy_from_slice(&register.to_be_bytes());
+    }
+
+    Ok(2 + byte_count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::{ModbusRtu, TcpModbus};
+    use crate::recv_buffer::RecvBuffer;
+    use crate::test_data::*;
+
+    /// A device with 16 coils and 16 holding registers, starting at address 0
+    struct TestDevice {
+        coils: [Coil; 16],
+        registers: [u16; 16],
+    }
+
+    impl TestDevice {
+        fn new() -> Self {
+            let mut registers = [0; 16];
+
+            for (index, register) in registers.iter_mut().enumerate() {
+                *register = 0x0100 + index as u16;
+            }
+
+            TestDevice {
+                coils: [Coil::Off; 16],
+                registers,
+            }
+        }
+    }
+
+    fn range(address: u16, quantity: usize, len: usize) -> Result<usize, ExceptionCode> {
+        let start = address as usize;
+
+        if start + quantity <= len {
+            Ok(start)
+        } else {
+            Err(ExceptionCode::IllegalDataAddress)
+        }
+    }
+
+    impl ModbusDevice for TestDevice {
+        fn read_coils(&mut self, address: u16, coils: &mut [Coil]) -> Result<(), ExceptionCode> {
+            let start = range(address, coils.len(), self.coils.len())?;
+            coils.copy_from_slice(&self.coils[start..start + coils.len()]);
+
+            Ok(())
+        }
+
+        fn read_holding_registers(
+            &mut self,
+            address: u16,
+            registers: &mut [u16],
+        ) -> Result<(), ExceptionCode> {
+            let start = range(address, registers.len(), self.registers.len())?;
+            registers.copy_from_slice(&self.registers[start..start + registers.len()]);
+
+            Ok(())
+        }
+
+        fn write_single_coil(&mut self, address: u16, value: Coil) -> Result<(), ExceptionCode> {
+            let start = range(address, 1, self.coils.len())?;
+            self.coils[start] = value;
+
+            Ok(())
+        }
+
+        fn write_multiple_registers(
+            &mut self,
+            address: u16,
+            registers: Registers,
+        ) -> Result<(), ExceptionCode> {
+            let start = range(address, registers.len(), self.registers.len())?;
+
+            for (index, value) in registers.iter().enumerate() {
+                self.registers[start + index] = value;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn process(device: &mut TestDevice, pdu: &[u8]) -> ([u8; PDU_MAX_LENGTH], usize) {
+        let mut out = [0; PDU_MAX_LENGTH];
+        let length = process_request(device, pdu, &mut out).unwrap();
+
+        (out, length)
+    }
+
+    #[test]
+    fn read_registers() {
+        let mut device = TestDevice::new();
+
+        let (out, length) = process(&mut device, &[0x03, 0x00, 0x02, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x03, 0x04, 0x01, 0x02, 0x01, 0x03]);
+
+        let (out, length) = process(&mut device, &[0x03, 0x00, 0x0F, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x83, 0x02]);
+    }
+
+    #[test]
+    fn coils() {
+        let mut device = TestDevice::new();
+
+        let (out, length) = process(&mut device, &[0x05, 0x00, 0x09, 0xFF, 0x00]);
+        assert_eq!(&out[..length], &[0x05, 0x00, 0x09, 0xFF, 0x00]);
+        assert_eq!(device.coils[9], Coil::On);
+
+        let (out, length) = process(&mut device, &[0x01, 0x00, 0x08, 0x00, 0x03]);
+        assert_eq!(&out[..length], &[0x01, 0x01, 0b0000_0010]);
+    }
+
+    #[test]
+    fn write_registers() {
+        let mut device = TestDevice::new();
+
+        let request = [0x10, 0x00, 0x01, 0x00, 0x02, 0x04, 0xAB, 0xCD, 0x12, 0x34];
+        let (out, length) = process(&mut device, &request);
+
+        assert_eq!(&out[..length], &request[..5]);
+        assert_eq!(&device.registers[..4], &[0x0100, 0xABCD, 0x1234, 0x0103]);
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut device = TestDevice::new();
+
+        // Not implemented by the device
+        let (out, length) = process(&mut device, &[0x04, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(&out[..length], &[0x84, 0x01]);
+
+        // Not recognized at all
+        let (out, length) = process(&mut device, &[0x30, 0x00]);
+        assert_eq!(&out[..length], &[0xB0, 0x01]);
+
+        // Quantity out of range
+        let (out, length) = process(&mut device, &[0x03, 0x00, 0x00, 0x00, 0x7E]);
+        assert_eq!(&out[..length], &[0x83, 0x03]);
+
+        // Bad coil value
+        let (out, length) = process(&mut device, &[0x05, 0x00, 0x00, 0x12, 0x34]);
+        assert_eq!(&out[..length], &[0x85, 0x03]);
+
+        // Runs past the end of the address space
+        let (out, length) = process(&mut device, &[0x03, 0xFF, 0xFF, 0x00, 0x02]);
+        assert_eq!(&out[..length], &[0x83, 0x02]);
+
+        // Serial line functions aren't supported
+        let (out, length) = process(&mut device, &[0x07]);
+        assert_eq!(&out[..length], &[0x87, 0x01]);
+
+        let mut out = [0; 1];
+        assert_eq!(
+            process_request(&mut device, &[0x07], &mut out),
+            Err(ModbusError::BufferFull)
+        );
+    }
+
+    #[test]
+    fn dispatch_keeps_header() {
+        let mut device = TestDevice::new();
+        let mut out = [0; 300];
+
+        let mut buf = RecvBuffer::<ModbusRtu>::new();
+        let (packet, _) = buf.process(ADU3_RTU).unwrap();
+
+        // Registers 0x6B and up don't exist
+        let length = dispatch(&mut device, &packet, &mut out).unwrap();
+        let response = ModbusRtu::pdu_body(&out[..length]).unwrap();
+
+        assert_eq!(out[0], ADU3_HEADER.address);
+        assert_eq!(response, &[0x83, 0x02]);
+
+        let mut request = [0; 12];
+        TcpModbus::write_adu(&ADU2_HEADER, &[0x03, 0x00, 0x00, 0x00, 0x01], &mut request).unwrap();
+
+        let mut buf = RecvBuffer::<TcpModbus>::new();
+        let (packet, _) = buf.process(&request).unwrap();
+
+        let length = dispatch(&mut device, &packet, &mut out).unwrap();
+        let header = TcpModbus::adu_header(&out[..length]).unwrap();
+
+        assert_eq!(header.transaction_id, ADU2_HEADER.transaction_id);
+        assert_eq!(header.unit_id, ADU2_HEADER.unit_id);
+        assert_eq!(
+            TcpModbus::pdu_body(&out[..length]),
+            Ok(&[0x03, 0x02, 0x01, 0x00][..])
+        );
+    }
+}
@@ -0,0 +1,376 @@
+//! Blocking MODBUS transport over `std::io`
+//!
+//! `ModbusStream` reads packets from anything that implements `Read` and `Write`, like a
+//! `TcpStream` or an opened serial port, and writes ADUs back to it. It's meant for simple tools
+//! and tests; for anything with more than one connection, use `RecvBuffer` with your own I/O, or
+//! the `codec` module.
+//!
+//! This module needs the `std` feature.
+
+use crate::protocols::{AduBuffer, ModbusProtocol};
+use crate::recv_buffer::{Packet, RecvBuffer};
+use crate::{Direction, ModbusError};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// A blocking connection that sends and receives ADUs of a MODBUS protocol
+///
+/// # Examples
+///
+/// ```no_run
+/// use modbus_core::protocols::*;
+/// use modbus_core::stream::ModbusStream;
+/// use modbus_core::Direction;
+/// use std::net::TcpStream;
+/// use std::time::Duration;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let tcp = TcpStream::connect("192.168.1.10:502")?;
+/// let mut stream: ModbusStream<TcpModbus, _> =
+///     ModbusStream::with_direction(tcp, Direction::Response);
+/// stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+///
+/// let header = TcpModbusHeader {
+///     transaction_id: 1,
+///     protocol_id: 0,
+///     length: 0,
+///     unit_id: 1,
+/// };
+///
+/// // Read 2 holding registers at address 0x10
+/// stream.write_packet(&header, &[0x03, 0x00, 0x10, 0x00, 0x02])?;
+///
+/// let packet = stream.read_packet()?;
+/// println!("{:?}", packet);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ModbusStream<P: ModbusProtocol, T> {
+    inner: T,
+    buffer: RecvBuffer<P>,
+
+    // Bytes that have been read, but not given to the buffer yet
+    read: P::Buffer,
+    read_start: usize,
+    read_end: usize,
+
+    // The PDU of the last packet, which has to outlive the next call to `RecvBuffer::process`
+    pdu: P::Buffer,
+}
+
+impl<P: ModbusProtocol, T: Read + Write> ModbusStream<P, T> {
+    /// Create a stream that receives ADUs travelling in either direction
+    pub fn new(inner: T) -> Self {
+        Self::with_buffer(inner, RecvBuffer::new())
+    }
+
+    /// Create a stream that receives ADUs travelling in a known direction
+    ///
+    /// A server receives queries, and a client receives responses. See
+    /// `RecvBuffer::with_direction`.
+    pub fn with_direction(inner: T, direction: Direction) -> Self {
+        Self::with_buffer(inner, RecvBuffer::with_direction(direction))
+    }
+
+    /// Create a stream that receives ADUs through an existing buffer
+    pub fn with_buffer(inner: T, buffer: RecvBuffer<P>) -> Self {
+        ModbusStream {
+            inner,
+            buffer,
+            read: P::Buffer::zeroed(),
+            read_start: 0,
+            read_end: 0,
+            pdu: P::Buffer::zeroed(),
+        }
+    }
+
+    /// Read from the underlying stream until a complete packet has been received
+    ///
+    /// Data that arrives after the packet is kept for the next call. If a read fails, including
+    /// because of a timeout, the error is returned and anything already received is kept, so
+    /// calling this again carries on where it left off.
+    ///
+    /// An invalid frame is returned as an error with kind `InvalidData` (unless the buffer is in
+    /// hunt mode). Only the bad frame is thrown away: bytes read after it are kept for the next
+    /// call. If the stream ends before a complete packet, returns an error with kind
+    /// `UnexpectedEof`.
+    pub fn read_packet(&mut self) -> io::Result<Packet<'_, P>> {
+        let (header, length) = loop {
+            // The buffer is given one byte at a time, so that if a frame turns out to be invalid,
+            // anything after it is still here. Even with no new data, there can be leftover bytes
+            // in the buffer from hunt mode.
+            let data = &self.read.as_ref()[self.read_start..self.read_end];
+            let piece = &data[..core::cmp::min(data.len(), 1)];
+
+            match self.buffer.process(piece) {
+                Ok((packet, rest)) => {
+                    self.read_start += piece.len() - rest.len();
+                    self.pdu.as_mut()[..packet.pdu.len()].copy_from_slice(packet.pdu);
+
+                    break (packet.header, packet.pdu.len());
+                }
+
+                Err(ModbusError::NotEnoughData) if piece.len() < data.len() => {
+                    self.read_start += 1;
+                    continue;
+                }
+
+                // All of the data is in the buffer now
+                Err(ModbusError::NotEnoughData) => {
+                    self.read_start = 0;
+                    self.read_end = 0;
+                }
+
+                Err(e) => {
+                    self.read_start += piece.len();
+
+                    return Err(e.into());
+                }
+            }
+
+            self.read_end = loop {
+                match self.inner.read(self.read.as_mut()) {
+                    Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            };
+        };
+
+        Ok(Packet {
+            header,
+            pdu: &self.pdu.as_ref()[..length],
+        })
+    }
+
+    /// Wrap a PDU in an ADU, and write it to the underlying stream
+    ///
+    /// See `ModbusProtocol::write_adu` for how the header is used.
+    pub fn write_packet(&mut self, header: &P::Header, pdu: &[u8]) -> io::Result<()> {
+        P::write_adu_to(header, pdu, &mut self.inner)?;
+
+        self.inner.flush()
+    }
+}
+
+impl<P: ModbusProtocol, T> ModbusStream<P, T> {
+    /// The buffer used for receiving, for changing settings like hunt mode
+    pub fn buffer_mut(&mut self) -> &mut RecvBuffer<P> {
+        &mut self.buffer
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying stream
+    ///
+    /// Reading from it directly will lose data if it's in the middle of an ADU.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Get the underlying stream back
+    ///
+    /// Any data that has been received but not returned as a packet is lost.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<P: ModbusProtocol, T: ReadTimeout> ModbusStream<P, T> {
+    /// Set how long `read_packet` waits for each read from the underlying stream
+    ///
+    /// When a read times out, `read_packet` returns the error from the underlying stream (with
+    /// kind `WouldBlock` or `TimedOut`, depending on the platform). `None` waits forever.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(timeout)
+    }
+}
+
+/// Streams that can time out when reading
+///
+/// This is implemented for the standard library's sockets. Implement it for other streams (like
+/// serial ports) to use `ModbusStream::set_read_timeout` with them.
+pub trait ReadTimeout {
+    /// Set the read timeout of the stream. `None` means reads never time out.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl<T: ReadTimeout + ?Sized> ReadTimeout for &T {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        (**self).set_read_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocols::{ModbusRtu, TcpModbus};
+    use crate::test_data::*;
+    use std::io::Cursor;
+    use std::net::TcpListener;
+
+    /// An in-memory stream, which hands out its input a few bytes at a time
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+        chunk: usize,
+    }
+
+    impl Duplex {
+        fn new(input: &[u8], chunk: usize) -> Self {
+            Duplex {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+                chunk,
+            }
+        }
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let length = core::cmp::min(buf.len(), self.chunk);
+            self.input.read(&mut buf[..length])
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn read_packets() {
+        let data = [ADU1_TCP, ADU2_TCP, ADU1_TCP].concat();
+
+        for &chunk in &[1, 5, 300] {
+            let mut stream: ModbusStream<TcpModbus, _> =
+                ModbusStream::new(Duplex::new(&data, chunk));
+
+            let packet = stream.read_packet().unwrap();
+            assert_eq!(packet.header, ADU1_HEADER);
+            assert_eq!(packet.pdu, ADU1_PDU());
+
+            let packet = stream.read_packet().unwrap();
+            assert_eq!(packet.header, ADU2_HEADER);
+            assert_eq!(packet.pdu, ADU2_PDU());
+
+            let packet = stream.read_packet().unwrap();
+            assert_eq!(packet.header, ADU1_HEADER);
+
+            let error = stream.read_packet().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn read_errors() {
+        let mut data = [ADU3_RTU, ADU3_RTU].concat();
+        data[2] ^= 1;
+
+        // The good ADU has to survive even when it arrives in the same read as the bad one
+        for &chunk in &[4, 300] {
+            let mut stream: ModbusStream<ModbusRtu, _> =
+                ModbusStream::with_direction(Duplex::new(&data, chunk), Direction::Query);
+
+            let error = stream.read_packet().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+            let packet = stream.read_packet().unwrap();
+            assert_eq!(packet.header, ADU3_HEADER);
+            assert_eq!(packet.pdu, ADU3_PDU());
+        }
+
+        // In hunt mode, garbage is skipped instead
+        let data = [&[0xFF, 0xFF], ADU3_RTU, ADU3_RTU].concat();
+
+        let mut stream: ModbusStream<ModbusRtu, _> =
+            ModbusStream::with_direction(Duplex::new(&data, 300), Direction::Query);
+        stream.buffer_mut().set_hunt_mode(true);
+
+        assert_eq!(stream.read_packet().unwrap().header, ADU3_HEADER);
+        assert_eq!(stream.read_packet().unwrap().header, ADU3_HEADER);
+        assert_eq!(stream.buffer_mut().skipped(), 2);
+    }
+
+    #[test]
+    fn write_packets() {
+        let mut stream: ModbusStream<ModbusRtu, _> = ModbusStream::new(Duplex::new(&[], 1));
+
+        stream.write_packet(&ADU3_HEADER, ADU3_PDU()).unwrap();
+        stream.write_packet(&ADU4_HEADER, ADU4_PDU()).unwrap();
+
+        let error = stream.write_packet(&ADU3_HEADER, &[]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        assert_eq!(stream.into_inner().output, [ADU3_RTU, ADU4_RTU].concat());
+    }
+
+    #[test]
+    fn tcp_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = std::thread::spawn(move || {
+            let (tcp, _) = listener.accept().unwrap();
+            let mut stream: ModbusStream<TcpModbus, _> =
+                ModbusStream::with_direction(tcp, Direction::Query);
+
+            let packet = stream.read_packet().unwrap();
+            assert_eq!(packet.header, ADU2_HEADER);
+
+            // Answer with ADU1, in two parts
+            let tcp = stream.get_mut();
+            tcp.write_all(&ADU1_TCP[..10]).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+            tcp.write_all(&ADU1_TCP[10..]).unwrap();
+
+            // Wait for the client to time out
+            stream.read_packet().unwrap_err();
+        });
+
+        let tcp = TcpStream::connect(address).unwrap();
+        let mut stream: ModbusStream<TcpModbus, _> =
+            ModbusStream::with_direction(tcp, Direction::Response);
+        stream
+            .set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+
+        stream.write_packet(&ADU2_HEADER, ADU2_PDU()).unwrap();
+
+        let packet = stream.read_packet().unwrap();
+        assert_eq!(packet.header, ADU1_HEADER);
+        assert_eq!(packet.pdu, ADU1_PDU());
+
+        let error = stream.read_packet().unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+
+        drop(stream);
+        server.join().unwrap();
+    }
+}
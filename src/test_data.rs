@@ -2,7 +2,7 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
 
-use crate::protocols::TcpModbusHeader;
+use crate::protocols::{ModbusAsciiHeader, ModbusRtuHeader, TcpModbusHeader};
 use crate::Direction;
 
 // Frame 58925 from modbus.pcap
@@ -55,3 +55,50 @@ pub const ADU2_FUNC_CODE: u8 = 4;
 pub fn ADU2_PDU() -> &'static [u8] {
     &ADU2_TCP[7..]
 }
+
+// Read Holding Registers query from the MODBUS over serial line specification
+pub const ADU3_RTU: &[u8] = &[0x11, 0x03, 0x00, 0x6b, 0x00, 0x03, 0x76, 0x87];
+
+pub const ADU3_DIRECTION: Direction = Direction::Query;
+pub const ADU3_HEADER: ModbusRtuHeader = ModbusRtuHeader {
+    address: 0x11,
+    crc: 0x8776,
+};
+pub const ADU3_ADU_LENGTH: usize = 8;
+pub const ADU3_FUNC_CODE: u8 = 3;
+
+pub fn ADU3_PDU() -> &'static [u8] {
+    &ADU3_RTU[1..6]
+}
+
+// Read Holding Registers response to ADU3
+pub const ADU4_RTU: &[u8] = &[
+    0x11, 0x03, 0x06, 0xae, 0x41, 0x56, 0x52, 0x43, 0x40, 0x49, 0xad,
+];
+
+pub const ADU4_DIRECTION: Direction = Direction::Response;
+pub const ADU4_HEADER: ModbusRtuHeader = ModbusRtuHeader {
+    address: 0x11,
+    crc: 0xad49,
+};
+pub const ADU4_ADU_LENGTH: usize = 11;
+pub const ADU4_FUNC_CODE: u8 = 3;
+
+pub fn ADU4_PDU() -> &'static [u8] {
+    &ADU4_RTU[1..9]
+}
+
+// ADU3 sent over MODBUS ASCII instead of MODBUS RTU
+pub const ADU5_ASCII: &[u8] = b":1103006B00037E\r\n";
+
+pub const ADU5_DIRECTION: Direction = Direction::Query;
+pub const ADU5_HEADER: ModbusAsciiHeader = ModbusAsciiHeader {
+    address: 0x11,
+    lrc: 0x7e,
+};
+pub const ADU5_ADU_LENGTH: usize = 17;
+pub const ADU5_FUNC_CODE: u8 = 3;
+
+pub fn ADU5_PDU() -> &'static [u8] {
+    &[0x03, 0x00, 0x6b, 0x00, 0x03]
+}